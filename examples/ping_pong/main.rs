@@ -45,11 +45,11 @@ pub fn main() {
     match mode.as_str() {
         "single" => {
             println!("Running single thread");
-            experiment.run_single_thread();
+            experiment.run_single_thread().unwrap();
         }
         "multi" => {
             println!("Running multi thread");
-            experiment.run_multi_thread();
+            experiment.run_multi_thread().unwrap();
         }
         _ => {}
     }
@@ -61,5 +61,5 @@ fn create_experiment(experiment_path: &Path) -> Experiment {
         .with_dynamic_factory("agent", DynamicFactory::<AgentDynamic>::new());
     let observer_factory = ObserverFactoryStorage::new()
         .with_observer_factory("std_logger", ObserverFactory::<Logger>::new());
-    Experiment::new(&experiment_path, dynamic_factory, observer_factory)
+    Experiment::new(experiment_path, dynamic_factory, observer_factory)
 }
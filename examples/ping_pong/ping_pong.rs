@@ -1,12 +1,13 @@
 use exdsdevs::{
-    containers::{Bag, Msg, Value},
-    dynamic::Dynamic,
+    containers::{Bag, BagExt, Outputs, SimRng, Value},
+    dynamic::{ConfluentPolicy, Dynamic},
     model::{Resources, Structure},
     time::Time,
 };
-use rand::{rngs::StdRng, Rng};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Serialize, Deserialize)]
 enum State {
     WAITING,
@@ -32,7 +33,7 @@ impl Dynamic for AgentDynamic {
         _: Time,
         init_value: &Value,
         _: &Resources,
-        _: &mut StdRng,
+        _: &mut SimRng,
     ) {
         if let Some(state) = init_value.get("state") {
             self.state = serde_json::from_value::<State>(state.clone())
@@ -41,7 +42,7 @@ impl Dynamic for AgentDynamic {
         self.last_count = 0;
     }
 
-    fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut StdRng) {
+    fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
         self.state = match self.state {
             State::STRIKE => State::WAITING,
             State::WAITING => State::WAITING,
@@ -54,46 +55,33 @@ impl Dynamic for AgentDynamic {
         _: Time,
         _: Time,
         x_bag: &Bag,
-        _: &mut StdRng,
+        _: &mut SimRng,
     ) {
-        match self.state {
-            State::WAITING => {
-                self.state = State::STRIKE;
-
-                if let Some(msg) = x_bag.get(0) {
-                    if let Value::Number(count) = msg.value() {
-                        if let Some(c) = count.as_i64() {
-                            self.last_count = c + 1;
-                        }
-                    }
-                }
+        if let State::WAITING = self.state {
+            self.state = State::STRIKE;
+
+            if let Some(count) = x_bag.first_on("in").and_then(Value::as_i64) {
+                self.last_count = count + 1;
             }
-            _ => {}
         }
     }
 
-    fn confluent_transition(
-        &mut self,
-        model_structure: &mut Structure,
-        sim_time: Time,
-        x_bag: &Bag,
-        rng: &mut StdRng,
-    ) {
-        self.internal_transition(model_structure, sim_time, rng);
-        self.external_transition(model_structure, sim_time, Time::Value(0), x_bag, rng);
+    fn confluent_policy(&self) -> ConfluentPolicy {
+        ConfluentPolicy::InternalThenExternal
     }
 
-    fn output(&self, _atomic_model_structure: &Structure, _sim_time: Time) -> Bag {
+    fn output(&self, atomic_model_structure: &Structure, _sim_time: Time) -> Bag {
         match self.state {
             State::STRIKE => {
-                let msg = Msg::new("out", Value::from(self.last_count));
-                vec![msg]
+                let mut outputs = Outputs::with_ports(&atomic_model_structure.output_ports);
+                outputs.put("out", Value::from(self.last_count));
+                outputs.into()
             }
             _ => panic!("Unknown state"),
         }
     }
 
-    fn time_advance(&self, _atomic_model_structure: &Structure, rng: &mut StdRng) -> Time {
+    fn time_advance(&self, _atomic_model_structure: &Structure, rng: &mut SimRng) -> Time {
         match self.state {
             State::STRIKE => Time::Value(rng.gen_range(2..10i128)),
             State::WAITING => Time::Inf,
@@ -116,7 +104,7 @@ impl Dynamic for RootDynamic {
         Self
     }
 
-    fn time_advance(&self, _: &Structure, _: &mut StdRng) -> Time {
+    fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
         Time::Inf
     }
 
@@ -0,0 +1,199 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use std::mem::take;
+
+use serde_json::Map;
+
+use crate::{
+    containers::{Bag, Value},
+    model::Model,
+    observer::Observer,
+    time::Time,
+};
+
+/// Condition on the model state of a `BreakpointObserver`.
+pub type Predicate = Box<dyn Fn(&Value) -> bool + Send>;
+/// Called by a `BreakpointObserver` with every hit.
+pub type Callback = Box<dyn FnMut(&Value) + Send>;
+
+/// Checks a predicate on the model state after every transition and records
+/// the transitions where it holds, for debugging without full logs.
+///
+/// A hit is an object with the `MODEL`, `EVENT`, `TIME`, `TIME_NEXT` and
+/// `STATE` of the transition, and the input `BAG` of external and confluent
+/// transitions. The hits are the result of the observer, and are passed to the
+/// callback as they happen.
+#[derive(Default)]
+pub struct BreakpointObserver {
+    predicate: Option<Predicate>,
+    callback: Option<Callback>,
+    model_full_name: String,
+    x_bag: Bag,
+    hits: Vec<Value>,
+}
+
+impl BreakpointObserver {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The condition on the model state; without one the observer never
+    /// breaks.
+    pub fn with_predicate(mut self, predicate: Predicate) -> Self {
+        self.predicate = Some(predicate);
+        self
+    }
+
+    /// Called with every hit, e.g. to dump it or to stop in a debugger.
+    pub fn with_callback(mut self, callback: Callback) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    fn check(&mut self, model: &Model, event: &str, sim_time: Time, t_next: Time) {
+        let x_bag = take(&mut self.x_bag);
+        let predicate = match &self.predicate {
+            Some(predicate) => predicate,
+            None => return,
+        };
+        let state = model.state();
+        if !predicate(&state) {
+            return;
+        }
+        let mut hit = Map::new();
+        hit.extend([
+            (
+                "MODEL".to_owned(),
+                Value::String(self.model_full_name.clone()),
+            ),
+            ("EVENT".to_owned(), Value::String(event.to_owned())),
+            ("TIME".to_owned(), Value::from(&sim_time)),
+            ("TIME_NEXT".to_owned(), Value::from(&t_next)),
+            ("STATE".to_owned(), state),
+        ]);
+        if !x_bag.is_empty() {
+            hit.insert(
+                "BAG".to_owned(),
+                Value::Array(x_bag.iter().map(Value::from).collect()),
+            );
+        }
+        let hit = Value::Object(hit);
+        if let Some(callback) = &mut self.callback {
+            callback(&hit);
+        }
+        self.hits.push(hit);
+    }
+}
+
+impl Observer for BreakpointObserver {
+    fn new() -> Self {
+        BreakpointObserver::new()
+    }
+
+    fn init_observer(&mut self, init_config: &Value) {
+        if let Some(model_full_name) = init_config.get("model_full_name").and_then(Value::as_str) {
+            self.model_full_name = model_full_name.to_owned();
+        }
+        self.hits.clear();
+    }
+
+    fn after_internal_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {
+        self.check(model, "INTERNAL_TRANSITION", sim_time, t_next);
+    }
+
+    fn before_external_transition(
+        &mut self,
+        _model: &Model,
+        _sim_time: Time,
+        x_bag: &Bag,
+        _elapsed: Time,
+    ) {
+        if self.predicate.is_some() {
+            self.x_bag = x_bag.to_vec();
+        }
+    }
+
+    fn after_external_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {
+        self.check(model, "EXTERNAL_TRANSITION", sim_time, t_next);
+    }
+
+    fn after_external_mail_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {
+        self.check(model, "EXTERNAL_MAIL_TRANSITION", sim_time, t_next);
+    }
+
+    fn before_confluent_transition(
+        &mut self,
+        _model: &Model,
+        _sim_time: Time,
+        x_bag: &Bag,
+        _t_last: Time,
+    ) {
+        if self.predicate.is_some() {
+            self.x_bag = x_bag.to_vec();
+        }
+    }
+
+    fn after_confluent_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {
+        self.check(model, "CONFLUENT_TRANSITION", sim_time, t_next);
+    }
+
+    fn result(&self) -> Option<Value> {
+        Some(Value::Array(self.hits.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic::Dynamic;
+    use crate::test_utils::{build_root_observed, child_state, Clock};
+    use std::{
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn test_breakpoint() {
+        let dumped = Arc::new(Mutex::new(Vec::new()));
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(40),
+            &PathBuf::new(),
+            &|| {
+                let dumped = dumped.clone();
+                vec![
+                    Box::new(
+                        BreakpointObserver::new()
+                            .with_predicate(Box::new(|state| {
+                                state["events"].as_array().unwrap().len() >= 3
+                            }))
+                            .with_callback(Box::new(move |hit| {
+                                dumped.lock().unwrap().push(hit["TIME"].clone())
+                            })),
+                    ),
+                    Box::new(BreakpointObserver::new()),
+                ]
+            },
+        );
+        root.run().unwrap();
+
+        let events = child_state(&root, "clock")["events"].clone();
+        let events = events.as_array().unwrap();
+        let results = &root.results()["root/clock"];
+        let hits = results[0].as_array().unwrap();
+        assert_eq!(hits.len(), events.len() - 2);
+        assert_eq!(hits[0]["MODEL"], Value::from("root/clock"));
+        assert_eq!(hits[0]["EVENT"], Value::from("INTERNAL_TRANSITION"));
+        assert_eq!(hits[0]["TIME"], events[2]);
+        assert_eq!(hits[0]["STATE"]["events"].as_array().unwrap().len(), 3);
+        assert!(hits[0].get("BAG").is_none());
+        assert_eq!(*dumped.lock().unwrap(), events[2..].to_vec());
+        assert_eq!(results[1], Value::Array(Vec::new()));
+    }
+}
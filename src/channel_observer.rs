@@ -0,0 +1,404 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use std::{
+    mem::replace,
+    sync::{mpsc::Sender, Mutex},
+};
+
+use crate::{
+    containers::{Bag, Mail, Value},
+    factory::Factory,
+    model::Model,
+    observer::Observer,
+    time::Time,
+};
+
+/// The events a `ChannelObserver` sends, the records of a `Logger`. Every
+/// event names the model it comes from.
+#[derive(Debug, Clone)]
+pub enum SimEvent {
+    Init {
+        model: String,
+        init_time: Time,
+        init_value: Value,
+        init_state: Value,
+        t_next: Time,
+    },
+    Outputs {
+        model: String,
+        sim_time: Time,
+        bag: Bag,
+    },
+    InternalTransition {
+        model: String,
+        sim_time: Time,
+        from_state: Value,
+        to_state: Value,
+        t_next: Time,
+    },
+    ExternalMailTransition {
+        model: String,
+        sim_time: Time,
+        from_state: Value,
+        to_state: Value,
+        t_next: Time,
+        mail: Mail,
+        elapsed: Time,
+    },
+    ExternalTransition {
+        model: String,
+        sim_time: Time,
+        from_state: Value,
+        to_state: Value,
+        t_next: Time,
+        x_bag: Bag,
+        elapsed: Time,
+    },
+    ConfluentTransition {
+        model: String,
+        sim_time: Time,
+        from_state: Value,
+        to_state: Value,
+        t_next: Time,
+        x_bag: Bag,
+    },
+    AfterSubmodelsTransition {
+        model: String,
+        sim_time: Time,
+        state: Value,
+        t_next: Time,
+    },
+    Finish {
+        model: String,
+        sim_time: Time,
+    },
+}
+
+/// Transition started by a `before_*` hook, completed by the matching
+/// `after_*` hook.
+enum PendingTransition {
+    None,
+    Internal {
+        from_state: Value,
+    },
+    ExternalMail {
+        from_state: Value,
+        mail: Mail,
+        elapsed: Time,
+    },
+    External {
+        from_state: Value,
+        x_bag: Bag,
+        elapsed: Time,
+    },
+    Confluent {
+        from_state: Value,
+        x_bag: Bag,
+    },
+}
+
+/// Sends the events of a model down a channel, e.g. to a user interface
+/// thread, instead of writing them to a file. Events are dropped when the
+/// observer has no sender or the receiver is gone, the simulation goes on.
+pub struct ChannelObserver {
+    sender: Option<Sender<SimEvent>>,
+    model_full_name: String,
+    pending: PendingTransition,
+}
+
+impl ChannelObserver {
+    pub fn with_sender(mut self, sender: Sender<SimEvent>) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    fn send(&self, event: SimEvent) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(event);
+        }
+    }
+}
+
+impl Observer for ChannelObserver {
+    fn new() -> Self {
+        ChannelObserver {
+            sender: None,
+            model_full_name: String::new(),
+            pending: PendingTransition::None,
+        }
+    }
+
+    fn init_observer(&mut self, init_config: &Value) {
+        if let Some(model_full_name) = init_config.get("model_full_name").and_then(Value::as_str) {
+            self.model_full_name = model_full_name.to_owned();
+        }
+        self.pending = PendingTransition::None;
+    }
+
+    fn on_init(&mut self, model: &Model, init_time: Time, init_value: &Value, t_next: Time) {
+        self.send(SimEvent::Init {
+            model: self.model_full_name.clone(),
+            init_time,
+            init_value: init_value.clone(),
+            init_state: model.state(),
+            t_next,
+        });
+    }
+
+    fn on_outputs(&mut self, _model: &Model, sim_time: Time, bag: &Bag) {
+        self.send(SimEvent::Outputs {
+            model: self.model_full_name.clone(),
+            sim_time,
+            bag: bag.to_vec(),
+        });
+    }
+
+    fn before_internal_transition(&mut self, model: &Model, _sim_time: Time, _t_last: Time) {
+        self.pending = PendingTransition::Internal {
+            from_state: model.state(),
+        };
+    }
+
+    fn after_internal_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {
+        if let PendingTransition::Internal { from_state } =
+            replace(&mut self.pending, PendingTransition::None)
+        {
+            self.send(SimEvent::InternalTransition {
+                model: self.model_full_name.clone(),
+                sim_time,
+                from_state,
+                to_state: model.state(),
+                t_next,
+            });
+        }
+    }
+
+    fn before_external_transition(
+        &mut self,
+        model: &Model,
+        _sim_time: Time,
+        x_bag: &Bag,
+        elapsed: Time,
+    ) {
+        self.pending = PendingTransition::External {
+            from_state: model.state(),
+            x_bag: x_bag.to_vec(),
+            elapsed,
+        };
+    }
+
+    fn after_external_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {
+        if let PendingTransition::External {
+            from_state,
+            x_bag,
+            elapsed,
+        } = replace(&mut self.pending, PendingTransition::None)
+        {
+            self.send(SimEvent::ExternalTransition {
+                model: self.model_full_name.clone(),
+                sim_time,
+                from_state,
+                to_state: model.state(),
+                t_next,
+                x_bag,
+                elapsed,
+            });
+        }
+    }
+
+    fn before_external_mail_transition(
+        &mut self,
+        model: &Model,
+        _sim_time: Time,
+        mail: &Mail,
+        elapsed: Time,
+    ) {
+        self.pending = PendingTransition::ExternalMail {
+            from_state: model.state(),
+            mail: mail.to_vec(),
+            elapsed,
+        };
+    }
+
+    fn after_external_mail_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {
+        if let PendingTransition::ExternalMail {
+            from_state,
+            mail,
+            elapsed,
+        } = replace(&mut self.pending, PendingTransition::None)
+        {
+            self.send(SimEvent::ExternalMailTransition {
+                model: self.model_full_name.clone(),
+                sim_time,
+                from_state,
+                to_state: model.state(),
+                t_next,
+                mail,
+                elapsed,
+            });
+        }
+    }
+
+    fn before_confluent_transition(
+        &mut self,
+        model: &Model,
+        _sim_time: Time,
+        x_bag: &Bag,
+        _t_last: Time,
+    ) {
+        self.pending = PendingTransition::Confluent {
+            from_state: model.state(),
+            x_bag: x_bag.to_vec(),
+        };
+    }
+
+    fn after_confluent_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {
+        if let PendingTransition::Confluent { from_state, x_bag } =
+            replace(&mut self.pending, PendingTransition::None)
+        {
+            self.send(SimEvent::ConfluentTransition {
+                model: self.model_full_name.clone(),
+                sim_time,
+                from_state,
+                to_state: model.state(),
+                t_next,
+                x_bag,
+            });
+        }
+    }
+
+    fn after_submodels_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {
+        self.send(SimEvent::AfterSubmodelsTransition {
+            model: self.model_full_name.clone(),
+            sim_time,
+            state: model.state(),
+            t_next,
+        });
+    }
+
+    fn after_finish(&mut self, _model: &Model, sim_time: Time) {
+        self.send(SimEvent::Finish {
+            model: self.model_full_name.clone(),
+            sim_time,
+        });
+    }
+}
+
+/// Creates `ChannelObserver`s sending to the same channel, so the models of
+/// every iteration of an experiment report to one receiver.
+pub struct ChannelObserverFactory {
+    sender: Mutex<Sender<SimEvent>>,
+}
+
+impl ChannelObserverFactory {
+    pub fn new(sender: Sender<SimEvent>) -> Self {
+        ChannelObserverFactory {
+            sender: Mutex::new(sender),
+        }
+    }
+}
+
+impl Factory for ChannelObserverFactory {
+    type Item = Box<dyn Observer>;
+
+    fn create(&self) -> Self::Item {
+        let sender = self.sender.lock().unwrap().clone();
+        Box::new(ChannelObserver::new().with_sender(sender))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic::Dynamic;
+    use crate::observer::ObserverFactoryStorage;
+    use crate::root_simulator::TerminationReason;
+    use crate::test_utils::{build_root_observed, Clock, Passive};
+    use std::{path::PathBuf, sync::mpsc};
+
+    #[test]
+    fn test_events_are_sent() {
+        let (sender, receiver) = mpsc::channel();
+        let observer_factory = ObserverFactoryStorage::new()
+            .with_observer_factory("channel", ChannelObserverFactory::new(sender));
+        let mut root = build_root_observed(
+            vec![
+                ("clock", Box::new(Clock::new())),
+                ("passive", Box::new(Passive::new())),
+            ],
+            Time::Value(10),
+            &PathBuf::new(),
+            &|| vec![observer_factory.get_observer("channel").unwrap()],
+        );
+        root.run().unwrap();
+        drop(root);
+        drop(observer_factory);
+
+        let events: Vec<SimEvent> = receiver.iter().collect();
+        let inits: Vec<&str> = events
+            .iter()
+            .filter_map(|event| match event {
+                SimEvent::Init { model, .. } => Some(model.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(inits, vec!["root/clock", "root/passive"]);
+        let mut transitions = 0;
+        for event in &events {
+            if let SimEvent::InternalTransition {
+                model,
+                sim_time,
+                from_state,
+                to_state,
+                ..
+            } = event
+            {
+                assert_eq!(model, "root/clock");
+                assert_eq!(
+                    to_state["events"].as_array().unwrap().last(),
+                    Some(&Value::from(sim_time))
+                );
+                assert_eq!(
+                    from_state["events"].as_array().unwrap().len() + 1,
+                    to_state["events"].as_array().unwrap().len()
+                );
+                transitions += 1;
+            }
+        }
+        assert!(transitions > 0);
+        assert!(matches!(
+            events.last(),
+            Some(SimEvent::Finish { sim_time, .. }) if *sim_time == Time::Value(10)
+        ));
+    }
+
+    #[test]
+    fn test_events_without_sender_are_dropped() {
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(10),
+            &PathBuf::new(),
+            &|| vec![Box::new(ChannelObserver::new())],
+        );
+        assert_eq!(root.run().unwrap(), TerminationReason::ReachedFinishTime);
+    }
+
+    #[test]
+    fn test_events_without_receiver_are_dropped() {
+        let (sender, receiver) = mpsc::channel();
+        drop(receiver);
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(10),
+            &PathBuf::new(),
+            &|| vec![Box::new(ChannelObserver::new().with_sender(sender.clone()))],
+        );
+        root.run().unwrap();
+    }
+}
@@ -6,12 +6,19 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms
 
+use serde::{de::DeserializeOwned, Serialize};
 use serde_json::Map;
-use std::rc::Rc;
+use std::{fmt, sync::Arc};
+
+use crate::errors::ExdsdevsError;
 
 pub type Bag = Vec<Msg>;
 pub type Mail = Vec<MailItem>;
 pub type Value = serde_json::Value;
+/// Random generator of the models. Every signature uses this alias, so the
+/// generator can be changed here only. It is pinned to ChaCha12, unlike
+/// `StdRng`, so that its stream can be saved in checkpoints.
+pub type SimRng = rand_chacha::ChaCha12Rng;
 
 #[derive(Debug, Clone)]
 pub struct MailItem {
@@ -19,29 +26,156 @@ pub struct MailItem {
     pub y_bag: Bag,
 }
 
+/// A message on a port. The value is reference counted, so routing a message
+/// to several destinations does not copy it, and bags can be sent to other
+/// threads.
 #[derive(Debug, Clone)]
 pub struct Msg {
     pub(crate) port: String,
-    pub(crate) value: Rc<Value>,
+    pub(crate) value: Arc<Value>,
 }
 
 impl Msg {
     pub fn new(port: &str, value: Value) -> Self {
         Self {
             port: port.to_owned(),
-            value: Rc::new(value),
+            value: Arc::new(value),
         }
     }
 
+    /// Creates a message with `payload` serialized into the value.
+    pub fn typed<T: Serialize>(port: &str, payload: &T) -> Result<Self, ExdsdevsError> {
+        serde_json::to_value(payload)
+            .map(|value| Self::new(port, value))
+            .map_err(|err| ExdsdevsError::ErrorMessagePayload(format!("port '{}': {}", port, err)))
+    }
+
     pub fn value(&self) -> &Value {
         &self.value
     }
 
+    /// Deserializes the value into `T`.
+    pub fn value_as<T: DeserializeOwned>(&self) -> Result<T, ExdsdevsError> {
+        T::deserialize(self.value.as_ref()).map_err(|err| {
+            ExdsdevsError::ErrorMessagePayload(format!("port '{}': {}", self.port, err))
+        })
+    }
+
     pub fn port(&self) -> &str {
         &self.port
     }
 }
 
+/// Renders as `port=value`, the value as compact JSON.
+impl fmt::Display for Msg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.port, self.value)
+    }
+}
+
+/// Renders as `model: [port=value, ...]`.
+impl fmt::Display for MailItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.model_name, self.y_bag.to_pretty_string())
+    }
+}
+
+/// Port lookups on a [`Bag`].
+pub trait BagExt {
+    /// Values of the messages received on `port`, in arrival order.
+    fn values_on<'a>(&'a self, port: &'a str) -> Box<dyn Iterator<Item = &'a Value> + 'a>;
+
+    fn first_on(&self, port: &str) -> Option<&Value>;
+
+    /// The messages as `[port=value, ...]`, for debugging.
+    fn to_pretty_string(&self) -> String;
+}
+
+impl BagExt for Bag {
+    fn values_on<'a>(&'a self, port: &'a str) -> Box<dyn Iterator<Item = &'a Value> + 'a> {
+        Box::new(
+            self.iter()
+                .filter(move |msg| msg.port == port)
+                .map(Msg::value),
+        )
+    }
+
+    fn first_on(&self, port: &str) -> Option<&Value> {
+        self.iter().find(|msg| msg.port == port).map(Msg::value)
+    }
+
+    fn to_pretty_string(&self) -> String {
+        let msgs: Vec<String> = self.iter().map(Msg::to_string).collect();
+        format!("[{}]", msgs.join(", "))
+    }
+}
+
+/// Collects the output messages of a dynamic.
+///
+/// When created with [`Outputs::with_ports`] every message is checked against
+/// the declared output ports, so a misspelled port is reported instead of the
+/// message being silently dropped by the couplings.
+#[derive(Debug, Default)]
+pub struct Outputs<'a> {
+    bag: Bag,
+    output_ports: Option<&'a [String]>,
+}
+
+impl<'a> Outputs<'a> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_ports(output_ports: &'a [String]) -> Self {
+        Self {
+            bag: Bag::new(),
+            output_ports: Some(output_ports),
+        }
+    }
+
+    fn check_port(&self, port: &str) -> Result<(), ExdsdevsError> {
+        match self.output_ports {
+            Some(output_ports) if !output_ports.iter().any(|output_port| output_port == port) => {
+                Err(ExdsdevsError::ErrorModelValidation(format!(
+                    "Unknown output port '{}', declared ports are {:?}",
+                    port, output_ports
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Appends a message, panics if the port is not declared.
+    pub fn put(&mut self, port: &str, value: Value) {
+        self.try_put(port, value)
+            .unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_put(&mut self, port: &str, value: Value) -> Result<(), ExdsdevsError> {
+        self.check_port(port)?;
+        self.bag.push(Msg::new(port, value));
+        Ok(())
+    }
+
+    /// Appends a message for each value, panics if the port is not declared.
+    pub fn put_many(&mut self, port: &str, values: impl IntoIterator<Item = Value>) {
+        self.check_port(port)
+            .unwrap_or_else(|err| panic!("{}", err));
+        self.bag
+            .extend(values.into_iter().map(|value| Msg::new(port, value)));
+    }
+
+    pub fn into_bag(self) -> Bag {
+        self.bag
+    }
+}
+
+impl From<Outputs<'_>> for Bag {
+    fn from(outputs: Outputs<'_>) -> Self {
+        outputs.into_bag()
+    }
+}
+
 impl From<&MailItem> for Value {
     fn from(mail: &MailItem) -> Self {
         let msg_vec: Vec<Value> = mail.y_bag.iter().map(Value::from).collect();
@@ -59,3 +193,102 @@ impl From<&Msg> for Value {
         Value::Object(val_map)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bags_are_send() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Bag>();
+        assert_send_sync::<Mail>();
+
+        let msg = Msg::new("out", Value::from(1));
+        let routed = msg.clone();
+        assert!(Arc::ptr_eq(&msg.value, &routed.value));
+        let handle = std::thread::spawn(move || routed.value().clone());
+        assert_eq!(handle.join().unwrap(), Value::from(1));
+    }
+
+    #[test]
+    fn test_pretty_printing() {
+        let bag = vec![
+            Msg::new("out", Value::from(1)),
+            Msg::new("log", serde_json::json!({"level": "info"})),
+        ];
+        assert_eq!(bag[0].to_string(), "out=1");
+        assert_eq!(bag.to_pretty_string(), "[out=1, log={\"level\":\"info\"}]");
+        assert_eq!(Bag::new().to_pretty_string(), "[]");
+        let mail_item = MailItem {
+            model_name: "root/a".to_owned(),
+            y_bag: bag,
+        };
+        assert_eq!(
+            mail_item.to_string(),
+            "root/a: [out=1, log={\"level\":\"info\"}]"
+        );
+    }
+
+    #[test]
+    fn test_outputs() {
+        let output_ports = vec!["out".to_owned()];
+        let mut outputs = Outputs::with_ports(&output_ports);
+        outputs.put("out", Value::from(1));
+        outputs.put_many("out", vec![Value::from(2), Value::from(3)]);
+        assert_eq!(
+            outputs.try_put("uot", Value::from(4)),
+            Err(ExdsdevsError::ErrorModelValidation(
+                "Unknown output port 'uot', declared ports are [\"out\"]".to_owned()
+            ))
+        );
+        let bag: Bag = outputs.into();
+        let values: Vec<&Value> = bag.iter().map(Msg::value).collect();
+        assert_eq!(
+            values,
+            vec![&Value::from(1), &Value::from(2), &Value::from(3)]
+        );
+        assert!(bag.iter().all(|msg| msg.port() == "out"));
+
+        let mut outputs = Outputs::new();
+        outputs.put("any", Value::Null);
+        assert_eq!(outputs.into_bag().len(), 1);
+    }
+
+    #[test]
+    fn test_typed_msg() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Ball {
+            count: i64,
+        }
+
+        let msg = Msg::typed("out", &Ball { count: 7 }).unwrap();
+        assert_eq!(msg.value(), &serde_json::json!({"count": 7}));
+        assert_eq!(msg.value_as::<Ball>(), Ok(Ball { count: 7 }));
+        assert!(matches!(
+            msg.value_as::<i64>(),
+            Err(ExdsdevsError::ErrorMessagePayload(_))
+        ));
+        assert_eq!(Msg::new("in", Value::from(3)).value_as::<i64>(), Ok(3));
+    }
+
+    #[test]
+    fn test_bag_ext() {
+        let bag = vec![
+            Msg::new("a", Value::from(1)),
+            Msg::new("b", Value::from(2)),
+            Msg::new("a", Value::from(3)),
+        ];
+        let values: Vec<&Value> = bag.values_on("a").collect();
+        assert_eq!(values, vec![&Value::from(1), &Value::from(3)]);
+        assert_eq!(bag.first_on("b"), Some(&Value::from(2)));
+        assert_eq!(bag.first_on("c"), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown output port 'uot'")]
+    fn test_outputs_put_unknown_port() {
+        let output_ports = vec!["out".to_owned()];
+        Outputs::with_ports(&output_ports).put("uot", Value::Null);
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use std::{
+    collections::BTreeMap,
+    fs::{DirBuilder, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use crate::{containers::Value, model::Model, observer::Observer, time::Time};
+
+/// Writes one CSV row per state change of a model with the columns `TIME`,
+/// `EVENT`, `MODEL` and the model state flattened into `STATE.<key>...`
+/// columns, nested objects giving dotted keys and arrays a JSON cell.
+///
+/// The rows are streamed to `<sim_dir>/<model_full_name>.csv`, the same layout
+/// as `Logger`. The header is written on the first event and takes its state
+/// columns from that state: a key appearing only later is not written, a
+/// missing one gives an empty cell.
+#[derive(Default)]
+pub struct CsvLogger {
+    model_full_name: String,
+    file_path: Option<PathBuf>,
+    stream: Option<BufWriter<File>>,
+    state_columns: Vec<String>,
+}
+
+impl Observer for CsvLogger {
+    fn new() -> CsvLogger {
+        CsvLogger::new()
+    }
+
+    fn init_observer(&mut self, config: &Value) {
+        let sim_dir = config.get("sim_dir").unwrap().as_str().unwrap();
+        let model_path = config.get("model_full_name").unwrap().as_str().unwrap();
+        let mut file_path = PathBuf::from_str(sim_dir).unwrap();
+        file_path.push(model_path);
+        self.model_full_name = model_path.to_owned();
+        self.close();
+        self.file_path = Some(file_path.with_extension("csv"));
+        self.state_columns.clear();
+    }
+
+    fn on_init(&mut self, model: &Model, init_time: Time, _init_value: &Value, _t_next: Time) {
+        self.record(init_time, "INIT", model);
+    }
+
+    fn after_internal_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.record(sim_time, "INTERNAL_TRANSITION", model);
+    }
+
+    fn after_external_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.record(sim_time, "EXTERNAL_TRANSITION", model);
+    }
+
+    fn after_external_mail_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.record(sim_time, "EXTERNAL_MAIL_TRANSITION", model);
+    }
+
+    fn after_confluent_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.record(sim_time, "CONFLUENT_TRANSITION", model);
+    }
+
+    fn after_submodels_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.record(sim_time, "AFTER_SUBMODELS_TRANSITION", model);
+    }
+
+    fn after_finish(&mut self, _model: &Model, _sim_time: Time) {
+        self.close();
+    }
+}
+
+impl CsvLogger {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn record(&mut self, sim_time: Time, event: &'static str, model: &Model) {
+        let mut state = BTreeMap::new();
+        Self::flatten("STATE", &model.state(), &mut state);
+        if self.stream.is_none() && !self.open(&state) {
+            return;
+        }
+        let row: Vec<String> = [sim_time.to_string(), event.to_owned()]
+            .iter()
+            .chain(std::iter::once(&self.model_full_name))
+            .map(|field| Self::escape(field))
+            .chain(self.state_columns.iter().map(|column| {
+                state
+                    .get(column)
+                    .map(|field| Self::escape(field))
+                    .unwrap_or_default()
+            }))
+            .collect();
+        writeln!(self.stream.as_mut().unwrap(), "{}", row.join(",")).unwrap();
+    }
+
+    /// Creates the file and writes the header with the columns of `state`,
+    /// the flattened state of the first event. Returns false when the logger
+    /// was not initialized.
+    fn open(&mut self, state: &BTreeMap<String, String>) -> bool {
+        let file_path = match &self.file_path {
+            Some(file_path) => file_path,
+            None => return false,
+        };
+        let file_dir = file_path.parent().unwrap();
+        if !file_dir.exists() {
+            DirBuilder::new().recursive(true).create(file_dir).unwrap()
+        }
+        let mut stream = BufWriter::new(File::create(file_path).unwrap());
+        self.state_columns = state.keys().cloned().collect();
+        let header: Vec<String> = ["TIME", "EVENT", "MODEL"]
+            .iter()
+            .map(|column| column.to_string())
+            .chain(self.state_columns.iter().cloned())
+            .map(|column| Self::escape(&column))
+            .collect();
+        writeln!(stream, "{}", header.join(",")).unwrap();
+        self.stream = Some(stream);
+        true
+    }
+
+    /// Flattens nested objects into dotted keys, arrays are kept as JSON.
+    fn flatten(prefix: &str, value: &Value, columns: &mut BTreeMap<String, String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    Self::flatten(&format!("{}.{}", prefix, key), value, columns);
+                }
+            }
+            Value::String(string) => {
+                columns.insert(prefix.to_owned(), string.clone());
+            }
+            Value::Null => {
+                columns.insert(prefix.to_owned(), String::new());
+            }
+            value => {
+                columns.insert(prefix.to_owned(), value.to_string());
+            }
+        }
+    }
+
+    fn escape(field: &str) -> String {
+        if field.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_owned()
+        }
+    }
+
+    /// Flushes the file; events after it are not logged.
+    fn close(&mut self) {
+        self.file_path = None;
+        if let Some(mut stream) = self.stream.take() {
+            stream.flush().unwrap();
+        }
+    }
+}
+
+impl Drop for CsvLogger {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::read_to_string;
+
+    use super::*;
+    use crate::dynamic::Dynamic;
+    use crate::test_utils::{build_root_observed, temp_dir, Clock};
+
+    #[test]
+    fn test_flatten() {
+        let mut columns = BTreeMap::new();
+        let state = serde_json::json!({"a": {"b": 1, "c": [true, "x,y"]}, "d": null, "e": "z"});
+        CsvLogger::flatten("STATE", &state, &mut columns);
+        assert_eq!(columns.len(), 4);
+        assert_eq!(columns["STATE.a.b"], "1");
+        assert_eq!(columns["STATE.a.c"], "[true,\"x,y\"]");
+        assert_eq!(columns["STATE.d"], "");
+        assert_eq!(columns["STATE.e"], "z");
+        assert_eq!(CsvLogger::escape("x,y"), "\"x,y\"");
+        assert_eq!(CsvLogger::escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_csv_logger() {
+        let sim_dir = temp_dir("csv_logger");
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(10),
+            &sim_dir,
+            &|| vec![Box::new(CsvLogger::new())],
+        );
+        let csv_path = sim_dir.join("root/clock.csv");
+        // The rows are streamed, the file is created on the init event.
+        assert!(csv_path.exists());
+        root.run().unwrap();
+        let csv = read_to_string(&csv_path).unwrap();
+        std::fs::remove_dir_all(&sim_dir).unwrap();
+
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("TIME,EVENT,MODEL,STATE.events,STATE.t_next")
+        );
+        let first = lines.next().unwrap();
+        assert!(first.starts_with("0,INIT,root/clock,[],"), "{}", first);
+        let rows: Vec<&str> = lines.collect();
+        assert!(!rows.is_empty());
+        assert!(rows
+            .iter()
+            .all(|line| line.contains(",INTERNAL_TRANSITION,root/clock,")));
+        // The events array is one quoted cell.
+        assert!(rows[1].contains(",\"["), "{}", rows[1]);
+    }
+}
@@ -9,15 +9,22 @@
 #![allow(unused_variables)]
 use std::{collections::BTreeMap, marker::PhantomData};
 
-use rand::rngs::StdRng;
-
 use crate::{
-    containers::{Bag, Mail, Value},
+    containers::{Bag, Mail, SimRng, Value},
+    errors::ExdsdevsError,
     factory::Factory,
-    model::{Resources, Structure},
+    model::{Resources, Structure, StructureChange},
     time::Time,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfluentPolicy {
+    InternalThenExternal,
+    ExternalThenInternal,
+    /// The dynamic implements `confluent_transition` itself.
+    Custom,
+}
+
 #[allow(unused_variables)]
 pub trait Dynamic {
     fn new() -> Self
@@ -26,13 +33,17 @@ pub trait Dynamic {
 
     fn dynamic_type(&self) -> String;
 
+    /// `init_value` is the init variant selected for the model in the current
+    /// experiment iteration, on top of the `default_init` of its model class.
+    /// A model built `with_restore_from_init` gets its init value through
+    /// `restore_state` instead; by default `init` does nothing.
     fn init(
         &mut self,
         model_structure: &mut Structure,
         init_time: Time,
         init_value: &Value,
         resources: &Resources,
-        rng: &mut StdRng,
+        rng: &mut SimRng,
     ) {
     }
 
@@ -40,7 +51,7 @@ pub trait Dynamic {
         &mut self,
         model_structure: &mut Structure,
         sim_time: Time,
-        rng: &mut StdRng,
+        rng: &mut SimRng,
     ) {
     }
 
@@ -50,7 +61,7 @@ pub trait Dynamic {
         sim_time: Time,
         elapsed: Time,
         x_bag: &Bag,
-        rng: &mut StdRng,
+        rng: &mut SimRng,
     ) {
     }
 
@@ -60,40 +71,109 @@ pub trait Dynamic {
         sim_time: Time,
         elapsed: Time,
         mail: &Mail,
-        rng: &mut StdRng,
+        rng: &mut SimRng,
     ) {
     }
 
-    /// # Variants:
-    /// `self.internal_transition(model_structure, sim_time);`
-    ///
-    /// `self.external_transition(model_structure, sim_time, Time::Value(0), x_bag);`
-    ///
-    ///
-    /// or:
-    ///
-    /// `self.external_transition(model_structure, sim_time, Time::Value(0), x_bag);`
-    ///
-    /// `self.internal_transition(model_structure, sim_time);`
-    ///
+    /// Order of the transitions performed by the default `confluent_transition`.
+    fn confluent_policy(&self) -> ConfluentPolicy {
+        ConfluentPolicy::Custom
+    }
+
+    /// By default performs `internal_transition` and `external_transition` in
+    /// the order given by `confluent_policy`. `elapsed` is the time since the
+    /// last transition of the model; the external transition gets it when it
+    /// comes first and a zero elapsed time after the internal transition.
+    /// Dynamics with the `Custom` policy must implement it.
     fn confluent_transition(
         &mut self,
         model_structure: &mut Structure,
         sim_time: Time,
+        elapsed: Time,
         x_bag: &Bag,
-        rng: &mut StdRng,
+        rng: &mut SimRng,
     ) {
-        unimplemented!("Simulation reaches confluent_transition, but it is not implemented!")
+        match self.confluent_policy() {
+            ConfluentPolicy::InternalThenExternal => {
+                self.internal_transition(model_structure, sim_time, rng);
+                self.external_transition(model_structure, sim_time, Time::Value(0), x_bag, rng);
+            }
+            ConfluentPolicy::ExternalThenInternal => {
+                self.external_transition(model_structure, sim_time, elapsed, x_bag, rng);
+                self.internal_transition(model_structure, sim_time, rng);
+            }
+            ConfluentPolicy::Custom => {
+                unimplemented!(
+                    "Simulation reaches confluent_transition, but it is not implemented!"
+                )
+            }
+        }
     }
 
     fn output(&self, model_structure: &Structure, sim_time: Time) -> Bag {
         Bag::new()
     }
 
-    fn time_advance(&self, model_structure: &Structure, rng: &mut StdRng) -> Time;
+    /// Called by the simulator instead of `output`, with the time elapsed since
+    /// the last transition of the model. Defaults to `output`.
+    fn output_with_elapsed(
+        &self,
+        model_structure: &Structure,
+        sim_time: Time,
+        elapsed: Time,
+    ) -> Bag {
+        self.output(model_structure, sim_time)
+    }
+
+    /// Outputs reacting to the messages `x_bag` the model has just received, for
+    /// memoryless transducers whose outputs depend on their inputs. Called
+    /// after every external and confluent transition; a non-empty bag is
+    /// emitted in a zero-time event right after the current one, where the
+    /// model makes no internal transition and `output` is not called. The
+    /// internal event of the model is postponed to that event when due at
+    /// the same time. Defaults to no output.
+    fn output_with_input(&self, model_structure: &Structure, sim_time: Time, x_bag: &Bag) -> Bag {
+        Bag::new()
+    }
+
+    /// Returning `Time::StopSim` ends the whole simulation after the current
+    /// event: `RootSimulator::run` calls `finish` at the current time.
+    fn time_advance(&self, model_structure: &Structure, rng: &mut SimRng) -> Time;
 
     fn state(&self) -> Value;
 
+    /// Checks invariants of the dynamic, e.g. of its configuration; called by
+    /// `Model::check` for every model of the hierarchy.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Rebuilds the dynamic from a value previously returned by `state()`.
+    ///
+    /// Implementations must keep the round-trip invariant: calling
+    /// `restore_state(&self.state())` leaves the dynamic unchanged. This is what
+    /// checkpoint restore relies on. Fails when `state` is not a state of the
+    /// dynamic.
+    fn restore_state(&mut self, state: &Value) -> Result<(), ExdsdevsError> {
+        Ok(())
+    }
+
+    /// Puts the dynamic back in the state it had before its first `init`, so
+    /// that a simulator reruns without rebuilding its model tree, see
+    /// `Simulator::reset`. `initial_state` is what `state()` returned then.
+    /// A dynamic implementing `restore_state` can pass it on, a stateless one
+    /// has nothing to do; by default the dynamic cannot be reset and `reset`
+    /// fails.
+    fn reset(&mut self, initial_state: &Value) -> Result<(), String> {
+        Err("the dynamic does not implement Dynamic::reset".to_owned())
+    }
+
+    /// Structural edits to apply to the coupled model, drained by the
+    /// simulator after every transition of the model.
+    fn structure_changes(&mut self) -> Vec<StructureChange> {
+        Vec::new()
+    }
+
     fn finish(&self, sim_time: Time) {}
 }
 
@@ -126,7 +206,9 @@ impl DynamicFactoryStorage {
         Default::default()
     }
 
-    fn add_dynamic_factory<T: Dynamic + 'static>(
+    /// Registers the dynamic `T` under `dynamic_class_name`, the
+    /// `dynamic_type` used by model classes to refer to it.
+    pub fn add_dynamic_factory<T: Dynamic + 'static>(
         &mut self,
         dynamic_class_name: &str,
         dynamic_factory: DynamicFactory<T>,
@@ -144,14 +226,263 @@ impl DynamicFactoryStorage {
         self
     }
 
-    pub fn get_dynamic(&self, dynamic_class: &str) -> Result<Box<dyn Dynamic>, String> {
+    /// Creates a new dynamic registered under `dynamic_class`.
+    pub fn get_dynamic(&self, dynamic_class: &str) -> Result<Box<dyn Dynamic>, ExdsdevsError> {
         if let Some(dynamic_factory) = self.factories.get(dynamic_class) {
             Ok(dynamic_factory.create())
         } else {
-            Err(format!(
-                "The Dynamic '{}' have not been registered",
-                dynamic_class
-            ))
+            Err(ExdsdevsError::ErrorBuildSimulator(format!(
+                "the dynamic '{}' has not been registered, registered dynamics are {:?}",
+                dynamic_class,
+                self.dynamic_classes()
+            )))
+        }
+    }
+
+    /// Names of the registered dynamics, sorted.
+    pub fn dynamic_classes(&self) -> Vec<&str> {
+        self.factories.keys().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::{
+        model::Model,
+        root_simulator::RootSimulator,
+        simulator::Simulator,
+        test_utils::{atomic_structure, build_root, child_state, root_model},
+    };
+
+    struct Counter {
+        count: i64,
+    }
+
+    impl Dynamic for Counter {
+        fn new() -> Self {
+            Counter { count: 0 }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "counter".to_owned()
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Inf
+        }
+
+        fn state(&self) -> Value {
+            Value::from(self.count)
+        }
+
+        fn restore_state(&mut self, state: &Value) -> Result<(), ExdsdevsError> {
+            self.count = state.as_i64().unwrap();
+            Ok(())
+        }
+    }
+
+    fn init_counter(init_value: &Value) -> Counter {
+        let mut structure = Structure::new(&[], &[], BTreeMap::new(), &[], &[], &[]);
+        let mut counter = Counter::new();
+        counter.init(
+            &mut structure,
+            Time::Value(0),
+            init_value,
+            &Resources::default(),
+            &mut SimRng::seed_from_u64(0),
+        );
+        counter
+    }
+
+    #[test]
+    fn test_dynamic_factory_storage() {
+        let mut storage = DynamicFactoryStorage::new();
+        storage.add_dynamic_factory("counter", DynamicFactory::<Counter>::new());
+        assert_eq!(
+            storage.get_dynamic("counter").unwrap().dynamic_type(),
+            "counter"
+        );
+        assert_eq!(storage.dynamic_classes(), vec!["counter"]);
+        assert_eq!(
+            storage.get_dynamic("agent").err().unwrap().to_string(),
+            "Simulator build error: the dynamic 'agent' has not been registered, \
+             registered dynamics are [\"counter\"]"
+        );
+    }
+
+    #[test]
+    fn test_init_ignores_init_value() {
+        // Not a serialized state of the counter, e.g. an empty `default_init`.
+        assert_eq!(init_counter(&serde_json::json!({})).state(), Value::from(0));
+        assert_eq!(init_counter(&Value::Null).state(), Value::from(0));
+    }
+
+    struct Ordered {
+        policy: ConfluentPolicy,
+        calls: Vec<&'static str>,
+        elapsed: Option<Time>,
+    }
+
+    impl Dynamic for Ordered {
+        fn new() -> Self {
+            Ordered {
+                policy: ConfluentPolicy::Custom,
+                calls: Vec::new(),
+                elapsed: None,
+            }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "ordered".to_owned()
+        }
+
+        fn confluent_policy(&self) -> ConfluentPolicy {
+            self.policy
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
+            self.calls.push("internal");
+        }
+
+        fn external_transition(
+            &mut self,
+            _: &Structure,
+            _: Time,
+            elapsed: Time,
+            _: &Bag,
+            _: &mut SimRng,
+        ) {
+            self.elapsed = Some(elapsed);
+            self.calls.push("external");
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Inf
+        }
+
+        fn state(&self) -> Value {
+            Value::Null
+        }
+    }
+
+    /// The transitions made by a confluent transition 3 time units after the
+    /// last one, and the elapsed time given to the external transition.
+    fn confluent_calls(policy: ConfluentPolicy) -> (Vec<&'static str>, Option<Time>) {
+        let mut structure = Structure::new(&[], &[], BTreeMap::new(), &[], &[], &[]);
+        let mut ordered = Ordered::new();
+        ordered.policy = policy;
+        ordered.confluent_transition(
+            &mut structure,
+            Time::Value(4),
+            Time::Value(3),
+            &Bag::new(),
+            &mut SimRng::seed_from_u64(0),
+        );
+        (ordered.calls, ordered.elapsed)
+    }
+
+    #[test]
+    fn test_confluent_policy() {
+        assert_eq!(
+            confluent_calls(ConfluentPolicy::InternalThenExternal),
+            (vec!["internal", "external"], Some(Time::Value(0)))
+        );
+        assert_eq!(
+            confluent_calls(ConfluentPolicy::ExternalThenInternal),
+            (vec!["external", "internal"], Some(Time::Value(3)))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "not implemented")]
+    fn test_confluent_policy_custom() {
+        confluent_calls(ConfluentPolicy::Custom);
+    }
+
+    #[test]
+    fn test_output_with_elapsed() {
+        /// Fires every 3 time units and remembers the elapsed time of its last output.
+        struct Periodic {
+            last_elapsed: Cell<Option<Time>>,
         }
+
+        impl Dynamic for Periodic {
+            fn new() -> Self {
+                Periodic {
+                    last_elapsed: Cell::new(None),
+                }
+            }
+
+            fn dynamic_type(&self) -> String {
+                "periodic".to_owned()
+            }
+
+            fn output_with_elapsed(&self, _: &Structure, _: Time, elapsed: Time) -> Bag {
+                self.last_elapsed.set(Some(elapsed));
+                Bag::new()
+            }
+
+            fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+                Time::Value(3)
+            }
+
+            fn state(&self) -> Value {
+                self.last_elapsed
+                    .get()
+                    .map_or(Value::Null, |elapsed| Value::from(&elapsed))
+            }
+        }
+
+        let mut root = build_root(
+            vec![("periodic", Box::new(Periodic::new()))],
+            Time::Value(10),
+        );
+        root.run().unwrap();
+        assert_eq!(child_state(&root, "periodic"), Value::from(3));
+    }
+
+    #[test]
+    fn test_restore_state_round_trip() {
+        let mut counter = Counter::new();
+        counter.restore_state(&Value::from(3)).unwrap();
+        let state = counter.state();
+        assert_eq!(state, Value::from(3));
+        counter.restore_state(&state).unwrap();
+        assert_eq!(counter.state(), state);
+    }
+
+    #[test]
+    fn test_restore_from_init() {
+        let counter = || Model::new(atomic_structure(&[]), Box::new(Counter::new()));
+        let model = root_model(
+            vec![
+                ("ignored", counter()),
+                ("restored", counter().with_restore_from_init(true)),
+                ("null", counter().with_restore_from_init(true)),
+            ],
+            &[],
+            Box::new(Counter::new()),
+        );
+        let simulator = Simulator::new("root", model, Resources::default());
+        let init_variant = [
+            ("root", Value::Null),
+            ("root/ignored", Value::from(5)),
+            ("root/restored", Value::from(5)),
+            ("root/null", Value::Null),
+        ]
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect();
+        let mut root = RootSimulator::from_simulator(simulator, Time::Value(0), Time::Inf);
+        root.init_static(&Default::default(), &init_variant, 0);
+        root.init();
+        assert_eq!(child_state(&root, "ignored"), Value::from(0));
+        assert_eq!(child_state(&root, "restored"), Value::from(5));
+        assert_eq!(child_state(&root, "null"), Value::from(0));
     }
 }
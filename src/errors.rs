@@ -0,0 +1,137 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use std::{error::Error, fmt::Display, io, mem, path::PathBuf, sync::Arc};
+
+#[derive(Debug, Clone)]
+pub enum ExdsdevsError {
+    ErrorModelValidation(String),
+    ErrorSimTime(String),
+    /// The IO error, with the file it happened on when known.
+    ErrorIo(Option<PathBuf>, Arc<io::Error>),
+    /// The JSON error, with the file being parsed when known.
+    ErrorParseJson(Option<PathBuf>, Arc<serde_json::Error>),
+    ErrorCheckpoint(String),
+    ErrorMessagePayload(String),
+    ErrorBuildSimulator(String),
+    ErrorCartesian(String),
+    /// The invalid trace error, with the trace file when known.
+    ErrorTrace(Option<PathBuf>, String),
+}
+
+impl Display for ExdsdevsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ErrorModelValidation(msg) => write!(f, "Model validation error: {}", msg),
+            Self::ErrorSimTime(msg) => write!(f, "Simulation time error: {}", msg),
+            Self::ErrorIo(Some(path), err) => write!(f, "IO error: {}: {}", path.display(), err),
+            Self::ErrorIo(None, err) => write!(f, "IO error: {}", err),
+            Self::ErrorParseJson(Some(path), err) => {
+                write!(f, "JSON parsing error: {}: {}", path.display(), err)
+            }
+            Self::ErrorParseJson(None, err) => write!(f, "JSON parsing error: {}", err),
+            Self::ErrorCheckpoint(msg) => write!(f, "Checkpoint error: {}", msg),
+            Self::ErrorMessagePayload(msg) => write!(f, "Message payload error: {}", msg),
+            Self::ErrorBuildSimulator(msg) => write!(f, "Simulator build error: {}", msg),
+            Self::ErrorCartesian(msg) => write!(f, "Init variants error: {}", msg),
+            Self::ErrorTrace(Some(path), msg) => {
+                write!(f, "Trace error: {}: {}", path.display(), msg)
+            }
+            Self::ErrorTrace(None, msg) => write!(f, "Trace error: {}", msg),
+        }
+    }
+}
+
+/// The wrapped IO and JSON errors are compared by kind and message.
+impl PartialEq for ExdsdevsError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::ErrorIo(_, err), Self::ErrorIo(_, other_err))
+                if err.kind() != other_err.kind() =>
+            {
+                false
+            }
+            _ => {
+                mem::discriminant(self) == mem::discriminant(other)
+                    && self.to_string() == other.to_string()
+            }
+        }
+    }
+}
+
+impl Error for ExdsdevsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ErrorIo(_, err) => Some(err.as_ref()),
+            Self::ErrorParseJson(_, err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ExdsdevsError {
+    fn from(err: io::Error) -> Self {
+        Self::ErrorIo(None, Arc::new(err))
+    }
+}
+
+impl From<serde_json::Error> for ExdsdevsError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::ErrorParseJson(None, Arc::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boxed_error() {
+        let read = || -> Result<(), Box<dyn Error>> {
+            Err(ExdsdevsError::from(io::Error::new(
+                io::ErrorKind::NotFound,
+                "experiment.json",
+            )))?;
+            Ok(())
+        };
+        let err = read().unwrap_err();
+        assert_eq!(err.to_string(), "IO error: experiment.json");
+        let err = err.downcast_ref::<ExdsdevsError>().unwrap();
+        assert!(
+            matches!(err, ExdsdevsError::ErrorIo(None, io_err) if io_err.kind() == io::ErrorKind::NotFound)
+        );
+        assert_eq!(err.source().unwrap().to_string(), "experiment.json");
+    }
+
+    #[test]
+    fn test_source_details() {
+        let json_err = serde_json::from_str::<serde_json::Value>("{\n  \"a\": }").unwrap_err();
+        let err =
+            ExdsdevsError::ErrorParseJson(Some(PathBuf::from("model.json")), Arc::new(json_err));
+        match &err {
+            ExdsdevsError::ErrorParseJson(_, json_err) => {
+                assert_eq!((json_err.line(), json_err.column()), (2, 8));
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(
+            err.to_string(),
+            "JSON parsing error: model.json: expected value at line 2 column 8"
+        );
+        assert!(err.source().unwrap().is::<serde_json::Error>());
+        assert_eq!(err.clone(), err);
+
+        let not_found = ExdsdevsError::from(io::Error::new(io::ErrorKind::NotFound, "denied"));
+        let denied = ExdsdevsError::from(io::Error::new(io::ErrorKind::PermissionDenied, "denied"));
+        assert_ne!(not_found, denied);
+        assert_eq!(
+            not_found,
+            ExdsdevsError::from(io::Error::new(io::ErrorKind::NotFound, "denied"))
+        );
+    }
+}
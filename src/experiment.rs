@@ -7,24 +7,333 @@
 // except according to those terms
 
 use std::{
-    collections::{BTreeMap, VecDeque},
-    fs::read_to_string,
+    cell::Cell,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    convert::TryFrom,
     path::{Path, PathBuf},
     str::FromStr,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc, Arc,
+    },
+    time::{Duration, Instant},
 };
 
 use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use threadpool::ThreadPool;
 
 use crate::{
     containers::Value,
     dynamic::DynamicFactoryStorage,
+    errors::ExdsdevsError,
     model::{ModelClass, ModelFactory},
     observer::ObserverFactoryStorage,
     root_simulator::RootSimulator,
     time::Time,
+    utils::{
+        cartesian_product, read_json_from_file, results_to_json, splitmix64, MAX_CARTESIAN_PRODUCT,
+    },
 };
 
+/// How the random seed of an iteration is derived from the `random_seed` of
+/// the experiment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedStrategy {
+    /// `random_seed + iteration`, the seeds of the versions before
+    /// `SeedStrategy` was introduced.
+    Sequential,
+    /// `random_seed` and `iteration` hashed together with SplitMix64, so the
+    /// seeds of consecutive iterations are unrelated.
+    Mixed,
+}
+
+impl Default for SeedStrategy {
+    fn default() -> Self {
+        SeedStrategy::Mixed
+    }
+}
+
+impl SeedStrategy {
+    pub fn iteration_seed(self, random_seed: u64, iteration: u64) -> u64 {
+        match self {
+            SeedStrategy::Sequential => random_seed.wrapping_add(iteration),
+            SeedStrategy::Mixed => splitmix64(splitmix64(random_seed) ^ iteration),
+        }
+    }
+}
+
+/// Observer results of one iteration, as returned by `RootSimulator::results`.
+pub type IterationResults = BTreeMap<String, Vec<Value>>;
+
+/// Aggregates the results of the iterations of an experiment.
+///
+/// In multi-thread runs the iterations are passed in order of completion.
+pub trait ResultsAnalyzer {
+    fn add_iteration(&mut self, var_number: u64, iteration: u64, results: &IterationResults);
+    fn analyze(&self) -> Value;
+
+    /// Current estimate of the quantity studied for the variant `var_number`,
+    /// checked by a `ConvergenceCriterion` after every iteration. `None` while
+    /// there is no estimate, or for analyzers that do not make one.
+    fn running_estimate(&self, var_number: u64) -> Option<f64> {
+        let _ = var_number;
+        None
+    }
+}
+
+/// Stops the iterations of a variant once the running estimate of the
+/// analyzer is stable: when it changed by less than `tolerance`, relative to
+/// its last value, over the last `window` iterations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConvergenceCriterion {
+    pub window: usize,
+    pub tolerance: f64,
+}
+
+impl ConvergenceCriterion {
+    pub fn new(window: usize, tolerance: f64) -> Self {
+        Self { window, tolerance }
+    }
+
+    /// Checks the estimates made after each iteration, in order.
+    pub fn is_converged(&self, estimates: &[f64]) -> bool {
+        if self.window == 0 || estimates.len() <= self.window {
+            return false;
+        }
+        let last = estimates[estimates.len() - 1];
+        let scale = if last == 0.0 { 1.0 } else { last.abs() };
+        estimates[estimates.len() - 1 - self.window..]
+            .iter()
+            .all(|estimate| (estimate - last).abs() / scale < self.tolerance)
+    }
+}
+
+/// Keeps the results of every iteration and returns them as an array of
+/// `{"VARIANT", "ITERATION", "RESULTS"}` objects ordered by variant and iteration.
+#[derive(Debug, Default)]
+pub struct CollectAllAnalyzer {
+    results: BTreeMap<(u64, u64), Value>,
+}
+
+impl CollectAllAnalyzer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl ResultsAnalyzer for CollectAllAnalyzer {
+    fn add_iteration(&mut self, var_number: u64, iteration: u64, results: &IterationResults) {
+        self.results
+            .insert((var_number, iteration), results_to_json(results));
+    }
+
+    fn analyze(&self) -> Value {
+        Value::Array(
+            self.results
+                .iter()
+                .map(|((var_number, iteration), results)| {
+                    let mut item = Map::new();
+                    item.insert("VARIANT".to_owned(), Value::from(*var_number));
+                    item.insert("ITERATION".to_owned(), Value::from(*iteration));
+                    item.insert("RESULTS".to_owned(), results.clone());
+                    Value::Object(item)
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Averages a numeric field of the observer results over the iterations of
+/// each variant.
+///
+/// Every observer result that is an object holding a number under `field`
+/// contributes to the mean of its model. `analyze` returns
+/// `{"<variant>": {"<model full name>": mean}}`. The running estimate is the
+/// mean over all the models of the variant.
+#[derive(Debug)]
+pub struct MeanAnalyzer {
+    field: String,
+    sums: BTreeMap<u64, BTreeMap<String, (f64, u64)>>,
+}
+
+impl MeanAnalyzer {
+    pub fn new(field: &str) -> Self {
+        Self {
+            field: field.to_owned(),
+            sums: BTreeMap::new(),
+        }
+    }
+}
+
+impl ResultsAnalyzer for MeanAnalyzer {
+    fn add_iteration(&mut self, var_number: u64, _iteration: u64, results: &IterationResults) {
+        let field = &self.field;
+        let sums = self.sums.entry(var_number).or_default();
+        for (model_full_name, observer_results) in results {
+            for value in observer_results
+                .iter()
+                .filter_map(|result| result.get(field))
+                .filter_map(Value::as_f64)
+            {
+                let (sum, count) = sums.entry(model_full_name.clone()).or_default();
+                *sum += value;
+                *count += 1;
+            }
+        }
+    }
+
+    fn analyze(&self) -> Value {
+        Value::Object(
+            self.sums
+                .iter()
+                .map(|(var_number, sums)| {
+                    let means = sums
+                        .iter()
+                        .map(|(model_full_name, (sum, count))| {
+                            (model_full_name.clone(), Value::from(sum / *count as f64))
+                        })
+                        .collect::<Map<String, Value>>();
+                    (var_number.to_string(), Value::Object(means))
+                })
+                .collect(),
+        )
+    }
+
+    fn running_estimate(&self, var_number: u64) -> Option<f64> {
+        let (sum, count) = self
+            .sums
+            .get(&var_number)?
+            .values()
+            .fold((0.0, 0), |(sum, count), (model_sum, model_count)| {
+                (sum + model_sum, count + model_count)
+            });
+        if count == 0 {
+            None
+        } else {
+            Some(sum / count as f64)
+        }
+    }
+}
+
+/// Two-sided 95% quantiles of the Student t distribution for 1 to 30 degrees of
+/// freedom.
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// Two-sided 95% quantile of the Student t distribution, approximated from the
+/// normal quantile beyond the table.
+fn t_95(degrees_of_freedom: u64) -> f64 {
+    match T_95.get(degrees_of_freedom as usize - 1) {
+        Some(t) => *t,
+        None => {
+            let z = 1.959_964;
+            z + (z * z * z + z) / (4.0 * degrees_of_freedom as f64)
+        }
+    }
+}
+
+/// Sum, sum of squares and count of the samples of one field.
+#[derive(Debug, Default, Clone, Copy)]
+struct Moments {
+    sum: f64,
+    sum_of_squares: f64,
+    count: u64,
+}
+
+impl Moments {
+    fn add(&mut self, sample: f64) {
+        self.sum += sample;
+        self.sum_of_squares += sample * sample;
+        self.count += 1;
+    }
+
+    /// `{"COUNT", "MEAN", "STD", "CI_LOW", "CI_HIGH"}`; the standard deviation
+    /// and the confidence interval are null for a single sample.
+    fn to_value(self) -> Value {
+        let count = self.count as f64;
+        let mean = self.sum / count;
+        let mut stats = Map::new();
+        stats.insert("COUNT".to_owned(), Value::from(self.count));
+        stats.insert("MEAN".to_owned(), Value::from(mean));
+        if self.count > 1 {
+            let variance = ((self.sum_of_squares - count * mean * mean) / (count - 1.0)).max(0.0);
+            let std = variance.sqrt();
+            let half_width = t_95(self.count - 1) * std / count.sqrt();
+            stats.insert("STD".to_owned(), Value::from(std));
+            stats.insert("CI_LOW".to_owned(), Value::from(mean - half_width));
+            stats.insert("CI_HIGH".to_owned(), Value::from(mean + half_width));
+        } else {
+            for key in ["STD", "CI_LOW", "CI_HIGH"] {
+                stats.insert(key.to_owned(), Value::Null);
+            }
+        }
+        Value::Object(stats)
+    }
+}
+
+/// Summary statistics of every numeric field of the observer results, over
+/// the iterations of each variant, with a 95% confidence interval of the mean
+/// (Student t).
+///
+/// `analyze` returns `{"<variant>": {"<model full name>": {"<field>": stats}}}`
+/// where stats is `{"COUNT", "MEAN", "STD", "CI_LOW", "CI_HIGH"}`; a field
+/// with a single sample has null `STD` and bounds.
+#[derive(Debug, Default)]
+pub struct StatsAnalyzer {
+    moments: BTreeMap<u64, BTreeMap<String, BTreeMap<String, Moments>>>,
+}
+
+impl StatsAnalyzer {
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+impl ResultsAnalyzer for StatsAnalyzer {
+    fn add_iteration(&mut self, var_number: u64, _iteration: u64, results: &IterationResults) {
+        let models = self.moments.entry(var_number).or_default();
+        for (model_full_name, observer_results) in results {
+            for fields in observer_results.iter().filter_map(Value::as_object) {
+                for (field, sample) in fields {
+                    if let Some(sample) = sample.as_f64() {
+                        models
+                            .entry(model_full_name.clone())
+                            .or_default()
+                            .entry(field.clone())
+                            .or_default()
+                            .add(sample);
+                    }
+                }
+            }
+        }
+    }
+
+    fn analyze(&self) -> Value {
+        Value::Object(
+            self.moments
+                .iter()
+                .map(|(var_number, models)| {
+                    let models = models
+                        .iter()
+                        .map(|(model_full_name, fields)| {
+                            let fields = fields
+                                .iter()
+                                .map(|(field, moments)| (field.clone(), moments.to_value()))
+                                .collect::<Map<String, Value>>();
+                            (model_full_name.clone(), Value::Object(fields))
+                        })
+                        .collect::<Map<String, Value>>();
+                    (var_number.to_string(), Value::Object(models))
+                })
+                .collect(),
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct ExperimentConfig {
     name: String,
@@ -40,13 +349,14 @@ struct ExperimentConfig {
 }
 
 impl ExperimentConfig {
-    fn new(experiment_path: &Path) -> ExperimentConfig {
-        let experiment_json_string = read_to_string(experiment_path).unwrap();
-        let mut experiment_config =
-            serde_json::from_str::<ExperimentConfig>(&experiment_json_string).unwrap();
-        let experiment_directory = experiment_path.parent().unwrap().to_path_buf();
+    fn new(experiment_path: &Path) -> Result<ExperimentConfig, ExdsdevsError> {
+        let mut experiment_config: ExperimentConfig = read_json_from_file(experiment_path)?;
+        let experiment_directory = experiment_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
         experiment_config.experiment_directory = Some(experiment_directory);
-        experiment_config
+        Ok(experiment_config)
     }
 
     fn experiment_name(&self) -> String {
@@ -61,27 +371,22 @@ impl ExperimentConfig {
         self.root_model_class.clone()
     }
 
-    fn init_time(&self) -> Time {
-        Time::Value(
-            i128::from_str(&self.init_time)
-                .unwrap_or_else(|_| panic!("Cannot convert value {} to Time", &self.init_time)),
-        )
+    fn init_time(&self) -> Result<Time, ExdsdevsError> {
+        Time::try_from(self.init_time.as_str())
+            .map_err(|err| ExdsdevsError::ErrorSimTime(format!("init_time: {}", err)))
     }
 
-    fn finish_time(&self) -> Time {
-        match self.finish_time.as_str() {
-            "Infinity" => Time::Inf,
-            t => {
-                let time = Time::Value(
-                    i128::from_str(t)
-                        .unwrap_or_else(|_| panic!("Cannot convert value {} to Time", t)),
-                );
-                if time < self.init_time() {
-                    panic!("finish_time cannot be lesser then init_time");
-                } else {
-                    time
-                }
-            }
+    fn finish_time(&self) -> Result<Time, ExdsdevsError> {
+        let time = Time::try_from(self.finish_time.as_str())
+            .map_err(|err| ExdsdevsError::ErrorSimTime(format!("finish_time: {}", err)))?;
+        let init_time = self.init_time()?;
+        if time < init_time {
+            Err(ExdsdevsError::ErrorSimTime(format!(
+                "finish_time {} is before init_time {}",
+                time, init_time
+            )))
+        } else {
+            Ok(time)
         }
     }
 
@@ -120,6 +425,47 @@ impl ExperimentConfig {
     }
 }
 
+/// Wall-clock durations of the iterations of the last experiment run, keyed by
+/// `(var_number, iteration)`.
+#[derive(Debug, Clone, Default)]
+pub struct ExperimentTiming {
+    durations: BTreeMap<(u64, u64), Duration>,
+}
+
+impl ExperimentTiming {
+    fn record(&mut self, var_number: u64, iteration: u64, duration: Duration) {
+        self.durations.insert((var_number, iteration), duration);
+    }
+
+    pub fn durations(&self) -> &BTreeMap<(u64, u64), Duration> {
+        &self.durations
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.durations.values().min().copied()
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        self.durations.values().max().copied()
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        if self.durations.is_empty() {
+            None
+        } else {
+            Some(self.durations.values().sum::<Duration>() / self.durations.len() as u32)
+        }
+    }
+
+    /// The `(var_number, iteration)` that took the longest.
+    pub fn slowest(&self) -> Option<(u64, u64)> {
+        self.durations
+            .iter()
+            .max_by_key(|(_, duration)| **duration)
+            .map(|(key, _)| *key)
+    }
+}
+
 pub struct Experiment {
     pub experiment_name: String,
     pub experiment_directory: PathBuf,
@@ -134,15 +480,36 @@ pub struct Experiment {
     pub random_seed: u64,
     pub iterations: u64,
     pub init_variants_factory: InitVariantsFactory,
+    analyzer: Option<Box<dyn ResultsAnalyzer + Send>>,
+    timing: ExperimentTiming,
+    progress: Option<Box<dyn FnMut(u64, u64) + Send>>,
+    completed_iterations: u64,
+    reuse_models: bool,
+    seed_strategy: SeedStrategy,
+    convergence: Option<ConvergenceCriterion>,
+    estimates: Vec<f64>,
 }
 
 impl Experiment {
+    /// Loads the experiment file and the model classes of its model directory.
+    /// Panics on an invalid configuration, see `try_new`.
     pub fn new(
         experiment_path: &Path,
         dynamic_factory: DynamicFactoryStorage,
         observer_factory: ObserverFactoryStorage,
     ) -> Self {
-        let experiment_config = ExperimentConfig::new(experiment_path);
+        Self::try_new(experiment_path, dynamic_factory, observer_factory)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Fails on an invalid experiment file or model class, or when a dynamic
+    /// or an observer of the model is missing from the factories.
+    pub fn try_new(
+        experiment_path: &Path,
+        dynamic_factory: DynamicFactoryStorage,
+        observer_factory: ObserverFactoryStorage,
+    ) -> Result<Self, ExdsdevsError> {
+        let experiment_config = ExperimentConfig::new(experiment_path)?;
 
         let experiment_name = experiment_config.experiment_name();
         let experiment_directory = experiment_config.experiment_directory();
@@ -150,20 +517,27 @@ impl Experiment {
         let results_directory = experiment_config.results_directory();
         let root_model_full_name: String = "root".to_owned();
         let root_model_class_name = experiment_config.root_model_class();
-        let model_factory = Arc::new(ModelFactory::new(
+        let model_factory = Arc::new(ModelFactory::try_new(
             &model_directory,
             dynamic_factory,
             observer_factory,
-        ));
+        )?);
         let init_variants_factory =
-            InitVariantsFactory::new(model_factory.class_storage(), &root_model_class_name);
-        let global_resources = Arc::new(Self::build_global_resources(&experiment_config));
-        let init_time = experiment_config.init_time();
-        let finish_time = experiment_config.finish_time();
+            InitVariantsFactory::try_new(model_factory.class_storage(), &root_model_class_name)?;
+        let global_resources = Arc::new(Self::build_global_resources(&experiment_config)?);
+        // Builds the model tree once, so that a dynamic or an observer missing
+        // from the factories fails here rather than in every iteration.
+        model_factory.build_simulator(
+            &root_model_class_name,
+            root_model_full_name.clone(),
+            &global_resources,
+        )?;
+        let init_time = experiment_config.init_time()?;
+        let finish_time = experiment_config.finish_time()?;
         let random_seed = experiment_config.random_seed();
         let iterations = experiment_config.iterations();
 
-        Self {
+        Ok(Self {
             experiment_name,
             experiment_directory,
             model_directory,
@@ -177,84 +551,235 @@ impl Experiment {
             random_seed,
             iterations,
             init_variants_factory,
+            analyzer: None,
+            timing: ExperimentTiming::default(),
+            progress: None,
+            completed_iterations: 0,
+            reuse_models: false,
+            seed_strategy: SeedStrategy::default(),
+            convergence: None,
+            estimates: Vec::new(),
+        })
+    }
+
+    /// Builds the model tree once per init variant and thread and resets it
+    /// between iterations, see `Simulator::reset`, instead of building it for
+    /// every iteration. Whether this is faster depends on how costly the tree
+    /// is to build compared to resetting its dynamics. Only for models whose
+    /// dynamics implement `Dynamic::reset`, an iteration panics otherwise,
+    /// and whose structure does not change during a run.
+    pub fn with_model_reuse(mut self, reuse_models: bool) -> Self {
+        self.reuse_models = reuse_models;
+        self
+    }
+
+    /// Stops the iterations of each variant once the running estimate of the
+    /// analyzer converges, `iterations` is then the maximum number of
+    /// iterations. In multi-thread runs the iterations already started when
+    /// the estimate converges are completed and passed to the analyzer too.
+    pub fn with_convergence(mut self, convergence: ConvergenceCriterion) -> Self {
+        self.convergence = Some(convergence);
+        self
+    }
+
+    /// Defaults to `SeedStrategy::Mixed`.
+    pub fn with_seed_strategy(mut self, seed_strategy: SeedStrategy) -> Self {
+        self.seed_strategy = seed_strategy;
+        self
+    }
+
+    /// The analyzer is `Send` so that the experiment can be run from another
+    /// thread, e.g. by an `ExperimentPool`.
+    pub fn with_analyzer(mut self, analyzer: Box<dyn ResultsAnalyzer + Send>) -> Self {
+        self.analyzer = Some(analyzer);
+        self
+    }
+
+    /// Sets a callback called with `(completed, total)` iterations every time an
+    /// iteration finishes; it runs on the thread that called the run method.
+    pub fn with_progress(mut self, progress: impl FnMut(u64, u64) + Send + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Iteration timings of the last `run_single_thread`/`run_multi_thread`.
+    pub fn last_timing(&self) -> &ExperimentTiming {
+        &self.timing
+    }
+
+    /// Returns whether the variant converged, see `with_convergence`.
+    fn add_iteration_results(
+        &mut self,
+        var_number: u64,
+        iteration: u64,
+        results: &IterationResults,
+    ) -> bool {
+        if let Some(analyzer) = &mut self.analyzer {
+            analyzer.add_iteration(var_number, iteration, results);
+        }
+        self.completed_iterations += 1;
+        if let Some(progress) = &mut self.progress {
+            let total = self.init_variants_factory.variants_count() * self.iterations;
+            progress(self.completed_iterations, total);
+        }
+        let estimate = self
+            .analyzer
+            .as_ref()
+            .and_then(|analyzer| analyzer.running_estimate(var_number));
+        match (&self.convergence, estimate) {
+            (Some(convergence), Some(estimate)) => {
+                self.estimates.push(estimate);
+                convergence.is_converged(&self.estimates)
+            }
+            _ => false,
         }
     }
 
-    pub fn run_single_thread(&mut self) {
+    /// Runs all the iterations of every init variant and returns the output of
+    /// the analyzer, if one is set. Stops at the first iteration that fails.
+    pub fn run_single_thread(&mut self) -> Result<Option<Value>, ExdsdevsError> {
+        self.timing = ExperimentTiming::default();
+        self.completed_iterations = 0;
         while let Some((var_number, init_variant)) =
             self.init_variants_factory.next_enumerated_variant()
         {
-            let init_variant = Arc::new(init_variant);
-            for iteration in 0..self.iterations {
-                let random_seed = self.random_seed + iteration;
-                let results_directory = self.results_directory.clone();
-                let model_factory = self.model_factory.clone();
-                let root_model_class_name = self.root_model_class_name.clone();
-                let root_model_full_name = self.root_model_full_name.clone();
-                let global_resources = self.global_resources.clone();
-                let init_time = self.init_time;
-                let finish_time = self.finish_time;
-                let init_variant = init_variant.clone();
-
-                let mut root = Self::create_root_simulator(
-                    results_directory,
-                    model_factory,
-                    root_model_class_name,
-                    root_model_full_name,
-                    global_resources,
-                    init_time,
-                    finish_time,
-                    var_number,
-                    iteration,
-                    random_seed,
-                    init_variant,
-                );
-                root.init();
-                root.run();
-            }
+            let job = self.iteration_job(var_number, init_variant);
+            self.estimates.clear();
+            let mut iterations = 0..self.iterations;
+            let converged = Cell::new(false);
+            job.run(
+                || {
+                    if converged.get() {
+                        None
+                    } else {
+                        iterations.next()
+                    }
+                },
+                |iteration, duration, results| {
+                    self.timing.record(var_number, iteration, duration);
+                    if self.add_iteration_results(var_number, iteration, &results) {
+                        converged.set(true);
+                    }
+                },
+            )?;
         }
+        Ok(self.analyzer.as_ref().map(|analyzer| analyzer.analyze()))
     }
 
-    pub fn run_multi_thread(&mut self) {
+    /// Same as `run_single_thread`, the iterations of each init variant run in
+    /// parallel. On a failure no other iteration is started and the error of
+    /// the first failed iteration is returned once the running ones are done.
+    pub fn run_multi_thread(&mut self) -> Result<Option<Value>, ExdsdevsError> {
+        let pool = threadpool::Builder::new().build();
+        self.run_in_pool(&pool)
+    }
+
+    fn run_in_pool(&mut self, pool: &ThreadPool) -> Result<Option<Value>, ExdsdevsError> {
+        self.timing = ExperimentTiming::default();
+        self.completed_iterations = 0;
+        // Every worker takes the next iteration as soon as it is free, so long
+        // iterations do not hold back the others; the results do not depend on
+        // which worker runs which iteration.
+        let iterations = self.iterations;
+        let workers = (pool.max_count() as u64).min(iterations);
         while let Some((var_number, init_variant)) =
             self.init_variants_factory.next_enumerated_variant()
         {
-            let pool = threadpool::Builder::new().build();
-            let init_variant = Arc::new(init_variant);
-
-            for iteration in 0..self.iterations {
-                let random_seed = self.random_seed + iteration;
-                let results_directory = self.results_directory.clone();
-                let model_factory = self.model_factory.clone();
-                let root_model_class_name = self.root_model_class_name.clone();
-                let root_model_full_name = self.root_model_full_name.clone();
-                let global_resources = self.global_resources.clone();
-                let init_time = self.init_time;
-                let finish_time = self.finish_time;
-                let init_variant = init_variant.clone();
+            let job = self.iteration_job(var_number, init_variant);
+            self.estimates.clear();
+            let next_iteration = Arc::new(AtomicU64::new(0));
+            // Number of iterations handed out, 0 to `started` - 1.
+            let started = Arc::new(AtomicU64::new(0));
+            let (results_tx, results_rx) = mpsc::sync_channel(workers as usize);
+
+            for _ in 0..workers {
+                let job = job.clone();
+                let next_iteration = next_iteration.clone();
+                let started = started.clone();
+                let results_tx = results_tx.clone();
                 pool.execute(move || {
-                    let mut root = Self::create_root_simulator(
-                        results_directory,
-                        model_factory,
-                        root_model_class_name,
-                        root_model_full_name,
-                        global_resources,
-                        init_time,
-                        finish_time,
-                        var_number,
-                        iteration,
-                        random_seed,
-                        init_variant,
-                    );
-                    root.init();
-                    root.run();
+                    let take_iteration = || {
+                        let iteration = next_iteration.fetch_add(1, Ordering::Relaxed);
+                        (iteration < iterations).then(|| {
+                            started.fetch_max(iteration + 1, Ordering::Relaxed);
+                            iteration
+                        })
+                    };
+                    let result = job.run(take_iteration, |iteration, duration, results| {
+                        results_tx
+                            .send(Ok((iteration, duration, results)))
+                            .expect("Experiment results channel is closed");
+                    });
+                    if let Err(error) = result {
+                        results_tx
+                            .send(Err(error))
+                            .expect("Experiment results channel is closed");
+                    }
                 });
             }
+            drop(results_tx);
+            let mut received = BTreeSet::new();
+            let mut failure = None;
+            for result in results_rx {
+                match result {
+                    Ok((iteration, duration, results)) => {
+                        self.timing.record(var_number, iteration, duration);
+                        received.insert(iteration);
+                        if self.add_iteration_results(var_number, iteration, &results) {
+                            // No worker starts another iteration.
+                            next_iteration.store(iterations, Ordering::Relaxed);
+                        }
+                    }
+                    Err(error) => {
+                        next_iteration.store(iterations, Ordering::Relaxed);
+                        failure = failure.or(Some(error));
+                    }
+                }
+            }
             pool.join();
+            if let Some(error) = failure {
+                return Err(error);
+            }
+            // A worker panicking in an iteration drops its sender without
+            // sending the results of that iteration.
+            let missing: Vec<u64> = (0..started.load(Ordering::Relaxed))
+                .filter(|iteration| !received.contains(iteration))
+                .collect();
+            if !missing.is_empty() {
+                return Err(ExdsdevsError::ErrorSimTime(format!(
+                    "iterations {:?} of init variant {} did not complete, \
+                     a worker thread panicked",
+                    missing, var_number
+                )));
+            }
+        }
+        Ok(self.analyzer.as_ref().map(|analyzer| analyzer.analyze()))
+    }
+
+    fn iteration_job(
+        &self,
+        var_number: u64,
+        init_variant: BTreeMap<String, Value>,
+    ) -> IterationJob {
+        IterationJob {
+            results_directory: self.results_directory.clone(),
+            model_factory: self.model_factory.clone(),
+            root_model_class_name: self.root_model_class_name.clone(),
+            root_model_full_name: self.root_model_full_name.clone(),
+            global_resources: self.global_resources.clone(),
+            init_time: self.init_time,
+            finish_time: self.finish_time,
+            random_seed: self.random_seed,
+            seed_strategy: self.seed_strategy,
+            reuse_models: self.reuse_models,
+            var_number,
+            init_variant: Arc::new(init_variant),
         }
     }
 
-    fn build_global_resources(experiment_config: &ExperimentConfig) -> BTreeMap<String, Value> {
+    fn build_global_resources(
+        experiment_config: &ExperimentConfig,
+    ) -> Result<BTreeMap<String, Value>, ExdsdevsError> {
         let experiment_directory = experiment_config.experiment_directory();
         experiment_config
             .global_resources()
@@ -270,48 +795,111 @@ impl Experiment {
                         tmp_path
                     };
                 }
-                let resource_value_string = read_to_string(rv_path).unwrap();
-                let resource_value = serde_json::from_str::<Value>(&resource_value_string).unwrap();
-                (resource_name.clone(), resource_value)
+                let resource_value: Value = read_json_from_file(rv_path)?;
+                Ok((resource_name.clone(), resource_value))
             })
-            .collect::<BTreeMap<String, Value>>()
-    }
-
-    #[allow(clippy::too_many_arguments)]
-    fn create_root_simulator(
-        results_directory: PathBuf,
-        model_factory: Arc<ModelFactory>,
-        root_model_class_name: String,
-        root_model_full_name: String,
-        global_resources: Arc<BTreeMap<String, Value>>,
-        init_time: Time,
-        finish_time: Time,
-        var_number: u64,
+            .collect()
+    }
+}
+
+/// Iterations of one init variant, run on one thread.
+#[derive(Clone)]
+struct IterationJob {
+    results_directory: PathBuf,
+    model_factory: Arc<ModelFactory>,
+    root_model_class_name: String,
+    root_model_full_name: String,
+    global_resources: Arc<BTreeMap<String, Value>>,
+    init_time: Time,
+    finish_time: Time,
+    random_seed: u64,
+    seed_strategy: SeedStrategy,
+    reuse_models: bool,
+    var_number: u64,
+    init_variant: Arc<BTreeMap<String, Value>>,
+}
+
+impl IterationJob {
+    /// Runs the iterations given by `next_iteration` until it returns `None`
+    /// and passes their timing and results to `report` as they complete. Stops
+    /// at the first iteration that fails.
+    fn run(
+        &self,
+        mut next_iteration: impl FnMut() -> Option<u64>,
+        mut report: impl FnMut(u64, Duration, IterationResults),
+    ) -> Result<(), ExdsdevsError> {
+        let mut previous_root = None;
+        while let Some(iteration) = next_iteration() {
+            let mut root = self.prepare_root_simulator(previous_root.take(), iteration)?;
+            let start = Instant::now();
+            root.run()?;
+            report(iteration, start.elapsed(), root.results());
+            if self.reuse_models {
+                previous_root = Some(root);
+            }
+        }
+        Ok(())
+    }
+
+    /// The root simulator of `iteration`, initialized: `previous_root` rewound
+    /// when there is one, else a new one built by the model factory.
+    fn prepare_root_simulator(
+        &self,
+        previous_root: Option<RootSimulator>,
         iteration: u64,
-        random_seed: u64,
-        init_variant: Arc<BTreeMap<String, Value>>,
-    ) -> RootSimulator {
-        let mut sim_dir = results_directory;
+    ) -> Result<RootSimulator, ExdsdevsError> {
+        let mut sim_dir = self.results_directory.clone();
         sim_dir.push(PathBuf::from(format!(
             "var_{}/iter_{}",
-            var_number, iteration
+            self.var_number, iteration
         )));
-        let mut root_simulator = RootSimulator::new(
-            model_factory,
-            root_model_class_name,
-            root_model_full_name,
-            global_resources,
-            init_time,
-            finish_time,
-        );
-        root_simulator.init_static(&sim_dir, &init_variant, random_seed);
-        root_simulator
+        let random_seed = self
+            .seed_strategy
+            .iteration_seed(self.random_seed, iteration);
+        let mut root_simulator = match previous_root {
+            Some(mut root_simulator) => {
+                root_simulator.rewind()?;
+                root_simulator
+            }
+            None => RootSimulator::try_new(
+                self.model_factory.clone(),
+                self.root_model_class_name.clone(),
+                self.root_model_full_name.clone(),
+                self.global_resources.clone(),
+                self.init_time,
+                self.finish_time,
+            )?,
+        };
+        root_simulator.iteration = iteration;
+        root_simulator.init_static(&sim_dir, &self.init_variant, random_seed);
+        root_simulator.try_init()?;
+        Ok(root_simulator)
+    }
+}
+
+/// Worker threads shared by several experiment runs, e.g. the experiments of a
+/// parameter sweep, which then do not pay the thread creation each time.
+pub struct ExperimentPool {
+    pool: ThreadPool,
+}
+
+impl ExperimentPool {
+    pub fn new(num_threads: usize) -> Self {
+        Self {
+            pool: threadpool::Builder::new().num_threads(num_threads).build(),
+        }
+    }
+
+    /// Same as `Experiment::run_multi_thread` on the threads of the pool.
+    pub fn run(&self, experiment: &mut Experiment) -> Result<Option<Value>, ExdsdevsError> {
+        experiment.run_in_pool(&self.pool)
     }
 }
 
 #[derive(Debug)]
 pub struct InitVariantsFactory {
     init_variants_values: BTreeMap<String, BTreeMap<String, Value>>,
+    default_init_values: BTreeMap<String, Value>,
     init_variants_names: BTreeMap<String, Vec<String>>,
     init_vec: Vec<VarDigit>,
     carry: usize,
@@ -327,18 +915,37 @@ struct VarDigit {
 
 impl InitVariantsFactory {
     pub fn new(class_storage: &BTreeMap<String, ModelClass>, root_model_class_name: &str) -> Self {
+        Self::try_new(class_storage, root_model_class_name).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as `new`, but fails when a model class is missing or the root
+    /// model class has no `root_init_variants`.
+    pub fn try_new(
+        class_storage: &BTreeMap<String, ModelClass>,
+        root_model_class_name: &str,
+    ) -> Result<Self, ExdsdevsError> {
+        let find_class = |model_class_name: &str| {
+            class_storage.get(model_class_name).ok_or_else(|| {
+                ExdsdevsError::ErrorModelValidation(format!(
+                    "model class '{}' was not found",
+                    model_class_name
+                ))
+            })
+        };
         let mut init_variants_values: BTreeMap<String, BTreeMap<String, Value>> = BTreeMap::new();
         let mut default_init_values: BTreeMap<String, Value> = BTreeMap::new();
         let mut model_stack: VecDeque<(String, &ModelClass)> = VecDeque::new();
         let root_model_full_name: String = "root".to_string();
-        let root_model_class = class_storage.get(root_model_class_name).unwrap();
+        let root_model_class = find_class(root_model_class_name)?;
         let root_default_init_values = root_model_class.get_default_init();
-        let root_init_variants = root_model_class.root_init_variants().unwrap_or_else(|| {
-            panic!(
-                "Error: no value set for root_init for {}",
+        let root_init_variants = root_model_class.root_init_variants().ok_or_else(|| {
+            ExdsdevsError::ErrorModelValidation(format!(
+                "no value set for root_init_variants of model class '{}'",
                 root_model_class_name
-            )
-        });
+            ))
+        })?;
+        let root_init_variants =
+            sweep_init_variants(root_init_variants, root_model_class.root_init_sweep())?;
         init_variants_values.insert(root_model_full_name.clone(), root_init_variants);
         default_init_values.insert(root_model_full_name.clone(), root_default_init_values);
         model_stack.push_back((root_model_full_name, root_model_class));
@@ -346,9 +953,10 @@ impl InitVariantsFactory {
         while let Some((model_full_name, model_class)) = model_stack.pop_front() {
             for (submodel_name, submodel) in model_class.submodels_iter() {
                 let submodel_full_name = format!("{}/{}", &model_full_name, submodel_name);
-                let submodel_init_variants = submodel.get_init_values();
+                let submodel_init_variants =
+                    sweep_init_variants(submodel.get_init_values(), submodel.init_sweep())?;
                 init_variants_values.insert(submodel_full_name.clone(), submodel_init_variants);
-                let submodel_class = class_storage.get(submodel.model_class()).unwrap();
+                let submodel_class = find_class(submodel.model_class())?;
                 let default_init_variant = submodel_class.get_default_init();
                 default_init_values.insert(submodel_full_name.clone(), default_init_variant);
                 model_stack.push_back((submodel_full_name, submodel_class));
@@ -368,13 +976,14 @@ impl InitVariantsFactory {
         let init_vec = Self::get_init_vec(&init_variants_names);
         let carry: usize = 0;
         let var_number: u64 = 0;
-        Self {
+        Ok(Self {
             init_variants_values,
+            default_init_values,
             init_variants_names,
             init_vec,
             carry,
             var_number,
-        }
+        })
     }
 
     fn get_init_vec(model_init_variants: &BTreeMap<String, Vec<String>>) -> Vec<VarDigit> {
@@ -388,14 +997,28 @@ impl InitVariantsFactory {
             .collect()
     }
 
+    /// Number of init variants, the product of the variant counts of the models.
+    pub fn variants_count(&self) -> u64 {
+        self.init_vec
+            .iter()
+            .map(|var_digit| var_digit.len as u64)
+            .product()
+    }
+
+    /// Next init variant as the init value of every model, the value passed to
+    /// `Dynamic::init`. The fields of a variant override the `default_init` of
+    /// the model class.
     pub fn next_enumerated_variant(&mut self) -> Option<(u64, BTreeMap<String, Value>)> {
         self.next_variant().map(|(var_number, next_variant)| {
             let mut next_variant_values: BTreeMap<String, Value> = BTreeMap::new();
             for (model_full_name, variant_name) in &next_variant {
                 if let Some(variants) = self.init_variants_values.get(model_full_name) {
                     if let Some(variant_value) = variants.get(variant_name) {
-                        next_variant_values
-                            .insert(model_full_name.to_owned(), variant_value.clone());
+                        let init_value = merge_init_values(
+                            self.default_init_values.get(model_full_name),
+                            variant_value,
+                        );
+                        next_variant_values.insert(model_full_name.to_owned(), init_value);
                     }
                 }
             }
@@ -439,3 +1062,798 @@ impl InitVariantsFactory {
         }
     }
 }
+
+/// Expands `init_variants` with the Cartesian product of the parameter ranges
+/// of `sweep`. A range is either an array of values or an object
+/// `{"from": .., "to": .., "step": ..}` with both ends included. Every
+/// combination is set over every variant, the expanded variants are named
+/// `variant:param=value,...`, or `param=value,...` without variants.
+fn sweep_init_variants(
+    init_variants: BTreeMap<String, Value>,
+    sweep: &BTreeMap<String, Value>,
+) -> Result<BTreeMap<String, Value>, ExdsdevsError> {
+    if sweep.is_empty() {
+        return Ok(init_variants);
+    }
+    let ranges = sweep
+        .iter()
+        .map(|(param, range)| Ok((param.clone(), sweep_values(param, range)?)))
+        .collect::<Result<_, ExdsdevsError>>()?;
+    let combinations: Vec<(String, Map<String, Value>)> = cartesian_product(ranges)?
+        .into_iter()
+        .map(|combination| {
+            let name = combination
+                .iter()
+                .map(|(param, value)| match value {
+                    Value::String(value) => format!("{}={}", param, value),
+                    _ => format!("{}={}", param, value),
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            (name, combination.into_iter().collect())
+        })
+        .collect();
+
+    if init_variants.is_empty() {
+        return Ok(combinations
+            .into_iter()
+            .map(|(name, fields)| (name, Value::Object(fields)))
+            .collect());
+    }
+    let mut swept_variants = BTreeMap::new();
+    for (variant_name, variant_value) in init_variants {
+        for (name, fields) in &combinations {
+            let mut variant_fields = match &variant_value {
+                Value::Object(variant_fields) => variant_fields.clone(),
+                Value::Null => Map::new(),
+                _ => {
+                    return Err(ExdsdevsError::ErrorCartesian(format!(
+                        "the init variant '{}' is not an object, its parameters cannot be swept",
+                        variant_name
+                    )))
+                }
+            };
+            for (param, value) in fields {
+                variant_fields.insert(param.clone(), value.clone());
+            }
+            swept_variants.insert(
+                format!("{}:{}", variant_name, name),
+                Value::Object(variant_fields),
+            );
+        }
+    }
+    Ok(swept_variants)
+}
+
+fn sweep_values(param: &str, range: &Value) -> Result<Vec<Value>, ExdsdevsError> {
+    let invalid = |reason: &str| {
+        Err(ExdsdevsError::ErrorCartesian(format!(
+            "invalid range of parameter '{}': {}",
+            param, reason
+        )))
+    };
+    let values = match range {
+        Value::Array(values) => values.clone(),
+        Value::Object(bounds) => {
+            let bound = |key: &str| bounds.get(key).and_then(Value::as_f64);
+            let (from, to, step) = match (bound("from"), bound("to"), bound("step")) {
+                (Some(from), Some(to), Some(step)) => (from, to, step),
+                _ => return invalid("expected numbers 'from', 'to' and 'step'"),
+            };
+            if step <= 0.0 {
+                return invalid("'step' must be positive");
+            }
+            let integers = ["from", "to", "step"]
+                .iter()
+                .all(|key| bounds[*key].is_i64());
+            let count = ((to - from) / step + 1e-9).floor() + 1.0;
+            if count.is_nan() || count > MAX_CARTESIAN_PRODUCT as f64 {
+                return Err(ExdsdevsError::ErrorCartesian(format!(
+                    "the range of parameter '{}' has more than {} values",
+                    param, MAX_CARTESIAN_PRODUCT
+                )));
+            }
+            (0..count.max(0.0) as i64)
+                .map(|idx| {
+                    if integers {
+                        Value::from(
+                            bounds["from"].as_i64().unwrap()
+                                + idx * bounds["step"].as_i64().unwrap(),
+                        )
+                    } else {
+                        Value::from(from + idx as f64 * step)
+                    }
+                })
+                .collect()
+        }
+        _ => return invalid("expected an array or an object"),
+    };
+    Ok(values)
+}
+
+fn merge_init_values(default_init: Option<&Value>, variant_value: &Value) -> Value {
+    match (default_init, variant_value) {
+        (Some(Value::Object(default_fields)), Value::Object(variant_fields)) => {
+            let mut fields = default_fields.clone();
+            for (key, value) in variant_fields {
+                fields.insert(key.clone(), value.clone());
+            }
+            Value::Object(fields)
+        }
+        (Some(default_init), Value::Null) => default_init.clone(),
+        _ => variant_value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::containers::SimRng;
+    use crate::dynamic::{Dynamic, DynamicFactory};
+    use crate::model::{Resources, Structure, StructureChange};
+    use crate::test_utils::{experiment_factories, temp_dir, write_experiment, Passive};
+    use rand::Rng;
+    use std::sync::Mutex;
+
+    fn iteration_results(clock: i64, passive: i64) -> IterationResults {
+        let mut results = IterationResults::new();
+        results.insert(
+            "root/clock".to_owned(),
+            vec![serde_json::json!({ "COUNT": clock }), Value::from("other")],
+        );
+        results.insert(
+            "root/passive".to_owned(),
+            vec![serde_json::json!({ "COUNT": passive })],
+        );
+        results
+    }
+
+    #[test]
+    fn test_run_with_analyzer_and_progress() {
+        let dir = temp_dir("experiment_run");
+        let experiment_path = write_experiment(&dir, 3);
+        let run = |multi_thread: bool| {
+            let progress = Arc::new(Mutex::new(Vec::new()));
+            let progress_log = progress.clone();
+            let (dynamic_factory, observer_factory) = experiment_factories();
+            let mut experiment =
+                Experiment::new(&experiment_path, dynamic_factory, observer_factory)
+                    .with_analyzer(Box::new(MeanAnalyzer::new("INTERNAL_TRANSITIONS")))
+                    .with_progress(move |completed, total| {
+                        progress_log.lock().unwrap().push((completed, total))
+                    });
+            let analysis = if multi_thread {
+                experiment.run_multi_thread().unwrap()
+            } else {
+                experiment.run_single_thread().unwrap()
+            };
+            assert_eq!(experiment.last_timing().durations().len(), 6);
+            let progress = progress.lock().unwrap().clone();
+            (analysis.unwrap(), progress)
+        };
+
+        let (single_analysis, single_progress) = run(false);
+        let (multi_analysis, multi_progress) = run(true);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(single_analysis, multi_analysis);
+        assert_eq!(
+            single_analysis
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            vec!["0", "1"]
+        );
+        assert!(single_analysis["0"]["root/clock_1"].as_f64().unwrap() > 0.0);
+        let expected: Vec<(u64, u64)> = (1..=6).map(|completed| (completed, 6)).collect();
+        assert_eq!(single_progress, expected);
+        assert_eq!(multi_progress, expected);
+    }
+
+    #[test]
+    fn test_try_new_errors() {
+        let dir = temp_dir("experiment_errors");
+        let try_new = |path: &Path| {
+            let (dynamic_factory, observer_factory) = experiment_factories();
+            Experiment::try_new(path, dynamic_factory, observer_factory).err()
+        };
+        assert!(matches!(
+            try_new(&dir.join("missing.json")),
+            Some(ExdsdevsError::ErrorIo(_, err)) if err.kind() == std::io::ErrorKind::NotFound
+        ));
+
+        let experiment_path = write_experiment(&dir, 1);
+        assert!(try_new(&experiment_path).is_none());
+        let (_, observer_factory) = experiment_factories();
+        let dynamic_factory = DynamicFactoryStorage::new()
+            .with_dynamic_factory("passive", DynamicFactory::<Passive>::new());
+        assert!(matches!(
+            Experiment::try_new(&experiment_path, dynamic_factory, observer_factory).err(),
+            Some(ExdsdevsError::ErrorBuildSimulator(msg))
+                if msg.contains("the dynamic 'clock' has not been registered")
+        ));
+        let (dynamic_factory, _) = experiment_factories();
+        assert!(matches!(
+            Experiment::try_new(&experiment_path, dynamic_factory, ObserverFactoryStorage::new())
+                .err(),
+            Some(ExdsdevsError::ErrorBuildSimulator(msg))
+                if msg.contains("the observer 'metrics' has not been registered")
+        ));
+        let mut config: Value = read_json_from_file(&experiment_path).unwrap();
+        config["finish_time"] = Value::from("soon");
+        std::fs::write(&experiment_path, config.to_string()).unwrap();
+        assert_eq!(
+            try_new(&experiment_path),
+            Some(ExdsdevsError::ErrorSimTime(
+                "finish_time: Cannot convert value soon to Time".to_owned()
+            ))
+        );
+        config["finish_time"] = Value::from("-1");
+        std::fs::write(&experiment_path, config.to_string()).unwrap();
+        assert!(matches!(
+            try_new(&experiment_path),
+            Some(ExdsdevsError::ErrorSimTime(_))
+        ));
+        config["finish_time"] = Value::from("20");
+        config["root_model_class"] = Value::from("unknown");
+        std::fs::write(&experiment_path, config.to_string()).unwrap();
+        assert_eq!(
+            try_new(&experiment_path),
+            Some(ExdsdevsError::ErrorModelValidation(
+                "model class 'unknown' was not found".to_owned()
+            ))
+        );
+        std::fs::write(dir.join("model/broken.json"), "{").unwrap();
+        assert!(matches!(
+            try_new(&experiment_path),
+            Some(ExdsdevsError::ErrorParseJson(Some(path), _)) if path.ends_with("broken.json")
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_experiment_pool() {
+        let dir = temp_dir("experiment_pool");
+        let experiment_path = write_experiment(&dir, 2);
+        let pool = ExperimentPool::new(2);
+        let analyses: Vec<Value> = (0..3)
+            .map(|_| {
+                let (dynamic_factory, observer_factory) = experiment_factories();
+                let mut experiment =
+                    Experiment::new(&experiment_path, dynamic_factory, observer_factory)
+                        .with_analyzer(Box::new(MeanAnalyzer::new("INTERNAL_TRANSITIONS")));
+                pool.run(&mut experiment).unwrap().unwrap()
+            })
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(analyses[0].as_object().unwrap().len(), 2);
+        assert_eq!(analyses[0], analyses[1]);
+        assert_eq!(analyses[0], analyses[2]);
+    }
+
+    #[test]
+    fn test_model_reuse() {
+        let dir = temp_dir("experiment_model_reuse");
+        let experiment_path = write_experiment(&dir, 5);
+        let pool = ExperimentPool::new(2);
+        let run = |reuse_models: bool, multi_thread: bool| {
+            let (dynamic_factory, observer_factory) = experiment_factories();
+            let mut experiment =
+                Experiment::new(&experiment_path, dynamic_factory, observer_factory)
+                    .with_model_reuse(reuse_models)
+                    .with_analyzer(Box::new(CollectAllAnalyzer::new()));
+            let analysis = if multi_thread {
+                pool.run(&mut experiment).unwrap()
+            } else {
+                experiment.run_single_thread().unwrap()
+            };
+            assert_eq!(experiment.last_timing().durations().len(), 10);
+            // The transition counts of every iteration, without the timings.
+            let mut counts = Vec::new();
+            for item in analysis.unwrap().as_array().unwrap() {
+                for (model, results) in item["RESULTS"].as_object().unwrap() {
+                    counts.push((
+                        item["VARIANT"].clone(),
+                        item["ITERATION"].clone(),
+                        model.clone(),
+                        results[0]["INTERNAL_TRANSITIONS"].clone(),
+                    ));
+                }
+            }
+            counts
+        };
+
+        let rebuilt = run(false, false);
+        assert_eq!(rebuilt.len(), 20);
+        assert_eq!(run(true, false), rebuilt);
+        assert_eq!(run(true, true), rebuilt);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Sleeps 20 ms at init in about a third of the iterations, depending on
+    /// the random stream of the iteration.
+    struct Sleeper;
+
+    impl Dynamic for Sleeper {
+        fn new() -> Self {
+            Sleeper
+        }
+
+        fn dynamic_type(&self) -> String {
+            "sleeper".to_owned()
+        }
+
+        fn init(&mut self, _: &mut Structure, _: Time, _: &Value, _: &Resources, rng: &mut SimRng) {
+            let millis = if rng.gen_bool(0.3) { 20 } else { 0 };
+            std::thread::sleep(Duration::from_millis(millis));
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Inf
+        }
+
+        fn state(&self) -> Value {
+            Value::Null
+        }
+
+        fn reset(&mut self, _: &Value) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    /// Records the iterations passed to the analyzer.
+    struct Seen(Arc<Mutex<Vec<(u64, u64)>>>);
+
+    impl ResultsAnalyzer for Seen {
+        fn add_iteration(&mut self, var_number: u64, iteration: u64, _: &IterationResults) {
+            self.0.lock().unwrap().push((var_number, iteration));
+        }
+
+        fn analyze(&self) -> Value {
+            Value::Null
+        }
+    }
+
+    #[test]
+    fn test_uneven_iterations_are_collected_once() {
+        let dir = temp_dir("experiment_uneven");
+        let experiment_path = write_experiment(&dir, 12);
+        let pool = ExperimentPool::new(3);
+        for reuse_models in [false, true] {
+            let (_, observer_factory) = experiment_factories();
+            let dynamic_factory = DynamicFactoryStorage::new()
+                .with_dynamic_factory("passive", DynamicFactory::<Passive>::new())
+                .with_dynamic_factory("clock", DynamicFactory::<Sleeper>::new());
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let mut experiment =
+                Experiment::new(&experiment_path, dynamic_factory, observer_factory)
+                    .with_model_reuse(reuse_models)
+                    .with_analyzer(Box::new(Seen(seen.clone())));
+            pool.run(&mut experiment).unwrap();
+
+            let mut seen = seen.lock().unwrap().clone();
+            seen.sort_unstable();
+            let expected: Vec<(u64, u64)> = (0..2)
+                .flat_map(|var_number| (0..12).map(move |iteration| (var_number, iteration)))
+                .collect();
+            assert_eq!(seen, expected);
+            assert_eq!(experiment.last_timing().durations().len(), 24);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_every_iteration_is_run_once() {
+        let dir = temp_dir("experiment_iterations_once");
+        let experiment_path = write_experiment(&dir, 100);
+        let (dynamic_factory, observer_factory) = experiment_factories();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut experiment = Experiment::new(&experiment_path, dynamic_factory, observer_factory)
+            .with_analyzer(Box::new(Seen(seen.clone())));
+        ExperimentPool::new(4).run(&mut experiment).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        for var_number in 0..2 {
+            let mut iterations: Vec<u64> = seen
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(var, _)| *var == var_number)
+                .map(|(_, iteration)| *iteration)
+                .collect();
+            iterations.sort_unstable();
+            assert_eq!(iterations, (0..100).collect::<Vec<u64>>());
+        }
+    }
+
+    /// Panics at its third init, counted across all the instances.
+    struct Crasher;
+
+    static CRASHER_INITS: AtomicU64 = AtomicU64::new(0);
+
+    impl Dynamic for Crasher {
+        fn new() -> Self {
+            Crasher
+        }
+
+        fn dynamic_type(&self) -> String {
+            "crasher".to_owned()
+        }
+
+        fn init(&mut self, _: &mut Structure, _: Time, _: &Value, _: &Resources, _: &mut SimRng) {
+            if CRASHER_INITS.fetch_add(1, Ordering::Relaxed) == 2 {
+                panic!("crasher init");
+            }
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Inf
+        }
+
+        fn state(&self) -> Value {
+            Value::Null
+        }
+    }
+
+    #[test]
+    fn test_worker_panic_is_reported() {
+        let dir = temp_dir("experiment_worker_panic");
+        let experiment_path = write_experiment(&dir, 6);
+        let (_, observer_factory) = experiment_factories();
+        let dynamic_factory = DynamicFactoryStorage::new()
+            .with_dynamic_factory("passive", DynamicFactory::<Passive>::new())
+            .with_dynamic_factory("clock", DynamicFactory::<Crasher>::new());
+        let mut experiment = Experiment::new(&experiment_path, dynamic_factory, observer_factory);
+        let error = ExperimentPool::new(2).run(&mut experiment).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(
+            error,
+            ExdsdevsError::ErrorSimTime(msg)
+                if msg.contains("did not complete, a worker thread panicked")
+        ));
+    }
+
+    /// Removes a submodel it does not have at its first event.
+    struct Remover;
+
+    impl Dynamic for Remover {
+        fn new() -> Self {
+            Remover
+        }
+
+        fn dynamic_type(&self) -> String {
+            "remover".to_owned()
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Value(1)
+        }
+
+        fn structure_changes(&mut self) -> Vec<StructureChange> {
+            vec![StructureChange::RemoveSubmodel("missing".to_owned())]
+        }
+
+        fn state(&self) -> Value {
+            Value::Null
+        }
+    }
+
+    #[test]
+    fn test_iteration_errors_are_returned() {
+        let dir = temp_dir("experiment_iteration_errors");
+        let experiment_path = write_experiment(&dir, 4);
+        for multi_thread in [false, true] {
+            let (_, observer_factory) = experiment_factories();
+            let dynamic_factory = DynamicFactoryStorage::new()
+                .with_dynamic_factory("passive", DynamicFactory::<Passive>::new())
+                .with_dynamic_factory("clock", DynamicFactory::<Remover>::new());
+            let mut experiment =
+                Experiment::new(&experiment_path, dynamic_factory, observer_factory);
+            let result = if multi_thread {
+                experiment.run_multi_thread()
+            } else {
+                experiment.run_single_thread()
+            };
+            assert!(matches!(
+                result,
+                Err(ExdsdevsError::ErrorModelValidation(msg))
+                    if msg.contains("removes unknown submodel 'missing'")
+            ));
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_seed_strategy() {
+        assert_eq!(SeedStrategy::default(), SeedStrategy::Mixed);
+        let seeds = |strategy: SeedStrategy| -> Vec<u64> {
+            (0..4)
+                .map(|iteration| strategy.iteration_seed(7, iteration))
+                .collect()
+        };
+        assert_eq!(seeds(SeedStrategy::Sequential), vec![7, 8, 9, 10]);
+        let mixed = seeds(SeedStrategy::Mixed);
+        assert_eq!(mixed, seeds(SeedStrategy::Mixed));
+        for pair in mixed.windows(2) {
+            assert!((pair[0] ^ pair[1]).count_ones() > 8);
+        }
+        assert_ne!(mixed[0], SeedStrategy::Mixed.iteration_seed(8, 0));
+    }
+
+    #[test]
+    fn test_convergence_criterion() {
+        let criterion = ConvergenceCriterion::new(2, 0.1);
+        assert!(!criterion.is_converged(&[1.0, 1.0]));
+        assert!(criterion.is_converged(&[5.0, 1.0, 1.05, 1.0]));
+        assert!(!criterion.is_converged(&[1.0, 1.2, 1.05, 1.0]));
+        assert!(criterion.is_converged(&[0.0, 0.0, 0.0]));
+        assert!(!ConvergenceCriterion::new(0, 0.1).is_converged(&[1.0, 1.0]));
+    }
+
+    #[test]
+    fn test_run_until_convergence() {
+        let dir = temp_dir("experiment_convergence");
+        let experiment_path = write_experiment(&dir, 200);
+        let run = |tolerance: f64, multi_thread: bool| {
+            let (dynamic_factory, observer_factory) = experiment_factories();
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let mut experiment =
+                Experiment::new(&experiment_path, dynamic_factory, observer_factory)
+                    .with_analyzer(Box::new(MeanAnalyzer::new("INTERNAL_TRANSITIONS")))
+                    .with_convergence(ConvergenceCriterion::new(5, tolerance))
+                    .with_progress({
+                        let seen = seen.clone();
+                        move |completed, _| seen.lock().unwrap().push(completed)
+                    });
+            let analysis = if multi_thread {
+                experiment.run_multi_thread().unwrap()
+            } else {
+                experiment.run_single_thread().unwrap()
+            };
+            assert_eq!(analysis.unwrap().as_object().unwrap().len(), 2);
+            let completed = seen.lock().unwrap().len();
+            completed
+        };
+
+        let converged = run(0.05, false);
+        assert!((2 * 6..400).contains(&converged), "{}", converged);
+        assert_eq!(run(0.05, false), converged);
+        assert!(run(0.05, true) < 400);
+        assert_eq!(run(0.0, false), 400);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_stats_analyzer() {
+        let mut analyzer = StatsAnalyzer::new();
+        for (iteration, (clock, passive)) in [(2, 5), (4, 5), (6, 5)].iter().enumerate() {
+            analyzer.add_iteration(0, iteration as u64, &iteration_results(*clock, *passive));
+        }
+        analyzer.add_iteration(1, 0, &iteration_results(3, 1));
+        let analysis = analyzer.analyze();
+
+        let clock = &analysis["0"]["root/clock"]["COUNT"];
+        assert_eq!(clock["COUNT"], Value::from(3));
+        assert_eq!(clock["MEAN"].as_f64(), Some(4.0));
+        assert_eq!(clock["STD"].as_f64(), Some(2.0));
+        let half_width = 4.303 * 2.0 / 3f64.sqrt();
+        assert!((clock["CI_LOW"].as_f64().unwrap() - (4.0 - half_width)).abs() < 1e-9);
+        assert!((clock["CI_HIGH"].as_f64().unwrap() - (4.0 + half_width)).abs() < 1e-9);
+        let passive = &analysis["0"]["root/passive"]["COUNT"];
+        assert_eq!(passive["STD"].as_f64(), Some(0.0));
+        assert_eq!(passive["CI_LOW"], passive["CI_HIGH"]);
+
+        let single = &analysis["1"]["root/clock"]["COUNT"];
+        assert_eq!(single["MEAN"].as_f64(), Some(3.0));
+        assert_eq!(single["STD"], Value::Null);
+        assert_eq!(single["CI_LOW"], Value::Null);
+    }
+
+    #[test]
+    fn test_t_95() {
+        assert_eq!(t_95(1), 12.706);
+        assert_eq!(t_95(30), 2.042);
+        assert!((t_95(60) - 2.000).abs() < 0.002);
+        assert!((t_95(1000) - 1.962).abs() < 0.002);
+    }
+
+    #[test]
+    fn test_experiment_timing() {
+        let mut timing = ExperimentTiming::default();
+        assert_eq!(timing.mean(), None);
+        timing.record(0, 0, Duration::from_millis(10));
+        timing.record(0, 1, Duration::from_millis(30));
+        timing.record(1, 0, Duration::from_millis(20));
+        assert_eq!(timing.min(), Some(Duration::from_millis(10)));
+        assert_eq!(timing.max(), Some(Duration::from_millis(30)));
+        assert_eq!(timing.mean(), Some(Duration::from_millis(20)));
+        assert_eq!(timing.slowest(), Some((0, 1)));
+        assert_eq!(timing.durations().len(), 3);
+    }
+
+    #[test]
+    fn test_collect_all_analyzer() {
+        let mut analyzer = CollectAllAnalyzer::new();
+        analyzer.add_iteration(0, 1, &iteration_results(2, 0));
+        analyzer.add_iteration(0, 0, &iteration_results(1, 0));
+        let analysis = analyzer.analyze();
+        assert_eq!(analysis.as_array().unwrap().len(), 2);
+        assert_eq!(analysis[0]["ITERATION"], Value::from(0));
+        assert_eq!(
+            analysis[1]["RESULTS"]["root/clock"][0],
+            serde_json::json!({ "COUNT": 2 })
+        );
+    }
+
+    #[test]
+    fn test_mean_analyzer() {
+        let mut analyzer = MeanAnalyzer::new("COUNT");
+        analyzer.add_iteration(0, 0, &iteration_results(1, 0));
+        analyzer.add_iteration(0, 1, &iteration_results(4, 0));
+        analyzer.add_iteration(1, 0, &iteration_results(3, 5));
+        assert_eq!(
+            analyzer.analyze(),
+            serde_json::json!({
+                "0": { "root/clock": 2.5, "root/passive": 0.0 },
+                "1": { "root/clock": 3.0, "root/passive": 5.0 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_init_sweep() {
+        let mut variants = BTreeMap::new();
+        variants.insert("fast".to_owned(), serde_json::json!({"period": 1}));
+        variants.insert("slow".to_owned(), Value::Null);
+        let mut sweep = BTreeMap::new();
+        sweep.insert(
+            "gain".to_owned(),
+            serde_json::json!({"from": 0, "to": 4, "step": 2}),
+        );
+        sweep.insert("mode".to_owned(), serde_json::json!(["a", "b"]));
+        let swept = sweep_init_variants(variants, &sweep).unwrap();
+        assert_eq!(swept.len(), 12);
+        assert_eq!(
+            swept["fast:gain=4,mode=b"],
+            serde_json::json!({"period": 1, "gain": 4, "mode": "b"})
+        );
+        assert_eq!(
+            swept["slow:gain=0,mode=a"],
+            serde_json::json!({"gain": 0, "mode": "a"})
+        );
+
+        let swept = sweep_init_variants(BTreeMap::new(), &sweep).unwrap();
+        assert_eq!(swept.len(), 6);
+        assert_eq!(
+            swept["gain=2,mode=a"],
+            serde_json::json!({"gain": 2, "mode": "a"})
+        );
+        let mut sweep = BTreeMap::new();
+        sweep.insert(
+            "rate".to_owned(),
+            serde_json::json!({"from": 0.5, "to": 1.0, "step": 0.25}),
+        );
+        let rates: Vec<Value> = sweep_init_variants(BTreeMap::new(), &sweep)
+            .unwrap()
+            .into_values()
+            .map(|variant| variant["rate"].clone())
+            .collect();
+        assert_eq!(rates, vec![0.5, 0.75, 1.0]);
+
+        for range in [
+            serde_json::json!([]),
+            serde_json::json!({"from": 0, "to": 1, "step": 0}),
+            serde_json::json!({"from": 0, "to": 1}),
+            serde_json::json!(3),
+        ] {
+            let mut sweep = BTreeMap::new();
+            sweep.insert("gain".to_owned(), range);
+            assert!(matches!(
+                sweep_init_variants(BTreeMap::new(), &sweep),
+                Err(ExdsdevsError::ErrorCartesian(_))
+            ));
+        }
+        // Huge ranges are rejected before their values are built.
+        for range in [
+            serde_json::json!({"from": 0, "to": 1_000_000_000_000_i64, "step": 1}),
+            serde_json::json!({"from": 0.0, "to": 1.0, "step": 1e-300}),
+        ] {
+            sweep.insert("gain".to_owned(), range);
+            assert_eq!(
+                sweep_init_variants(BTreeMap::new(), &sweep),
+                Err(ExdsdevsError::ErrorCartesian(format!(
+                    "the range of parameter 'gain' has more than {} values",
+                    MAX_CARTESIAN_PRODUCT
+                )))
+            );
+        }
+        let mut variants = BTreeMap::new();
+        variants.insert("scalar".to_owned(), Value::from(1));
+        sweep.insert("gain".to_owned(), serde_json::json!([1]));
+        assert_eq!(
+            sweep_init_variants(variants, &sweep),
+            Err(ExdsdevsError::ErrorCartesian(
+                "the init variant 'scalar' is not an object, its parameters cannot be swept"
+                    .to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_init_sweep_variants_count() {
+        let dir = temp_dir("experiment_sweep");
+        write_experiment(&dir, 1);
+        let root_path = dir.join("model/root.json");
+        let mut root_class: Value = read_json_from_file(&root_path).unwrap();
+        root_class["submodels"]["clock_2"]["init_sweep"] = serde_json::json!({"period": [1, 2, 3]});
+        root_class["root_init_sweep"] =
+            serde_json::json!({"seed": {"from": 1, "to": 2, "step": 1}});
+        std::fs::write(&root_path, root_class.to_string()).unwrap();
+
+        let (dynamic_factory, observer_factory) = experiment_factories();
+        let model_factory =
+            ModelFactory::new(&dir.join("model"), dynamic_factory, observer_factory);
+        let mut factory = InitVariantsFactory::new(model_factory.class_storage(), "root");
+        assert_eq!(factory.variants_count(), 2 * 3 * 2);
+        let (_, variant) = factory.next_enumerated_variant().unwrap();
+        assert_eq!(variant["root"], serde_json::json!({"seed": 1}));
+        assert_eq!(variant["root/clock_2"], serde_json::json!({"period": 1}));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_init_variants_override_default_init() {
+        let model_class = |name: &str, submodels: Value, default_init: Value| -> ModelClass {
+            serde_json::from_value(serde_json::json!({
+                "model_class": name,
+                "dynamic_type": name,
+                "input_ports": [],
+                "output_ports": [],
+                "submodels": submodels,
+                "external_input_couplings": [],
+                "internal_couplings": [],
+                "external_output_couplings": [],
+                "default_init": default_init,
+                "root_init_variants": {"default": null},
+                "local_resources": {},
+                "observers": []
+            }))
+            .unwrap()
+        };
+        let mut class_storage = BTreeMap::new();
+        class_storage.insert(
+            "root".to_owned(),
+            model_class(
+                "root",
+                serde_json::json!({"clock": {
+                    "model_class": "clock",
+                    "init_variants": {"fast": {"period": 1}, "slow": {"period": 5}}
+                }}),
+                serde_json::json!({"mode": "default"}),
+            ),
+        );
+        class_storage.insert(
+            "clock".to_owned(),
+            model_class(
+                "clock",
+                serde_json::json!({}),
+                serde_json::json!({"period": 2, "phase": 0}),
+            ),
+        );
+
+        let mut factory = InitVariantsFactory::new(&class_storage, "root");
+        let (_, fast) = factory.next_enumerated_variant().unwrap();
+        let (_, slow) = factory.next_enumerated_variant().unwrap();
+        assert!(factory.next_enumerated_variant().is_none());
+        assert_eq!(fast["root"], serde_json::json!({"mode": "default"}));
+        assert_eq!(
+            fast["root/clock"],
+            serde_json::json!({"period": 1, "phase": 0})
+        );
+        assert_eq!(
+            slow["root/clock"],
+            serde_json::json!({"period": 5, "phase": 0})
+        );
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use std::fmt::Write;
+
+use crate::model::Model;
+
+const ROOT_NAME: &str = "root";
+
+/// Renders the model hierarchy as a Graphviz DOT graph.
+///
+/// Every coupled model becomes a cluster holding a point node that stands for
+/// the model's own ports, so external input and output couplings are drawn as
+/// edges to and from that node. Edges are labeled `source_port -> destination_port`.
+pub fn model_to_dot(model: &Model) -> String {
+    let mut dot = String::new();
+    writeln!(dot, "digraph {} {{", quote(ROOT_NAME)).unwrap();
+    writeln!(dot, "    compound=true;").unwrap();
+    write_model(&mut dot, model, ROOT_NAME, 1);
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+fn write_model(dot: &mut String, model: &Model, full_name: &str, depth: usize) {
+    let indent = "    ".repeat(depth);
+    let short_name = full_name.rsplit('/').next().unwrap_or(full_name);
+    let label = format!("{} ({})", short_name, model.dynamic_type());
+    if model.submodel_names().is_empty() {
+        writeln!(
+            dot,
+            "{}{} [shape=box, label={}];",
+            indent,
+            quote(full_name),
+            quote(&label)
+        )
+        .unwrap();
+        return;
+    }
+
+    writeln!(
+        dot,
+        "{}subgraph {} {{",
+        indent,
+        quote(&format!("cluster_{}", full_name))
+    )
+    .unwrap();
+    writeln!(dot, "{}    label={};", indent, quote(&label)).unwrap();
+    writeln!(dot, "{}    {} [shape=point];", indent, quote(full_name)).unwrap();
+    for submodel_name in model.submodel_names() {
+        let submodel = model.submodel(submodel_name).unwrap();
+        let submodel_full_name = format!("{}/{}", full_name, submodel_name);
+        write_model(dot, submodel, &submodel_full_name, depth + 1);
+    }
+    for coupling in model.couplings().iter() {
+        let node = |submodel: Option<&str>| match submodel {
+            Some(submodel) => format!("{}/{}", full_name, submodel),
+            None => full_name.to_owned(),
+        };
+        writeln!(
+            dot,
+            "{}    {} -> {} [label={}];",
+            indent,
+            quote(&node(coupling.source_model)),
+            quote(&node(coupling.destination_model)),
+            quote(&format!(
+                "{} -> {}",
+                coupling.source_port, coupling.destination_port
+            ))
+        )
+        .unwrap();
+    }
+    writeln!(dot, "{}}}", indent).unwrap();
+}
+
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic::Dynamic;
+    use crate::model::{Resources, Structure};
+    use crate::simulator::Simulator;
+    use crate::test_utils::Passive;
+    use std::collections::BTreeMap;
+
+    fn atomic(full_name: &str) -> Simulator {
+        let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+        let model = Model::new(structure, Box::new(Passive::new()));
+        Simulator::new(full_name, model, Resources::default())
+    }
+
+    #[test]
+    fn test_model_to_dot() {
+        let mut inner_submodels = BTreeMap::new();
+        inner_submodels.insert("c".to_owned(), atomic("root/b/c"));
+        let inner = Structure::new(
+            &["in"],
+            &["out"],
+            inner_submodels,
+            &[("in", "c", "in")],
+            &[],
+            &[("c", "out", "out")],
+        );
+        let inner = Simulator::new(
+            "root/b",
+            Model::new(inner, Box::new(Passive::new())),
+            Resources::default(),
+        );
+        let mut submodels = BTreeMap::new();
+        submodels.insert("a".to_owned(), atomic("root/a"));
+        submodels.insert("b".to_owned(), inner);
+        let structure = Structure::new(
+            &[],
+            &[],
+            submodels,
+            &[],
+            &[("a", "out", "b", "in"), ("b", "out", "a", "in")],
+            &[],
+        );
+        let model = Model::new(structure, Box::new(Passive::new()));
+
+        let dot = model_to_dot(&model);
+        assert!(dot.starts_with("digraph \"root\" {"));
+        assert_eq!(dot.matches('{').count(), dot.matches('}').count());
+        assert!(dot.contains("subgraph \"cluster_root/b\" {"));
+        assert!(dot.contains("\"root/b/c\" [shape=box"));
+        for edge in [
+            "\"root/a\" -> \"root/b\" [label=\"out -> in\"];",
+            "\"root/b\" -> \"root/a\" [label=\"out -> in\"];",
+            "\"root/b\" -> \"root/b/c\" [label=\"in -> in\"];",
+            "\"root/b/c\" -> \"root/b\" [label=\"out -> out\"];",
+        ] {
+            assert!(dot.contains(edge), "missing edge {} in\n{}", edge, dot);
+        }
+        assert_eq!(dot.matches(" -> \"").count(), 4);
+    }
+}
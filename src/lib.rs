@@ -6,13 +6,26 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms
 
+pub mod breakpoint;
+pub mod channel_observer;
 pub mod containers;
+pub mod csv_logger;
 pub mod dynamic;
+pub mod errors;
 pub mod experiment;
+pub mod export;
 pub mod factory;
 pub mod logger;
+pub mod metrics;
 pub mod model;
 pub mod observer;
 pub mod root_simulator;
+pub mod sampling;
 pub mod simulator;
+#[cfg(test)]
+pub(crate) mod test_utils;
 pub mod time;
+pub mod trace_driver;
+pub mod trace_recorder;
+pub mod trajectory;
+pub mod utils;
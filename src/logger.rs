@@ -7,13 +7,18 @@
 // except according to those terms
 
 use std::{
+    collections::HashSet,
+    convert::TryFrom,
     fs::{DirBuilder, File, OpenOptions},
     io::{BufWriter, Write},
     mem::replace,
     path::PathBuf,
     str::FromStr,
+    sync::{Arc, Mutex},
 };
 
+#[cfg(feature = "gzip")]
+use flate2::write::GzEncoder;
 use serde_json::Map;
 
 use crate::{
@@ -92,9 +97,147 @@ enum LogEvent {
     },
 }
 
+/// Kinds of the records a `Logger` writes, named after their `EVENT` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogEventKind {
+    Init,
+    Outputs,
+    InternalTransition,
+    ExternalMailTransition,
+    ExternalTransition,
+    ConfluentTransition,
+    AfterSubmodelsTransition,
+}
+
+impl LogEventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Init => "INIT",
+            Self::Outputs => "OUTPUTS",
+            Self::InternalTransition => "INTERNAL_TRANSITION",
+            Self::ExternalMailTransition => "EXTERNAL_MAIL_TRANSITION",
+            Self::ExternalTransition => "EXTERNAL_TRANSITION",
+            Self::ConfluentTransition => "CONFLUENT_TRANSITION",
+            Self::AfterSubmodelsTransition => "AFTER_SUBMODELS_TRANSITION",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Init,
+            Self::Outputs,
+            Self::InternalTransition,
+            Self::ExternalMailTransition,
+            Self::ExternalTransition,
+            Self::ConfluentTransition,
+            Self::AfterSubmodelsTransition,
+        ]
+        .iter()
+        .copied()
+        .find(|kind| kind.name() == name)
+    }
+}
+
+/// Line terminator written after every log record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    None,
+}
+
+impl LineEnding {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+            Self::None => "",
+        }
+    }
+}
+
+/// When the buffered log records are written to the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushPolicy {
+    EveryEvent,
+    /// Flushes every given number of events, which is positive.
+    EveryN(usize),
+    OnFinish,
+}
+
+/// Compression of the log files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// Writes `*.log.gz` files, requires the `gzip` feature.
+    #[cfg(feature = "gzip")]
+    Gzip,
+}
+
+type LogSink = Box<dyn Write + Send>;
+
+/// The writer of `Logger::to_writer`, kept by the logger across runs.
+#[derive(Clone)]
+struct SharedSink(Arc<Mutex<LogSink>>);
+
+impl Write for SharedSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+enum LogStream {
+    Plain(BufWriter<LogSink>),
+    #[cfg(feature = "gzip")]
+    Gzip(GzEncoder<BufWriter<LogSink>>),
+}
+
+impl LogStream {
+    /// Flushes the stream and, for compressed streams, writes the trailer.
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(mut stream) => stream.flush(),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(stream) => stream.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for LogStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(stream) => stream.write(buf),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(stream) => stream.flush(),
+            #[cfg(feature = "gzip")]
+            Self::Gzip(stream) => stream.flush(),
+        }
+    }
+}
+
 pub struct Logger {
     log_event: LogEvent,
-    stream: Option<BufWriter<File>>,
+    stream: Option<LogStream>,
+    compression: Compression,
+    line_ending: LineEnding,
+    flush_policy: FlushPolicy,
+    unflushed_events: usize,
+    event_filter: Option<HashSet<LogEventKind>>,
+    time_window: Option<(Time, Time)>,
+    out_dir: Option<PathBuf>,
+    writer: Option<SharedSink>,
+    model_full_name: String,
+    iteration: u64,
 }
 
 impl Observer for Logger {
@@ -102,6 +245,85 @@ impl Observer for Logger {
         Logger::new()
     }
 
+    /// Reads the options from the `observer_config` of the model class:
+    /// `line_ending` is one of `"lf"`, `"crlf"` or `"none"`; `flush_policy` is
+    /// `"every_event"`, `"on_finish"` or a positive number of events between
+    /// flushes; `event_filter` is a list of `EVENT` names to record;
+    /// `time_window` is the `[from, to]` times of the recorded events;
+    /// `compression` is `"none"` or `"gzip"`; `out_dir` is the directory of
+    /// the logs, see `with_out_dir`.
+    fn config(&mut self, observer_config: &Value) {
+        if let Some(out_dir) = observer_config.get("out_dir") {
+            let out_dir = out_dir
+                .as_str()
+                .unwrap_or_else(|| panic!("Log directory must be a string, got {}", out_dir));
+            self.out_dir = Some(PathBuf::from(out_dir));
+        }
+        match observer_config.get("compression").and_then(Value::as_str) {
+            Some("none") => self.compression = Compression::None,
+            #[cfg(feature = "gzip")]
+            Some("gzip") => self.compression = Compression::Gzip,
+            #[cfg(not(feature = "gzip"))]
+            Some("gzip") => panic!("Gzip compression requires the `gzip` feature"),
+            Some(compression) => panic!("Unknown compression '{}'", compression),
+            None => {}
+        }
+        if let Some(event_filter) = observer_config.get("event_filter") {
+            let event_filter = event_filter
+                .as_array()
+                .unwrap_or_else(|| panic!("Event filter must be a list of event names"))
+                .iter()
+                .map(|name| {
+                    name.as_str()
+                        .and_then(LogEventKind::from_name)
+                        .unwrap_or_else(|| panic!("Unknown log event {}", name))
+                })
+                .collect();
+            self.event_filter = Some(event_filter);
+        }
+        if let Some(time_window) = observer_config.get("time_window") {
+            let bound = |index: usize| {
+                time_window
+                    .get(index)
+                    .and_then(|time| Time::try_from(time).ok())
+                    .unwrap_or_else(|| {
+                        panic!("Time window must be [from, to], got {}", time_window)
+                    })
+            };
+            self.time_window = Some((bound(0), bound(1)));
+        }
+        match observer_config.get("flush_policy") {
+            Some(Value::String(policy)) if policy == "every_event" => {
+                self.flush_policy = FlushPolicy::EveryEvent
+            }
+            Some(Value::String(policy)) if policy == "on_finish" => {
+                self.flush_policy = FlushPolicy::OnFinish
+            }
+            Some(Value::Number(events)) => {
+                let events = events
+                    .as_u64()
+                    .and_then(|events| usize::try_from(events).ok())
+                    .filter(|events| *events > 0)
+                    .unwrap_or_else(|| {
+                        panic!(
+                            "Flush policy must be a positive number of events, got {}",
+                            events
+                        )
+                    });
+                self.flush_policy = FlushPolicy::EveryN(events)
+            }
+            Some(policy) => panic!("Unknown flush policy '{}'", policy),
+            None => {}
+        }
+        match observer_config.get("line_ending").and_then(Value::as_str) {
+            Some("lf") => self.line_ending = LineEnding::Lf,
+            Some("crlf") => self.line_ending = LineEnding::Crlf,
+            Some("none") => self.line_ending = LineEnding::None,
+            Some(line_ending) => panic!("Unknown line ending '{}'", line_ending),
+            None => {}
+        }
+    }
+
     fn init_observer(&mut self, config: &Value) {
         let sim_dir = config
             .as_object()
@@ -117,28 +339,47 @@ impl Observer for Logger {
             .unwrap()
             .as_str()
             .unwrap();
-        let mut model_log_file = PathBuf::from_str(sim_dir).unwrap();
-        model_log_file.push(model_path);
-        let model_log_file = model_log_file.with_extension("log");
-        let model_log_dir = model_log_file.parent().unwrap();
-
-        if !model_log_dir.exists() {
-            DirBuilder::new()
-                .recursive(true)
-                .create(model_log_dir)
-                .unwrap()
-        }
-        let log_file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .open(model_log_file)
-            .unwrap();
+        let sink: LogSink = match &self.writer {
+            Some(writer) => Box::new(writer.clone()),
+            None => Box::new(self.open_log_file(sim_dir, model_path)),
+        };
 
+        self.model_full_name = model_path.to_owned();
+        self.iteration = config
+            .get("iteration")
+            .and_then(Value::as_u64)
+            .unwrap_or_default();
         self.log_event = LogEvent::None;
-        self.stream = Some(BufWriter::new(log_file))
+        let sink = BufWriter::new(sink);
+        self.stream = Some(match self.compression {
+            Compression::None => LogStream::Plain(sink),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                LogStream::Gzip(GzEncoder::new(sink, flate2::Compression::default()))
+            }
+        });
+        self.unflushed_events = 0;
     }
 
+    /// Starts the file with a `HEADER` record identifying the model, which is
+    /// written regardless of the event filter.
     fn on_init(&mut self, model: &Model, init_time: Time, init_value: &Value, t_next: Time) {
+        let mut header_map = Map::new();
+        header_map.extend([
+            (
+                "model".to_owned(),
+                Value::String(self.model_full_name.clone()),
+            ),
+            ("iteration".to_owned(), Value::from(self.iteration)),
+            ("init_time".to_owned(), Value::from(&init_time)),
+        ]);
+        let mut header = Map::new();
+        header.insert("HEADER".to_owned(), Value::Object(header_map));
+        self.internal_write(&Value::Object(header));
+
+        if !self.records(LogEventKind::Init, init_time) {
+            return;
+        }
         let init_state = model.state();
         let log_event = LogEvent::Init {
             init_time,
@@ -150,6 +391,9 @@ impl Observer for Logger {
     }
 
     fn on_outputs(&mut self, _model: &Model, sim_time: Time, bag: &Bag) {
+        if !self.records(LogEventKind::Outputs, sim_time) {
+            return;
+        }
         let log_event = LogEvent::Outputs {
             sim_time,
             bag: bag.to_vec(),
@@ -157,7 +401,10 @@ impl Observer for Logger {
         self.write(log_event);
     }
 
-    fn before_internal_transition(&mut self, model: &Model, sim_time: Time) {
+    fn before_internal_transition(&mut self, model: &Model, sim_time: Time, _t_last: Time) {
+        if !self.records(LogEventKind::InternalTransition, sim_time) {
+            return;
+        }
         let from_state = model.state();
         self.log_event = LogEvent::PreInternalTransition {
             sim_time,
@@ -190,6 +437,9 @@ impl Observer for Logger {
         x_bag: &Bag,
         elapsed: Time,
     ) {
+        if !self.records(LogEventKind::ExternalTransition, sim_time) {
+            return;
+        }
         let from_state = model.state();
         self.log_event = LogEvent::PreExternalTransition {
             sim_time,
@@ -228,6 +478,9 @@ impl Observer for Logger {
         mail: &Mail,
         elapsed: Time,
     ) {
+        if !self.records(LogEventKind::ExternalMailTransition, sim_time) {
+            return;
+        }
         let from_state = model.state();
         self.log_event = LogEvent::PreExternalMailTransition {
             sim_time,
@@ -259,7 +512,16 @@ impl Observer for Logger {
         }
     }
 
-    fn before_confluent_transition(&mut self, model: &Model, sim_time: Time, x_bag: &Bag) {
+    fn before_confluent_transition(
+        &mut self,
+        model: &Model,
+        sim_time: Time,
+        x_bag: &Bag,
+        _t_last: Time,
+    ) {
+        if !self.records(LogEventKind::ConfluentTransition, sim_time) {
+            return;
+        }
         let from_state = model.state();
         self.log_event = LogEvent::PreConfluentTransition {
             sim_time,
@@ -289,6 +551,9 @@ impl Observer for Logger {
     }
 
     fn after_submodels_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {
+        if !self.records(LogEventKind::AfterSubmodelsTransition, sim_time) {
+            return;
+        }
         let state = model.state();
         let log_event = LogEvent::AfterSubmodelsTransition {
             state,
@@ -299,7 +564,9 @@ impl Observer for Logger {
     }
 
     fn after_finish(&mut self, _model: &Model, _sim_time: Time) {
-        self.flush();
+        if let Some(stream) = self.stream.take() {
+            stream.finish().unwrap();
+        }
     }
 
     fn before_finish(&mut self, _model: &Model, _sim_time: Time) {}
@@ -320,6 +587,106 @@ impl Logger {
         Self {
             log_event: LogEvent::None,
             stream: None,
+            compression: Compression::None,
+            line_ending: LineEnding::Lf,
+            flush_policy: FlushPolicy::EveryEvent,
+            unflushed_events: 0,
+            event_filter: None,
+            time_window: None,
+            out_dir: None,
+            writer: None,
+            model_full_name: String::new(),
+            iteration: 0,
+        }
+    }
+
+    /// Writes the log to `writer` instead of a file under the simulation
+    /// directory, e.g. to stdout or a socket. The writer serves one model; the
+    /// runs of the model, e.g. after `RootSimulator::reset`, append their log
+    /// to it.
+    pub fn to_writer(writer: Box<dyn Write + Send>) -> Self {
+        let mut logger = Self::new();
+        logger.writer = Some(SharedSink(Arc::new(Mutex::new(writer))));
+        logger
+    }
+
+    /// Records only the events of the given kinds; by default every event is
+    /// recorded.
+    pub fn with_event_filter(mut self, event_filter: HashSet<LogEventKind>) -> Self {
+        self.event_filter = Some(event_filter);
+        self
+    }
+
+    /// Records only the events at a time in `[from, to]`, to capture one phase
+    /// of a long simulation; the header is always written.
+    pub fn with_time_window(mut self, from: Time, to: Time) -> Self {
+        self.time_window = Some((from, to));
+        self
+    }
+
+    /// Writes the logs under `out_dir` rather than directly in the simulation
+    /// directory; a relative `out_dir` is taken from the simulation directory.
+    pub fn with_out_dir<P: Into<PathBuf>>(mut self, out_dir: P) -> Self {
+        self.out_dir = Some(out_dir.into());
+        self
+    }
+
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn with_flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.flush_policy = flush_policy;
+        self
+    }
+
+    pub fn with_line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Creates the log file of `model_path` under `sim_dir`, along with its
+    /// directories.
+    fn open_log_file(&self, sim_dir: &str, model_path: &str) -> File {
+        let mut model_log_file = PathBuf::from_str(sim_dir).unwrap();
+        if let Some(out_dir) = &self.out_dir {
+            model_log_file.push(out_dir);
+        }
+        model_log_file.push(model_path);
+        let model_log_file = match self.compression {
+            Compression::None => model_log_file.with_extension("log"),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => model_log_file.with_extension("log.gz"),
+        };
+        let model_log_dir = model_log_file.parent().unwrap();
+
+        if !model_log_dir.exists() {
+            DirBuilder::new()
+                .recursive(true)
+                .create(model_log_dir)
+                .unwrap()
+        }
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(model_log_file)
+            .unwrap()
+    }
+
+    /// Whether the events of `kind` at `sim_time` pass the event filter and
+    /// the time window; checked before the event is built, so that the states
+    /// of filtered out events are not even serialized.
+    fn records(&self, kind: LogEventKind, sim_time: Time) -> bool {
+        if let Some(event_filter) = &self.event_filter {
+            if !event_filter.contains(&kind) {
+                return false;
+            }
+        }
+        match self.time_window {
+            Some((from, to)) => from <= sim_time && sim_time <= to,
+            None => true,
         }
     }
 
@@ -469,8 +836,15 @@ impl Logger {
         if let Some(stream) = &mut self.stream {
             let val = serde_json::to_string(value).unwrap();
             stream.write_all(val.as_bytes()).unwrap();
-            stream.write_all("\n".as_bytes()).unwrap();
-            self.flush();
+            stream
+                .write_all(self.line_ending.as_str().as_bytes())
+                .unwrap();
+            self.unflushed_events += 1;
+            match self.flush_policy {
+                FlushPolicy::EveryEvent => self.flush(),
+                FlushPolicy::EveryN(events) if self.unflushed_events >= events => self.flush(),
+                _ => {}
+            }
         }
     }
 
@@ -478,5 +852,412 @@ impl Logger {
         if let Some(stream) = &mut self.stream {
             stream.flush().unwrap();
         }
+        self.unflushed_events = 0;
+    }
+}
+
+impl Drop for Logger {
+    fn drop(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::collections::BTreeMap;
+    use std::fs::read_to_string;
+    use std::rc::Rc;
+
+    use rand::Rng;
+
+    use super::*;
+    use crate::containers::{Msg, SimRng};
+    use crate::dynamic::{ConfluentPolicy, Dynamic};
+    use crate::errors::ExdsdevsError;
+    use crate::model::Structure;
+    use crate::observer::{ObserverFactory, ObserverFactoryStorage};
+    use crate::test_utils::{build_root_coupled, build_root_observed, temp_dir, Clock};
+
+    fn run_logged(name: &str, logger: fn() -> Logger) -> String {
+        let sim_dir = temp_dir(name);
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(20),
+            &sim_dir,
+            &|| vec![Box::new(logger())],
+        );
+        root.run().unwrap();
+        let log = read_to_string(sim_dir.join("root/clock.log")).unwrap();
+        std::fs::remove_dir_all(&sim_dir).unwrap();
+        log
+    }
+
+    /// Log sink readable once the logger is gone.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_to_writer() {
+        let buffer = SharedBuffer::default();
+        let sim_dir = temp_dir("logger_to_writer");
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(20),
+            &sim_dir,
+            &|| vec![Box::new(Logger::to_writer(Box::new(buffer.clone())))],
+        );
+        root.run().unwrap();
+        assert!(!sim_dir.exists());
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(log.starts_with("{\"HEADER\":{\"model\":\"root/clock\""));
+        let file_log = run_logged("logger_file", Logger::new);
+        assert_eq!(log, file_log);
+
+        // A rerun appends to the same writer.
+        root.reset(0).unwrap();
+        root.run().unwrap();
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(log, file_log.repeat(2));
+    }
+
+    #[test]
+    fn test_line_ending() {
+        let log = run_logged("logger_lf", Logger::new);
+        assert!(log.ends_with("}\n") && !log.contains('\r'));
+        assert!(log.lines().count() > 1);
+
+        let log = run_logged("logger_crlf", || {
+            Logger::new().with_line_ending(LineEnding::Crlf)
+        });
+        assert!(log
+            .split_terminator("\r\n")
+            .all(|line| line.starts_with('{')));
+        assert!(log.ends_with("}\r\n"));
+
+        let log = run_logged("logger_none", || {
+            Logger::new().with_line_ending(LineEnding::None)
+        });
+        assert!(!log.contains('\n') && log.contains("}{"));
+    }
+
+    /// Ticks every time unit, sends a random draw on `out` and remembers the
+    /// values received on `in`, in their order.
+    struct Talker {
+        draw: i64,
+        received: Vec<Value>,
+    }
+
+    impl Dynamic for Talker {
+        fn new() -> Self {
+            Talker {
+                draw: 0,
+                received: Vec::new(),
+            }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "talker".to_owned()
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, _: Time, rng: &mut SimRng) {
+            self.draw = rng.gen_range(0..1000);
+        }
+
+        fn external_transition(
+            &mut self,
+            _: &Structure,
+            _: Time,
+            _: Time,
+            x_bag: &Bag,
+            _: &mut SimRng,
+        ) {
+            self.received
+                .extend(x_bag.iter().map(|msg| msg.value().clone()));
+        }
+
+        fn confluent_policy(&self) -> ConfluentPolicy {
+            ConfluentPolicy::InternalThenExternal
+        }
+
+        fn output(&self, _: &Structure, _: Time) -> Bag {
+            vec![Msg::new("out", Value::from(self.draw))]
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Value(1)
+        }
+
+        fn state(&self) -> Value {
+            serde_json::json!({"draw": self.draw, "received": self.received})
+        }
+    }
+
+    #[test]
+    fn test_logs_are_reproducible() {
+        let run = |name: &str| {
+            let sim_dir = temp_dir(name);
+            let children = ["a", "b", "c", "d"]
+                .iter()
+                .map(|&child| (child, Box::new(Talker::new()) as Box<dyn Dynamic + Send>))
+                .collect();
+            // The talkers are imminent together, `d` gets the messages of the
+            // three others in one bag at every event.
+            let couplings = [
+                ("a", "out", "d", "in"),
+                ("b", "out", "d", "in"),
+                ("c", "out", "d", "in"),
+                ("d", "out", "a", "in"),
+            ];
+            let mut root =
+                build_root_coupled(children, &couplings, Time::Value(30), &sim_dir, &|| {
+                    vec![Box::new(Logger::new())]
+                });
+            root.run().unwrap();
+            let logs: Vec<String> = ["a", "b", "c", "d"]
+                .iter()
+                .map(|child| read_to_string(sim_dir.join(format!("root/{}.log", child))).unwrap())
+                .collect();
+            std::fs::remove_dir_all(&sim_dir).unwrap();
+            logs
+        };
+        let first = run("logger_repeat_1");
+        assert!(first.iter().all(|log| !log.is_empty()));
+        assert!(first[3].contains("\"EVENT\":\"CONFLUENT_TRANSITION\""));
+        for attempt in 2..5 {
+            assert_eq!(run(&format!("logger_repeat_{}", attempt)), first);
+        }
+    }
+
+    #[test]
+    fn test_flush_policy() {
+        let sim_dir = temp_dir("logger_flush");
+        let log_file = sim_dir.join("root/clock.log");
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(20),
+            &sim_dir,
+            &|| {
+                vec![Box::new(
+                    Logger::new().with_flush_policy(FlushPolicy::OnFinish),
+                )]
+            },
+        );
+        root.run_until(Time::Value(10)).unwrap();
+        assert_eq!(read_to_string(&log_file).unwrap(), "");
+        root.run().unwrap();
+        let finished = read_to_string(&log_file).unwrap();
+        assert!(!finished.is_empty());
+
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(20),
+            &sim_dir,
+            &|| {
+                vec![Box::new(
+                    Logger::new().with_flush_policy(FlushPolicy::OnFinish),
+                )]
+            },
+        );
+        root.run_until(Time::Value(20)).unwrap();
+        drop(root);
+        assert_eq!(read_to_string(&log_file).unwrap(), finished);
+
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(20),
+            &sim_dir,
+            &|| {
+                vec![Box::new(
+                    Logger::new().with_flush_policy(FlushPolicy::EveryN(2)),
+                )]
+            },
+        );
+        root.run_until(Time::Value(20)).unwrap();
+        let flushed = read_to_string(&log_file).unwrap();
+        assert!(flushed.lines().count() >= finished.lines().count() - 1);
+        std::fs::remove_dir_all(&sim_dir).unwrap();
+    }
+
+    #[test]
+    fn test_event_filter() {
+        let log = run_logged("logger_filter", || {
+            Logger::new().with_event_filter([LogEventKind::Outputs].iter().copied().collect())
+        });
+        let mut lines = log.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            r#"{"HEADER":{"model":"root/clock","iteration":0,"init_time":0}}"#
+        );
+        assert!(lines.all(|line| line.contains("\"EVENT\":\"OUTPUTS\"")));
+        assert!(log.lines().count() > 2);
+    }
+
+    /// Counts the calls to `state`.
+    struct Counted(Rc<Cell<usize>>);
+
+    impl Dynamic for Counted {
+        fn new() -> Self {
+            Counted(Rc::default())
+        }
+
+        fn dynamic_type(&self) -> String {
+            "counted".to_owned()
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Inf
+        }
+
+        fn state(&self) -> Value {
+            self.0.set(self.0.get() + 1);
+            Value::Null
+        }
+    }
+
+    #[test]
+    fn test_filtered_events_are_not_built() {
+        let calls = Rc::new(Cell::new(0));
+        let structure = Structure::new(&[], &[], BTreeMap::new(), &[], &[], &[]);
+        let model = Model::new(structure, Box::new(Counted(calls.clone())));
+        let mut logger =
+            Logger::new().with_event_filter([LogEventKind::Outputs].iter().copied().collect());
+        let sim_time = Time::Value(1);
+        logger.before_internal_transition(&model, sim_time, Time::Value(0));
+        logger.after_internal_transition(&model, sim_time, Time::Inf);
+        logger.before_external_transition(&model, sim_time, &Bag::new(), Time::Value(1));
+        logger.after_external_transition(&model, sim_time, Time::Inf);
+        logger.before_confluent_transition(&model, sim_time, &Bag::new(), Time::Value(0));
+        logger.after_confluent_transition(&model, sim_time, Time::Inf);
+        logger.after_submodels_transition(&model, sim_time, Time::Inf);
+        assert_eq!(calls.get(), 0);
+
+        // Neither are the events out of the time window.
+        let mut logger = Logger::new().with_time_window(Time::Value(5), Time::Value(10));
+        logger.before_internal_transition(&model, sim_time, Time::Value(0));
+        logger.after_internal_transition(&model, sim_time, Time::Inf);
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_gzip_compression() {
+        use std::io::Read;
+
+        let sim_dir = temp_dir("logger_gzip");
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(20),
+            &sim_dir,
+            &|| vec![Box::new(Logger::new().with_compression(Compression::Gzip))],
+        );
+        root.run().unwrap();
+        let log_file = File::open(sim_dir.join("root/clock.log.gz")).unwrap();
+        let mut log = String::new();
+        flate2::read::GzDecoder::new(log_file)
+            .read_to_string(&mut log)
+            .unwrap();
+        std::fs::remove_dir_all(&sim_dir).unwrap();
+        assert_eq!(log, run_logged("logger_plain", Logger::new));
+    }
+
+    #[test]
+    fn test_registered_logger_out_dir() {
+        let mut storage = ObserverFactoryStorage::new();
+        storage.add_observer_factory("std_logger", ObserverFactory::<Logger>::new());
+        assert_eq!(storage.observer_classes(), vec!["std_logger"]);
+        assert!(matches!(
+            storage.get_observer("csv_logger"),
+            Err(ExdsdevsError::ErrorBuildSimulator(_))
+        ));
+
+        let sim_dir = temp_dir("logger_out_dir");
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(20),
+            &sim_dir,
+            &|| {
+                let mut logger = storage.get_observer("std_logger").unwrap();
+                logger.config(&serde_json::json!({ "out_dir": "logs" }));
+                vec![logger]
+            },
+        );
+        root.run().unwrap();
+        let log = read_to_string(sim_dir.join("logs/root/clock.log")).unwrap();
+        std::fs::remove_dir_all(&sim_dir).unwrap();
+        assert!(log.lines().count() > 1);
+    }
+
+    #[test]
+    fn test_time_window() {
+        let full_log = run_logged("logger_full", Logger::new);
+        let log = run_logged("logger_time_window", || {
+            Logger::new().with_time_window(Time::Value(5), Time::Value(10))
+        });
+        let times = |log: &str| -> Vec<Time> {
+            log.lines()
+                .skip(1)
+                .map(|line| {
+                    let record: Value = serde_json::from_str(line).unwrap();
+                    Time::try_from(&record["TIME"]).unwrap()
+                })
+                .collect()
+        };
+        assert_eq!(log.lines().next(), full_log.lines().next());
+        let expected: Vec<Time> = times(&full_log)
+            .into_iter()
+            .filter(|time| *time >= Time::Value(5) && *time <= Time::Value(10))
+            .collect();
+        assert!(!expected.is_empty());
+        assert_eq!(times(&log), expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "Flush policy must be a positive number of events, got 0")]
+    fn test_config_zero_flush_policy() {
+        Logger::new().config(&serde_json::json!({ "flush_policy": 0 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Flush policy must be a positive number of events, got 2.5")]
+    fn test_config_fractional_flush_policy() {
+        Logger::new().config(&serde_json::json!({ "flush_policy": 2.5 }));
+    }
+
+    #[test]
+    fn test_config() {
+        let mut logger = Logger::new();
+        logger.config(&serde_json::json!({ "line_ending": "crlf" }));
+        assert_eq!(logger.line_ending, LineEnding::Crlf);
+        logger.config(&serde_json::json!({}));
+        assert_eq!(logger.line_ending, LineEnding::Crlf);
+        logger.config(&serde_json::json!({ "flush_policy": 100 }));
+        assert_eq!(logger.flush_policy, FlushPolicy::EveryN(100));
+        logger.config(&serde_json::json!({ "flush_policy": "on_finish" }));
+        assert_eq!(logger.flush_policy, FlushPolicy::OnFinish);
+        logger.config(&serde_json::json!({ "event_filter": ["INIT", "OUTPUTS"] }));
+        assert_eq!(
+            logger.event_filter,
+            Some(
+                [LogEventKind::Init, LogEventKind::Outputs]
+                    .iter()
+                    .copied()
+                    .collect()
+            )
+        );
+        logger.config(&serde_json::json!({ "time_window": [5, "Inf"] }));
+        assert_eq!(logger.time_window, Some((Time::Value(5), Time::Inf)));
     }
 }
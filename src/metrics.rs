@@ -0,0 +1,200 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use std::time::{Duration, Instant};
+
+use serde_json::Map;
+
+use crate::{
+    containers::{Bag, Mail, Value},
+    model::Model,
+    observer::Observer,
+    time::Time,
+};
+
+/// Counts the transitions and output messages of a model and measures the wall
+/// clock time spent in its transition and output functions. Unlike `Logger` it never
+/// serializes the model state, so it is cheap enough to stay attached.
+#[derive(Default)]
+pub struct Metrics {
+    internal_transitions: u64,
+    external_transitions: u64,
+    external_mail_transitions: u64,
+    confluent_transitions: u64,
+    output_messages: u64,
+    transition_time: Duration,
+    transition_start: Option<Instant>,
+    output_time: Duration,
+    output_start: Option<Instant>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn start_transition(&mut self) {
+        self.transition_start = Some(Instant::now());
+    }
+
+    fn finish_transition(&mut self) {
+        if let Some(start) = self.transition_start.take() {
+            self.transition_time += start.elapsed();
+        }
+    }
+}
+
+impl Observer for Metrics {
+    fn new() -> Metrics {
+        Metrics::new()
+    }
+
+    fn init_observer(&mut self, _init_config: &Value) {
+        *self = Metrics::new();
+    }
+
+    fn before_output(&mut self, _model: &Model, _sim_time: Time) {
+        self.output_start = Some(Instant::now());
+    }
+
+    fn on_outputs(&mut self, _model: &Model, _sim_time: Time, bag: &Bag) {
+        self.output_messages += bag.len() as u64;
+        if let Some(start) = self.output_start.take() {
+            self.output_time += start.elapsed();
+        }
+    }
+
+    fn before_internal_transition(&mut self, _model: &Model, _sim_time: Time, _t_last: Time) {
+        self.start_transition();
+    }
+
+    fn after_internal_transition(&mut self, _model: &Model, _sim_time: Time, _t_next: Time) {
+        self.internal_transitions += 1;
+        self.finish_transition();
+    }
+
+    fn before_external_transition(
+        &mut self,
+        _model: &Model,
+        _sim_time: Time,
+        _x_bag: &Bag,
+        _elapsed: Time,
+    ) {
+        self.start_transition();
+    }
+
+    fn after_external_transition(&mut self, _model: &Model, _sim_time: Time, _t_next: Time) {
+        self.external_transitions += 1;
+        self.finish_transition();
+    }
+
+    fn before_external_mail_transition(
+        &mut self,
+        _model: &Model,
+        _sim_time: Time,
+        _mail: &Mail,
+        _elapsed: Time,
+    ) {
+        self.start_transition();
+    }
+
+    fn after_external_mail_transition(&mut self, _model: &Model, _sim_time: Time, _t_next: Time) {
+        self.external_mail_transitions += 1;
+        self.finish_transition();
+    }
+
+    fn before_confluent_transition(
+        &mut self,
+        _model: &Model,
+        _sim_time: Time,
+        _x_bag: &Bag,
+        _t_last: Time,
+    ) {
+        self.start_transition();
+    }
+
+    fn after_confluent_transition(&mut self, _model: &Model, _sim_time: Time, _t_next: Time) {
+        self.confluent_transitions += 1;
+        self.finish_transition();
+    }
+
+    fn result(&self) -> Option<Value> {
+        let mut result_map = Map::new();
+        result_map.extend([
+            (
+                "INTERNAL_TRANSITIONS".to_owned(),
+                Value::from(self.internal_transitions),
+            ),
+            (
+                "EXTERNAL_TRANSITIONS".to_owned(),
+                Value::from(self.external_transitions),
+            ),
+            (
+                "EXTERNAL_MAIL_TRANSITIONS".to_owned(),
+                Value::from(self.external_mail_transitions),
+            ),
+            (
+                "CONFLUENT_TRANSITIONS".to_owned(),
+                Value::from(self.confluent_transitions),
+            ),
+            (
+                "OUTPUT_MESSAGES".to_owned(),
+                Value::from(self.output_messages),
+            ),
+            (
+                "TRANSITION_TIME_NS".to_owned(),
+                Value::from(self.transition_time.as_nanos() as u64),
+            ),
+            (
+                "OUTPUT_TIME_NS".to_owned(),
+                Value::from(self.output_time.as_nanos() as u64),
+            ),
+        ]);
+        Some(Value::Object(result_map))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic::Dynamic;
+    use crate::test_utils::{build_root_observed, child_state, Clock, Passive};
+
+    #[test]
+    fn test_metrics_counts_transitions() {
+        let mut root = build_root_observed(
+            vec![
+                ("clock", Box::new(Clock::new())),
+                ("idle", Box::new(Passive)),
+            ],
+            Time::Value(50),
+            &std::path::PathBuf::new(),
+            &|| vec![Box::new(Metrics::new())],
+        );
+        root.run().unwrap();
+
+        let results = root.results();
+        assert_eq!(
+            results.keys().collect::<Vec<_>>(),
+            vec!["root/clock", "root/idle"]
+        );
+        let events = child_state(&root, "clock")["events"]
+            .as_array()
+            .unwrap()
+            .len() as u64;
+        let clock = &results["root/clock"][0];
+        assert_eq!(clock["INTERNAL_TRANSITIONS"], Value::from(events));
+        assert_eq!(clock["EXTERNAL_TRANSITIONS"], Value::from(0));
+        // The timer resolution may round short outputs down to 0 ns.
+        assert!(clock["OUTPUT_TIME_NS"].is_u64());
+        assert_eq!(
+            results["root/idle"][0]["INTERNAL_TRANSITIONS"],
+            Value::from(0)
+        );
+    }
+}
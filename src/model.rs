@@ -6,18 +6,21 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms
 
-use rand::rngs::StdRng;
+use serde::de::{Deserializer, MapAccess, Visitor};
 use serde::{Deserialize, Serialize};
 
-use crate::containers::{Bag, Mail, MailItem, Msg, Value};
+use crate::containers::{Bag, Mail, MailItem, Msg, SimRng, Value};
 use crate::dynamic::{Dynamic, DynamicFactoryStorage};
-use crate::observer::ObserverFactoryStorage;
+use crate::errors::ExdsdevsError;
+use crate::observer::{Observer, ObserverFactoryStorage};
 use crate::simulator::Simulator;
 use crate::time::Time;
+use crate::utils::read_json_from_file;
 
 use std::collections::btree_map::{Iter, IterMut};
 use std::collections::VecDeque;
-use std::fs::read_to_string;
+use std::fmt;
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 pub use std::{
@@ -25,13 +28,44 @@ pub use std::{
     rc::Rc,
 };
 
+/// Read-only data shared by the dynamics: `local` comes from the model class,
+/// `global` is shared by every model of a run without being copied.
 #[derive(Default, Debug, Clone)]
 pub struct Resources {
     pub local: Value,
     pub global: Arc<BTreeMap<String, Value>>,
 }
 
-#[derive(Clone)]
+impl Resources {
+    /// Keeps the resources already set and takes the missing ones from `other`.
+    pub(crate) fn or(self, other: &Resources) -> Resources {
+        Resources {
+            local: if self.local.is_null() {
+                other.local.clone()
+            } else {
+                self.local
+            },
+            global: if self.global.is_empty() {
+                other.global.clone()
+            } else {
+                self.global
+            },
+        }
+    }
+
+    /// Shares the global resources of the parent model when there are none.
+    pub(crate) fn inherit_global(&mut self, global: &Arc<BTreeMap<String, Value>>) {
+        if self.global.is_empty() {
+            self.global = global.clone();
+        }
+    }
+}
+
+/// Forwards the messages on the input port `source_port` of a coupled model to
+/// the input port `destination_model_port` of its submodel
+/// `destination_model`. The two names need not match, so a coupled model can
+/// expose a library model under its own port names.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ExternalInputCoupling {
     pub source_port: String,
     pub destination_model: String,
@@ -48,7 +82,7 @@ impl ExternalInputCoupling {
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InternalCoupling {
     pub source_model: String,
     pub source_model_port: String,
@@ -72,7 +106,10 @@ impl InternalCoupling {
     }
 }
 
-#[derive(Clone)]
+/// Forwards the outputs on the port `source_model_port` of the submodel
+/// `source_model` to the output port `destination_port` of the coupled model,
+/// under that name; see `ExternalInputCoupling`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ExternalOutputCoupling {
     pub source_model: String,
     pub source_model_port: String,
@@ -89,24 +126,444 @@ impl ExternalOutputCoupling {
     }
 }
 
+/// A structural edit of a coupled model, returned by
+/// `Dynamic::structure_changes` and applied by the simulator after the
+/// transitions of the current event.
+pub enum StructureChange {
+    /// Adds the submodel `name`, which must not be in use. The simulator is
+    /// renamed `<model full name>/<name>`, with its own submodels under it,
+    /// and initialized at the current time with its `init_value` (and the ones
+    /// of its own submodels) already set.
+    AddSubmodel {
+        name: String,
+        simulator: Box<Simulator>,
+    },
+    /// Finishes and removes a submodel together with the couplings using it.
+    RemoveSubmodel(String),
+    AddExternalInputCoupling(ExternalInputCoupling),
+    RemoveExternalInputCoupling(ExternalInputCoupling),
+    AddInternalCoupling(InternalCoupling),
+    RemoveInternalCoupling(InternalCoupling),
+    AddExternalOutputCoupling(ExternalOutputCoupling),
+    RemoveExternalOutputCoupling(ExternalOutputCoupling),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CouplingKind {
+    ExternalInput,
+    Internal,
+    ExternalOutput,
+}
+
+/// A coupling of a coupled model, `None` as a model stands for the coupled
+/// model itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coupling<'a> {
+    pub kind: CouplingKind,
+    pub source_model: Option<&'a str>,
+    pub source_port: &'a str,
+    pub destination_model: Option<&'a str>,
+    pub destination_port: &'a str,
+}
+
+/// All couplings of a coupled model: external input, internal and external
+/// output couplings in this order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CouplingReport<'a> {
+    couplings: Vec<Coupling<'a>>,
+}
+
+impl<'a> CouplingReport<'a> {
+    fn new(structure: &'a Structure) -> Self {
+        let external_input = structure
+            .external_input_couplings
+            .iter()
+            .map(|coupling| Coupling {
+                kind: CouplingKind::ExternalInput,
+                source_model: None,
+                source_port: &coupling.source_port,
+                destination_model: Some(&coupling.destination_model),
+                destination_port: &coupling.destination_model_port,
+            });
+        let internal = structure
+            .internal_couplings
+            .iter()
+            .map(|coupling| Coupling {
+                kind: CouplingKind::Internal,
+                source_model: Some(&coupling.source_model),
+                source_port: &coupling.source_model_port,
+                destination_model: Some(&coupling.destination_model),
+                destination_port: &coupling.destination_model_port,
+            });
+        let external_output = structure
+            .external_output_couplings
+            .iter()
+            .map(|coupling| Coupling {
+                kind: CouplingKind::ExternalOutput,
+                source_model: Some(&coupling.source_model),
+                source_port: &coupling.source_model_port,
+                destination_model: None,
+                destination_port: &coupling.destination_port,
+            });
+        CouplingReport {
+            couplings: external_input
+                .chain(internal)
+                .chain(external_output)
+                .collect(),
+        }
+    }
+
+    pub fn couplings(&self) -> &[Coupling<'a>] {
+        &self.couplings
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Coupling<'a>> {
+        self.couplings.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.couplings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.couplings.is_empty()
+    }
+}
+
+/// The dynamic of a model, with whether it may be moved to another thread.
+pub(crate) enum DynamicBox {
+    Local(Box<dyn Dynamic>),
+    Send(Box<dyn Dynamic + Send>),
+}
+
+impl std::ops::Deref for DynamicBox {
+    type Target = dyn Dynamic;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Local(dynamic) => dynamic.as_ref(),
+            Self::Send(dynamic) => dynamic.as_ref(),
+        }
+    }
+}
+
+impl std::ops::DerefMut for DynamicBox {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Self::Local(dynamic) => dynamic.as_mut(),
+            Self::Send(dynamic) => dynamic.as_mut(),
+        }
+    }
+}
+
 pub struct Model {
     pub structure: Structure,
-    pub dynamic: Box<dyn Dynamic>,
+    dynamic: DynamicBox,
 }
 
 impl Model {
     pub fn new(structure: Structure, dynamic: Box<dyn Dynamic>) -> Self {
+        Model {
+            structure,
+            dynamic: DynamicBox::Local(dynamic),
+        }
+    }
+
+    /// Same as `new` with a dynamic that may be moved to another thread, which
+    /// `RootSimulator::run_parallel` requires.
+    pub fn new_send(structure: Structure, dynamic: Box<dyn Dynamic + Send>) -> Self {
+        Model {
+            structure,
+            dynamic: DynamicBox::Send(dynamic),
+        }
+    }
+
+    /// Takes the model apart, see `from_parts`.
+    pub(crate) fn into_parts(self) -> (Structure, DynamicBox) {
+        (self.structure, self.dynamic)
+    }
+
+    pub(crate) fn from_parts(structure: Structure, dynamic: DynamicBox) -> Self {
         Model { structure, dynamic }
     }
 
+    /// The messages a submodel receives are ordered by the couplings that
+    /// delivered them: external input couplings, then internal couplings, each
+    /// in declaration order; for internal couplings the source models come in
+    /// name order and keep the order of their outputs. With `sort_x_bags` the
+    /// bags are then stably sorted by destination port name.
+    pub fn with_sorted_x_bags(mut self, sort_x_bags: bool) -> Self {
+        self.structure.sort_x_bags = sort_x_bags;
+        self
+    }
+
+    /// With `restore_from_init` the init value of the model is a serialized
+    /// state: when it is not null, the simulator passes it to
+    /// `Dynamic::restore_state` instead of calling `Dynamic::init`.
+    pub fn with_restore_from_init(mut self, restore_from_init: bool) -> Self {
+        self.structure.restore_from_init = restore_from_init;
+        self
+    }
+
+    /// Among the imminent submodels of a coupled model, the ones with a higher
+    /// priority compute their outputs and make their transitions first; equal
+    /// priorities go in name order. The default priority is 0.
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.structure.priority = priority;
+        self
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.structure.priority
+    }
+
+    /// Makes this coupled model follow classic DEVS: when several submodels
+    /// are imminent, `select` receives their names, in priority order, and
+    /// returns the one that computes its outputs and makes its internal
+    /// transition. The others stay imminent and are handled by the next steps
+    /// at the same time; receiving a message meanwhile gives them an external
+    /// transition rather than a confluent one.
+    pub fn with_select(mut self, select: fn(&[String]) -> String) -> Self {
+        self.structure.select = Some(select);
+        self
+    }
+
+    /// Sets the global resources of the model. They are shared with the
+    /// submodels that have none of their own and take precedence over the ones
+    /// given to `Simulator::new`.
+    pub fn with_resources(mut self, resources: BTreeMap<String, Value>) -> Self {
+        self.structure.resources.global = Arc::new(resources);
+        self
+    }
+
+    /// Declares the input port `name`, if needed, and the type of its values.
+    /// Panics when `name` is empty, see `try_with_input_port_typed`.
+    pub fn with_input_port_typed(self, name: &str, port_type: PortType) -> Self {
+        self.try_with_input_port_typed(name, port_type)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_with_input_port_typed(
+        mut self,
+        name: &str,
+        port_type: PortType,
+    ) -> Result<Self, ExdsdevsError> {
+        self.declare_input_port(name)?;
+        self.structure
+            .input_port_types
+            .insert(name.to_owned(), port_type);
+        Ok(self)
+    }
+
+    /// Declares the input port `name`, if needed, and the maximum number of
+    /// messages it may receive at once. A bag exceeding it makes the run fail
+    /// with `ErrorSimTime`, which turns a runaway zero-time loop into an error.
+    /// Panics when `name` is empty, see `try_with_input_port_capacity`.
+    pub fn with_input_port_capacity(self, name: &str, capacity: usize) -> Self {
+        self.try_with_input_port_capacity(name, capacity)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_with_input_port_capacity(
+        mut self,
+        name: &str,
+        capacity: usize,
+    ) -> Result<Self, ExdsdevsError> {
+        self.declare_input_port(name)?;
+        self.structure
+            .input_port_capacities
+            .insert(name.to_owned(), capacity);
+        Ok(self)
+    }
+
+    /// Adds an internal coupling from `source_port` of the submodel `source`
+    /// to each `(submodel, port)` of `destinations`.
+    pub fn with_broadcast_coupling(
+        mut self,
+        source: &str,
+        source_port: &str,
+        destinations: &[(&str, &str)],
+    ) -> Self {
+        for &(destination, destination_port) in destinations {
+            self.structure
+                .internal_couplings
+                .push(InternalCoupling::new(
+                    source,
+                    source_port,
+                    destination,
+                    destination_port,
+                ));
+        }
+        self
+    }
+
+    /// Adds an internal coupling from `source_port` of the submodel `source`
+    /// to the input port `destination_port` of every other submodel declaring
+    /// it. The couplings are expanded now, submodels added later are not
+    /// connected.
+    pub fn with_broadcast_to_port(
+        self,
+        source: &str,
+        source_port: &str,
+        destination_port: &str,
+    ) -> Self {
+        let destinations: Vec<String> = self
+            .structure
+            .sub_simulators
+            .iter()
+            .filter(|(name, simulator)| {
+                name.as_str() != source
+                    && simulator
+                        .model
+                        .structure
+                        .input_ports
+                        .iter()
+                        .any(|port| port == destination_port)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        let destinations: Vec<(&str, &str)> = destinations
+            .iter()
+            .map(|name| (name.as_str(), destination_port))
+            .collect();
+        self.with_broadcast_coupling(source, source_port, &destinations)
+    }
+
+    /// Declares the input port `port`, if needed, and forwards its messages to
+    /// the input port `submodel_port` of `submodel`, e.g.
+    /// `("request", "server", "in")` exposes the `in` port of `server` as
+    /// `request`. Panics when `port` is empty, see
+    /// `try_with_external_input_coupling`.
+    pub fn with_external_input_coupling(self, coupling: (&str, &str, &str)) -> Self {
+        self.try_with_external_input_coupling(coupling)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_with_external_input_coupling(
+        mut self,
+        (port, submodel, submodel_port): (&str, &str, &str),
+    ) -> Result<Self, ExdsdevsError> {
+        self.declare_input_port(port)?;
+        self.structure
+            .external_input_couplings
+            .push(ExternalInputCoupling::new(port, submodel, submodel_port));
+        Ok(self)
+    }
+
+    /// Declares the output port `port`, if needed, and forwards to it the
+    /// outputs on the port `submodel_port` of `submodel`. Panics when `port` is
+    /// empty, see `try_with_external_output_coupling`.
+    pub fn with_external_output_coupling(self, coupling: (&str, &str, &str)) -> Self {
+        self.try_with_external_output_coupling(coupling)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_with_external_output_coupling(
+        mut self,
+        (submodel, submodel_port, port): (&str, &str, &str),
+    ) -> Result<Self, ExdsdevsError> {
+        declare_port("output", &mut self.structure.output_ports, port)?;
+        self.structure
+            .external_output_couplings
+            .push(ExternalOutputCoupling::new(submodel, submodel_port, port));
+        Ok(self)
+    }
+
+    fn declare_input_port(&mut self, name: &str) -> Result<(), ExdsdevsError> {
+        declare_port("input", &mut self.structure.input_ports, name)
+    }
+
+    pub(crate) fn submodel_input_port_type(&self, submodel: &str, port: &str) -> Option<PortType> {
+        self.submodel(submodel)
+            .and_then(|model| model.structure.input_port_types.get(port))
+            .copied()
+    }
+
+    pub fn submodel_names(&self) -> Vec<&str> {
+        self.structure
+            .sub_simulators
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn submodel(&self, submodel_name: &str) -> Option<&Model> {
+        self.structure
+            .sub_simulators
+            .get(submodel_name)
+            .map(|simulator| &simulator.model)
+    }
+
+    pub fn couplings(&self) -> CouplingReport<'_> {
+        CouplingReport::new(&self.structure)
+    }
+
+    /// Destinations of the output port `source_port` of the submodel
+    /// `source_model`, in declaration order: `(Some(submodel), port)` for an
+    /// internal coupling, `(None, port)` for an output port of this model.
+    pub fn fan_out(&self, source_model: &str, source_port: &str) -> Vec<(Option<&str>, &str)> {
+        self.couplings()
+            .iter()
+            .filter(|coupling| {
+                coupling.source_model == Some(source_model) && coupling.source_port == source_port
+            })
+            .map(|coupling| (coupling.destination_model, coupling.destination_port))
+            .collect()
+    }
+
+    pub fn dynamic_type(&self) -> String {
+        self.dynamic.dynamic_type()
+    }
+
+    /// Describes the structure of the model and its submodels, see
+    /// `ModelDefinition`.
+    pub fn to_definition(&self) -> ModelDefinition {
+        let structure = &self.structure;
+        ModelDefinition {
+            dynamic_type: self.dynamic_type(),
+            input_ports: structure.input_ports.clone(),
+            input_port_types: structure.input_port_types.clone(),
+            input_port_capacities: structure.input_port_capacities.clone(),
+            sort_x_bags: structure.sort_x_bags,
+            restore_from_init: structure.restore_from_init,
+            priority: structure.priority,
+            output_ports: structure.output_ports.clone(),
+            submodels: structure
+                .sub_simulators
+                .iter()
+                .map(|(name, simulator)| (name.clone(), simulator.model.to_definition()))
+                .collect(),
+            external_input_couplings: structure
+                .external_input_couplings
+                .iter()
+                .map(From::from)
+                .collect(),
+            internal_couplings: structure
+                .internal_couplings
+                .iter()
+                .map(From::from)
+                .collect(),
+            external_output_couplings: structure
+                .external_output_couplings
+                .iter()
+                .map(From::from)
+                .collect(),
+            observers: Vec::new(),
+        }
+    }
+
     pub(crate) fn has_submodels(&self) -> bool {
         !self.structure.sub_simulators.is_empty()
     }
 
-    pub(crate) fn sub_simulators(&mut self) -> IterMut<String, Simulator> {
+    pub(crate) fn sub_simulators(&mut self) -> IterMut<'_, String, Simulator> {
         self.structure.sub_simulators.iter_mut()
     }
 
+    pub(crate) fn sub_simulators_iter(&self) -> Iter<'_, String, Simulator> {
+        self.structure.sub_simulators.iter()
+    }
+
     pub(crate) fn external_input_couplings(&self) -> &[ExternalInputCoupling] {
         &self.structure.external_input_couplings
     }
@@ -126,6 +583,148 @@ impl Model {
             .unwrap()
     }
 
+    /// Validates the couplings of the model and of all its submodels.
+    ///
+    /// Every coupling must reference an existing submodel and ports declared in
+    /// `input_ports`/`output_ports` of the corresponding models, and no two
+    /// submodels may have the same name. The error reports the full name of
+    /// the model holding the first offending coupling.
+    pub fn check(&self) -> Result<(), ExdsdevsError> {
+        self.check_named("")
+    }
+
+    pub(crate) fn check_named(&self, model_full_name: &str) -> Result<(), ExdsdevsError> {
+        let location = location(model_full_name);
+        validate_port_names("input", &self.structure.input_ports)
+            .and_then(|_| validate_port_names("output", &self.structure.output_ports))
+            .map_err(|message| {
+                ExdsdevsError::ErrorModelValidation(format!("{} of {}", message, location))
+            })?;
+        let mut submodel_names = HashSet::new();
+        for simulator in self.structure.sub_simulators.values() {
+            let name = simulator.full_name.rsplit('/').next().unwrap_or_default();
+            if !submodel_names.insert(name) {
+                return Err(ExdsdevsError::ErrorModelValidation(format!(
+                    "duplicate submodel name '{}' of {}",
+                    name, location
+                )));
+            }
+        }
+        for coupling in self.external_input_couplings() {
+            self.check_external_input_coupling(coupling, &location)?;
+        }
+        for coupling in self.internal_couplings() {
+            self.check_internal_coupling(coupling, &location)?;
+        }
+        for coupling in self.external_output_couplings() {
+            self.check_external_output_coupling(coupling, &location)?;
+        }
+
+        self.dynamic
+            .validate()
+            .map_err(|msg| ExdsdevsError::ErrorModelValidation(format!("{}: {}", location, msg)))?;
+
+        for simulator in self.structure.sub_simulators.values() {
+            simulator.model.check_named(&simulator.full_name)?;
+        }
+        Ok(())
+    }
+
+    /// Structure of the submodel `name` referenced by `coupling`.
+    fn coupled_submodel(
+        &self,
+        name: &str,
+        coupling: &str,
+        location: &str,
+    ) -> Result<&Structure, ExdsdevsError> {
+        self.structure
+            .sub_simulators
+            .get(name)
+            .map(|simulator| &simulator.model.structure)
+            .ok_or_else(|| {
+                ExdsdevsError::ErrorModelValidation(format!(
+                    "{} of {} references unknown submodel '{}'",
+                    coupling, location, name
+                ))
+            })
+    }
+
+    fn check_external_input_coupling(
+        &self,
+        coupling: &ExternalInputCoupling,
+        location: &str,
+    ) -> Result<(), ExdsdevsError> {
+        let name = format!(
+            "external input coupling '{}' -> '{}:{}'",
+            coupling.source_port, coupling.destination_model, coupling.destination_model_port
+        );
+        check_declared(
+            &self.structure.input_ports,
+            &coupling.source_port,
+            &name,
+            location,
+        )?;
+        let destination = self.coupled_submodel(&coupling.destination_model, &name, location)?;
+        check_declared(
+            &destination.input_ports,
+            &coupling.destination_model_port,
+            &name,
+            location,
+        )
+    }
+
+    fn check_internal_coupling(
+        &self,
+        coupling: &InternalCoupling,
+        location: &str,
+    ) -> Result<(), ExdsdevsError> {
+        let name = format!(
+            "internal coupling '{}:{}' -> '{}:{}'",
+            coupling.source_model,
+            coupling.source_model_port,
+            coupling.destination_model,
+            coupling.destination_model_port
+        );
+        let source = self.coupled_submodel(&coupling.source_model, &name, location)?;
+        check_declared(
+            &source.output_ports,
+            &coupling.source_model_port,
+            &name,
+            location,
+        )?;
+        let destination = self.coupled_submodel(&coupling.destination_model, &name, location)?;
+        check_declared(
+            &destination.input_ports,
+            &coupling.destination_model_port,
+            &name,
+            location,
+        )
+    }
+
+    fn check_external_output_coupling(
+        &self,
+        coupling: &ExternalOutputCoupling,
+        location: &str,
+    ) -> Result<(), ExdsdevsError> {
+        let name = format!(
+            "external output coupling '{}:{}' -> '{}'",
+            coupling.source_model, coupling.source_model_port, coupling.destination_port
+        );
+        let source = self.coupled_submodel(&coupling.source_model, &name, location)?;
+        check_declared(
+            &source.output_ports,
+            &coupling.source_model_port,
+            &name,
+            location,
+        )?;
+        check_declared(
+            &self.structure.output_ports,
+            &coupling.destination_port,
+            &name,
+            location,
+        )
+    }
+
     pub(crate) fn get_y_bag_from_mail(&self, mail: &Mail) -> Bag {
         let mut bag: Bag = Bag::new();
         for ExternalOutputCoupling {
@@ -154,14 +753,23 @@ impl Model {
         &mut self,
         init_time: Time,
         init_value: &Value,
-        resources: &Resources,
-        rng: &mut StdRng,
-    ) {
+        rng: &mut SimRng,
+    ) -> Result<(), ExdsdevsError> {
+        if self.structure.restore_from_init && !init_value.is_null() {
+            return self.dynamic.restore_state(init_value);
+        }
+        let resources = std::mem::take(&mut self.structure.resources);
         self.dynamic
-            .init(&mut self.structure, init_time, init_value, resources, rng)
+            .init(&mut self.structure, init_time, init_value, &resources, rng);
+        self.structure.resources = resources;
+        Ok(())
+    }
+
+    pub(crate) fn reset(&mut self, initial_state: &Value) -> Result<(), String> {
+        self.dynamic.reset(initial_state)
     }
 
-    pub(crate) fn time_advance(&self, rng: &mut StdRng) -> Time {
+    pub(crate) fn time_advance(&self, rng: &mut SimRng) -> Time {
         self.dynamic.time_advance(&self.structure, rng)
     }
 
@@ -169,11 +777,21 @@ impl Model {
         self.dynamic.state()
     }
 
-    pub(crate) fn output(&self, sim_time: Time) -> Bag {
-        self.dynamic.output(&self.structure, sim_time)
+    pub(crate) fn restore_state(&mut self, state: &Value) -> Result<(), ExdsdevsError> {
+        self.dynamic.restore_state(state)
+    }
+
+    pub(crate) fn output(&self, sim_time: Time, elapsed: Time) -> Bag {
+        self.dynamic
+            .output_with_elapsed(&self.structure, sim_time, elapsed)
+    }
+
+    pub(crate) fn output_with_input(&self, sim_time: Time, x_bag: &Bag) -> Bag {
+        self.dynamic
+            .output_with_input(&self.structure, sim_time, x_bag)
     }
 
-    pub(crate) fn internal_transition(&mut self, sim_time: Time, rng: &mut StdRng) {
+    pub(crate) fn internal_transition(&mut self, sim_time: Time, rng: &mut SimRng) {
         self.dynamic
             .internal_transition(&mut self.structure, sim_time, rng);
     }
@@ -183,7 +801,7 @@ impl Model {
         sim_time: Time,
         elapsed: Time,
         x_bag: &Bag,
-        rng: &mut StdRng,
+        rng: &mut SimRng,
     ) {
         self.dynamic
             .external_transition(&self.structure, sim_time, elapsed, x_bag, rng)
@@ -194,32 +812,296 @@ impl Model {
         sim_time: Time,
         elapsed: Time,
         mail: &Mail,
-        rng: &mut StdRng,
+        rng: &mut SimRng,
     ) {
         self.dynamic
             .external_mail_transition(&mut self.structure, sim_time, elapsed, mail, rng)
     }
 
-    pub(crate) fn confluent_transition(&mut self, sim_time: Time, x_bag: &Bag, rng: &mut StdRng) {
+    pub(crate) fn confluent_transition(
+        &mut self,
+        sim_time: Time,
+        elapsed: Time,
+        x_bag: &Bag,
+        rng: &mut SimRng,
+    ) {
         self.dynamic
-            .confluent_transition(&mut self.structure, sim_time, x_bag, rng);
+            .confluent_transition(&mut self.structure, sim_time, elapsed, x_bag, rng);
     }
 
     pub(crate) fn finish(&mut self, sim_time: Time) {
         self.dynamic.finish(sim_time);
     }
+
+    pub(crate) fn structure_changes(&mut self) -> Vec<StructureChange> {
+        self.dynamic.structure_changes()
+    }
+
+    /// Removes the submodel `name` together with the couplings using it, e.g.
+    /// to simulate the same topology minus one component. The other submodels
+    /// are left as they are, so an input port fed only by the removed one
+    /// receives nothing.
+    pub fn without_submodel(&mut self, name: &str) -> Option<Model> {
+        self.remove_submodel(name).map(|simulator| simulator.model)
+    }
+
+    /// Removes the external input couplings `(source_port, destination_model,
+    /// destination_model_port)`, the tuples of `Structure::new`. Returns the
+    /// number of couplings removed, 0 when there was none.
+    pub fn without_external_input_coupling(&mut self, coupling: (&str, &str, &str)) -> usize {
+        let coupling = ExternalInputCoupling::new(coupling.0, coupling.1, coupling.2);
+        remove_all(&mut self.structure.external_input_couplings, &coupling)
+    }
+
+    /// Removes the internal couplings `(source_model, source_model_port,
+    /// destination_model, destination_model_port)`, see
+    /// `without_external_input_coupling`.
+    pub fn without_internal_coupling(&mut self, coupling: (&str, &str, &str, &str)) -> usize {
+        let coupling = InternalCoupling::new(coupling.0, coupling.1, coupling.2, coupling.3);
+        remove_all(&mut self.structure.internal_couplings, &coupling)
+    }
+
+    /// Removes the external output couplings `(source_model,
+    /// source_model_port, destination_port)`, see
+    /// `without_external_input_coupling`.
+    pub fn without_external_output_coupling(&mut self, coupling: (&str, &str, &str)) -> usize {
+        let coupling = ExternalOutputCoupling::new(coupling.0, coupling.1, coupling.2);
+        remove_all(&mut self.structure.external_output_couplings, &coupling)
+    }
+
+    fn remove_submodel(&mut self, name: &str) -> Option<Simulator> {
+        let structure = &mut self.structure;
+        structure
+            .external_input_couplings
+            .retain(|coupling| coupling.destination_model != name);
+        structure
+            .internal_couplings
+            .retain(|coupling| coupling.source_model != name && coupling.destination_model != name);
+        structure
+            .external_output_couplings
+            .retain(|coupling| coupling.source_model != name);
+        structure.sub_simulators.remove(name)
+    }
+
+    /// Fails when `change` adds a submodel under a name in use, removes an
+    /// unknown submodel or adds a coupling to unknown submodels or ports.
+    /// Removing a missing coupling does nothing.
+    pub(crate) fn check_structure_change(
+        &self,
+        model_full_name: &str,
+        change: &StructureChange,
+    ) -> Result<(), ExdsdevsError> {
+        let location = location(model_full_name);
+        match change {
+            StructureChange::AddSubmodel { name, .. } if self.submodel(name).is_some() => {
+                Err(ExdsdevsError::ErrorModelValidation(format!(
+                    "structure change of {} adds submodel '{}', which already exists",
+                    location, name
+                )))
+            }
+            StructureChange::RemoveSubmodel(name) if self.submodel(name).is_none() => {
+                Err(ExdsdevsError::ErrorModelValidation(format!(
+                    "structure change of {} removes unknown submodel '{}'",
+                    location, name
+                )))
+            }
+            StructureChange::AddExternalInputCoupling(coupling) => {
+                self.check_external_input_coupling(coupling, &location)
+            }
+            StructureChange::AddInternalCoupling(coupling) => {
+                self.check_internal_coupling(coupling, &location)
+            }
+            StructureChange::AddExternalOutputCoupling(coupling) => {
+                self.check_external_output_coupling(coupling, &location)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Applies a change checked by `check_structure_change` that does not
+    /// involve initializing a submodel; a removed submodel is returned to be
+    /// finished by the caller.
+    pub(crate) fn apply_structure_change(&mut self, change: StructureChange) -> Option<Simulator> {
+        let structure = &mut self.structure;
+        match change {
+            StructureChange::AddSubmodel { name, simulator } => {
+                structure.sub_simulators.insert(name, *simulator);
+            }
+            StructureChange::RemoveSubmodel(name) => return self.remove_submodel(&name),
+            StructureChange::AddExternalInputCoupling(coupling) => {
+                structure.external_input_couplings.push(coupling)
+            }
+            StructureChange::RemoveExternalInputCoupling(coupling) => structure
+                .external_input_couplings
+                .retain(|existing| *existing != coupling),
+            StructureChange::AddInternalCoupling(coupling) => {
+                structure.internal_couplings.push(coupling)
+            }
+            StructureChange::RemoveInternalCoupling(coupling) => structure
+                .internal_couplings
+                .retain(|existing| *existing != coupling),
+            StructureChange::AddExternalOutputCoupling(coupling) => {
+                structure.external_output_couplings.push(coupling)
+            }
+            StructureChange::RemoveExternalOutputCoupling(coupling) => structure
+                .external_output_couplings
+                .retain(|existing| *existing != coupling),
+        }
+        None
+    }
+}
+
+fn declare_port(kind: &str, ports: &mut Vec<String>, name: &str) -> Result<(), ExdsdevsError> {
+    if name.is_empty() {
+        return Err(ExdsdevsError::ErrorModelValidation(format!(
+            "empty {} port name",
+            kind
+        )));
+    }
+    if !ports.iter().any(|port| port == name) {
+        ports.push(name.to_owned());
+    }
+    Ok(())
+}
+
+/// How validation errors name the model `model_full_name`.
+fn location(model_full_name: &str) -> String {
+    if model_full_name.is_empty() {
+        "model".to_owned()
+    } else {
+        format!("model '{}'", model_full_name)
+    }
+}
+
+/// Deserializes the submodels of a model class or definition, rejecting a
+/// name given twice instead of keeping the last one.
+fn deserialize_submodels<'de, D, T>(deserializer: D) -> Result<BTreeMap<String, T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    struct SubmodelsVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for SubmodelsVisitor<T> {
+        type Value = BTreeMap<String, T>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a map of submodels")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut submodels = BTreeMap::new();
+            while let Some((name, submodel)) = map.next_entry::<String, T>()? {
+                if submodels.contains_key(&name) {
+                    return Err(serde::de::Error::custom(format!(
+                        "duplicate submodel name '{}'",
+                        name
+                    )));
+                }
+                submodels.insert(name, submodel);
+            }
+            Ok(submodels)
+        }
+    }
+
+    deserializer.deserialize_map(SubmodelsVisitor(PhantomData))
+}
+
+/// Fails when `coupling` of the model at `location` uses a `port` missing
+/// from `ports`.
+fn check_declared(
+    ports: &[String],
+    port: &str,
+    coupling: &str,
+    location: &str,
+) -> Result<(), ExdsdevsError> {
+    if ports.iter().any(|declared| declared == port) {
+        Ok(())
+    } else {
+        Err(ExdsdevsError::ErrorModelValidation(format!(
+            "{} of {} references undeclared port '{}'",
+            coupling, location, port
+        )))
+    }
+}
+
+/// Removes every element equal to `removed`, returns how many there were.
+fn remove_all<T: PartialEq>(elements: &mut Vec<T>, removed: &T) -> usize {
+    let len = elements.len();
+    elements.retain(|element| element != removed);
+    len - elements.len()
+}
+
+/// Expected JSON kind of the values received on an input port.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PortType {
+    Any,
+    Null,
+    Bool,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl PortType {
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Null => value.is_null(),
+            Self::Bool => value.is_boolean(),
+            Self::Number => value.is_number(),
+            Self::String => value.is_string(),
+            Self::Array => value.is_array(),
+            Self::Object => value.is_object(),
+        }
+    }
 }
 
 pub struct Structure {
     pub input_ports: Vec<String>,
+    /// Types of the typed input ports, the other ports accept any value.
+    pub input_port_types: BTreeMap<String, PortType>,
+    /// Maximum number of messages a port may receive in one bag, see
+    /// `Model::with_input_port_capacity`.
+    pub input_port_capacities: BTreeMap<String, usize>,
     pub output_ports: Vec<String>,
     pub sub_simulators: BTreeMap<String, Simulator>,
     pub external_input_couplings: Vec<ExternalInputCoupling>,
     pub internal_couplings: Vec<InternalCoupling>,
     pub external_output_couplings: Vec<ExternalOutputCoupling>,
+    /// Sorts the bags delivered to the submodels by port name, see
+    /// `Model::with_sorted_x_bags`.
+    pub sort_x_bags: bool,
+    /// Initializes the dynamic from its init value, see
+    /// `Model::with_restore_from_init`.
+    pub restore_from_init: bool,
+    /// The resources of the model, reachable from every transition through the
+    /// structure; also passed to `Dynamic::init`.
+    pub resources: Resources,
+    /// Priority among simultaneous events of the parent's submodels, see
+    /// `Model::with_priority`.
+    pub priority: i32,
+    /// Classic DEVS tie-break function, see `Model::with_select`.
+    pub select: Option<fn(&[String]) -> String>,
+}
+
+/// Port names must be non-empty and unique.
+fn validate_port_names(kind: &str, ports: &[String]) -> Result<(), String> {
+    for (index, port) in ports.iter().enumerate() {
+        if port.is_empty() {
+            return Err(format!("empty {} port name", kind));
+        }
+        if ports[..index].contains(port) {
+            return Err(format!("duplicate {} port '{}'", kind, port));
+        }
+    }
+    Ok(())
 }
 
 impl Structure {
+    /// Panics when a port name is empty or declared twice, see `try_new`.
     pub fn new(
         input_ports: &[&str],
         output_ports: &[&str],
@@ -228,9 +1110,40 @@ impl Structure {
         internal_couplings: &[(&str, &str, &str, &str)],
         external_output_couplings: &[(&str, &str, &str)],
     ) -> Self {
-        Self {
-            input_ports: input_ports.iter().map(|&port| port.to_string()).collect(),
-            output_ports: output_ports.iter().map(|&port| port.to_string()).collect(),
+        Self::try_new(
+            input_ports,
+            output_ports,
+            submodels,
+            external_input_couplings,
+            internal_couplings,
+            external_output_couplings,
+        )
+        .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_new(
+        input_ports: &[&str],
+        output_ports: &[&str],
+        submodels: BTreeMap<String, Simulator>,
+        external_input_couplings: &[(&str, &str, &str)],
+        internal_couplings: &[(&str, &str, &str, &str)],
+        external_output_couplings: &[(&str, &str, &str)],
+    ) -> Result<Self, ExdsdevsError> {
+        let input_ports: Vec<String> = input_ports.iter().map(|&port| port.to_string()).collect();
+        let output_ports: Vec<String> = output_ports.iter().map(|&port| port.to_string()).collect();
+        validate_port_names("input", &input_ports)
+            .and_then(|_| validate_port_names("output", &output_ports))
+            .map_err(ExdsdevsError::ErrorModelValidation)?;
+        Ok(Self {
+            input_ports,
+            input_port_types: BTreeMap::new(),
+            input_port_capacities: BTreeMap::new(),
+            sort_x_bags: false,
+            restore_from_init: false,
+            resources: Resources::default(),
+            priority: 0,
+            select: None,
+            output_ports,
             sub_simulators: submodels,
             external_input_couplings: external_input_couplings
                 .iter()
@@ -250,7 +1163,7 @@ impl Structure {
                     ExternalOutputCoupling::new(src_model, src_port, dst_port)
                 })
                 .collect(),
-        }
+        })
     }
 }
 
@@ -267,13 +1180,28 @@ impl ModelFactory {
         dynamic_factory_storage: DynamicFactoryStorage,
         observer_factory_storage: ObserverFactoryStorage,
     ) -> Self {
+        Self::try_new(
+            model_directory,
+            dynamic_factory_storage,
+            observer_factory_storage,
+        )
+        .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as `new`, but fails on a model class file that cannot be read or
+    /// parsed.
+    pub fn try_new(
+        model_directory: &Path,
+        dynamic_factory_storage: DynamicFactoryStorage,
+        observer_factory_storage: ObserverFactoryStorage,
+    ) -> Result<Self, ExdsdevsError> {
         let class_files = Self::prepare_paths(model_directory);
-        let class_storage = Self::parse_class_files(class_files.as_slice());
-        ModelFactory {
+        let class_storage = Self::parse_class_files(class_files.as_slice())?;
+        Ok(ModelFactory {
             class_storage,
             dynamic_factory_storage,
             observer_factory_storage,
-        }
+        })
     }
 
     pub fn class_storage(&self) -> &BTreeMap<String, ModelClass> {
@@ -314,34 +1242,35 @@ impl ModelFactory {
         class_paths
     }
 
-    fn parse_class_files(class_files: &[PathBuf]) -> BTreeMap<String, ModelClass> {
+    fn parse_class_files(
+        class_files: &[PathBuf],
+    ) -> Result<BTreeMap<String, ModelClass>, ExdsdevsError> {
         class_files
             .iter()
-            .map(|local_path| read_to_string(local_path).unwrap())
-            .map(|json_string| serde_json::from_str::<ModelClass>(&json_string).unwrap())
-            .map(|model_class| (model_class.model_class(), model_class))
-            .collect::<BTreeMap<String, ModelClass>>()
+            .map(|local_path| {
+                read_json_from_file::<ModelClass, _>(local_path)
+                    .map(|model_class| (model_class.model_class(), model_class))
+            })
+            .collect()
     }
 
+    /// Builds the simulator of `model_class_name` and of its submodels. Fails
+    /// when a model class, a dynamic or an observer is not registered.
     pub(crate) fn build_simulator(
         &self,
         model_class_name: &str,
         model_full_name: String,
         global_resources: &Arc<BTreeMap<String, Value>>,
-    ) -> Simulator {
-        let model_class = self
-            .class_storage
-            .get(model_class_name)
-            .unwrap_or_else(|| panic!("Model class '{}' was not registered", model_class_name));
+    ) -> Result<Simulator, ExdsdevsError> {
+        let model_class = self.class_storage.get(model_class_name).ok_or_else(|| {
+            ExdsdevsError::ErrorModelValidation(format!(
+                "model class '{}' was not found",
+                model_class_name
+            ))
+        })?;
         let dynamic = self
             .dynamic_factory_storage
-            .get_dynamic(model_class.dynamic_type())
-            .unwrap_or_else(|_| {
-                panic!(
-                    "Model dynamic '{}' was not registered",
-                    model_class.dynamic_type()
-                )
-            });
+            .get_dynamic(model_class.dynamic_type())?;
 
         let sub_simulators = model_class
             .submodels_iter()
@@ -351,10 +1280,10 @@ impl ModelFactory {
                     submodel.model_class(),
                     submodel_full_name,
                     global_resources,
-                );
-                (submodel_name.clone(), model)
+                )?;
+                Ok((submodel_name.clone(), model))
             })
-            .collect::<BTreeMap<String, Simulator>>();
+            .collect::<Result<BTreeMap<String, Simulator>, ExdsdevsError>>()?;
 
         let external_input_couplings: Vec<ExternalInputCoupling> = model_class
             .external_input_couplings()
@@ -374,6 +1303,13 @@ impl ModelFactory {
 
         let structure = Structure {
             input_ports: model_class.input_ports().to_vec(),
+            input_port_types: model_class.input_port_types.clone(),
+            input_port_capacities: model_class.input_port_capacities.clone(),
+            sort_x_bags: model_class.sort_x_bags,
+            restore_from_init: model_class.restore_from_init,
+            resources: Resources::default(),
+            priority: 0,
+            select: None,
             output_ports: model_class.output_ports().to_vec(),
             sub_simulators,
             external_input_couplings,
@@ -389,24 +1325,10 @@ impl ModelFactory {
         };
 
         let mut simulator = Simulator::new(&model_full_name, model, resources);
-        for ObserverClass {
-            observer_class,
-            observer_config,
-        } in model_class.observers()
-        {
-            let mut observer = self
-                .observer_factory_storage
-                .get_observer(observer_class)
-                .unwrap_or_else(|_| {
-                    panic!(
-                        "Observer with name '{}' has not been registered",
-                        observer_class
-                    )
-                });
-            observer.config(observer_config);
-            simulator.add_observer(observer);
+        for observer_class in model_class.observers() {
+            simulator.add_observer(observer_class.create(&self.observer_factory_storage)?);
         }
-        simulator
+        Ok(simulator)
     }
 }
 
@@ -414,6 +1336,10 @@ impl ModelFactory {
 pub(crate) struct Submodel {
     model_class: String,
     init_variants: Option<BTreeMap<String, Value>>,
+    /// Parameter ranges swept over every init variant, see
+    /// `InitVariantsFactory`.
+    #[serde(default)]
+    init_sweep: BTreeMap<String, Value>,
 }
 
 impl Submodel {
@@ -427,15 +1353,29 @@ impl Submodel {
             None => BTreeMap::new(),
         }
     }
+
+    pub(crate) fn init_sweep(&self) -> &BTreeMap<String, Value> {
+        &self.init_sweep
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 struct ModelClassExtInCoupl {
     src_port: String,
     dst_model: String,
     dst_port: String,
 }
 
+impl From<&ExternalInputCoupling> for ModelClassExtInCoupl {
+    fn from(coupling: &ExternalInputCoupling) -> Self {
+        ModelClassExtInCoupl {
+            src_port: coupling.source_port.clone(),
+            dst_model: coupling.destination_model.clone(),
+            dst_port: coupling.destination_model_port.clone(),
+        }
+    }
+}
+
 impl From<&ModelClassExtInCoupl> for ExternalInputCoupling {
     fn from(ext_in_coupl: &ModelClassExtInCoupl) -> Self {
         let ModelClassExtInCoupl {
@@ -451,7 +1391,7 @@ impl From<&ModelClassExtInCoupl> for ExternalInputCoupling {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 struct ModelClassIntCoupl {
     src_model: String,
     src_port: String,
@@ -459,6 +1399,17 @@ struct ModelClassIntCoupl {
     dst_port: String,
 }
 
+impl From<&InternalCoupling> for ModelClassIntCoupl {
+    fn from(coupling: &InternalCoupling) -> Self {
+        ModelClassIntCoupl {
+            src_model: coupling.source_model.clone(),
+            src_port: coupling.source_model_port.clone(),
+            dst_model: coupling.destination_model.clone(),
+            dst_port: coupling.destination_model_port.clone(),
+        }
+    }
+}
+
 impl From<&ModelClassIntCoupl> for InternalCoupling {
     fn from(int_coupl: &ModelClassIntCoupl) -> Self {
         let ModelClassIntCoupl {
@@ -476,13 +1427,23 @@ impl From<&ModelClassIntCoupl> for InternalCoupling {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 struct ModelClassExtOutCoupl {
     src_model: String,
     src_port: String,
     dst_port: String,
 }
 
+impl From<&ExternalOutputCoupling> for ModelClassExtOutCoupl {
+    fn from(coupling: &ExternalOutputCoupling) -> Self {
+        ModelClassExtOutCoupl {
+            src_model: coupling.source_model.clone(),
+            src_port: coupling.source_model_port.clone(),
+            dst_port: coupling.destination_port.clone(),
+        }
+    }
+}
+
 impl From<&ModelClassExtOutCoupl> for ExternalOutputCoupling {
     fn from(ext_out_coupl: &ModelClassExtOutCoupl) -> Self {
         let ModelClassExtOutCoupl {
@@ -498,28 +1459,183 @@ impl From<&ModelClassExtOutCoupl> for ExternalOutputCoupling {
     }
 }
 
+/// Self-contained description of a built model: the structure of the model
+/// and its submodels, with every dynamic named by its `dynamic_type`. Written
+/// by `Model::to_definition`, turned back into a model by `build`. Resources,
+/// select functions and dynamic states are not part of it.
+///
+/// A definition may also list the observers of each model by class, as model
+/// classes do; `build_simulator` creates them. `to_definition` does not write
+/// them since an observer does not know its class.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ModelDefinition {
+    dynamic_type: String,
+    input_ports: Vec<String>,
+    #[serde(default)]
+    input_port_types: BTreeMap<String, PortType>,
+    #[serde(default)]
+    input_port_capacities: BTreeMap<String, usize>,
+    #[serde(default)]
+    sort_x_bags: bool,
+    #[serde(default)]
+    restore_from_init: bool,
+    #[serde(default)]
+    priority: i32,
+    output_ports: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_submodels")]
+    submodels: BTreeMap<String, ModelDefinition>,
+    #[serde(default)]
+    external_input_couplings: Vec<ModelClassExtInCoupl>,
+    #[serde(default)]
+    internal_couplings: Vec<ModelClassIntCoupl>,
+    #[serde(default)]
+    external_output_couplings: Vec<ModelClassExtOutCoupl>,
+    #[serde(default)]
+    observers: Vec<ObserverClass>,
+}
+
+impl ModelDefinition {
+    /// Builds the model named `full_name`, the dynamics are created from
+    /// `dynamic_factory_storage` by their type. The model is checked like
+    /// `Model::check` does. The observers are left out, see `build_simulator`.
+    pub fn build(
+        &self,
+        full_name: &str,
+        dynamic_factory_storage: &DynamicFactoryStorage,
+    ) -> Result<Model, ExdsdevsError> {
+        let model = self.build_unchecked(full_name, dynamic_factory_storage, None)?;
+        model.check_named(full_name)?;
+        Ok(model)
+    }
+
+    /// Same as `build`, with the observers of every model created from
+    /// `observer_factory_storage` by their class and configured.
+    pub fn build_simulator(
+        &self,
+        full_name: &str,
+        dynamic_factory_storage: &DynamicFactoryStorage,
+        observer_factory_storage: &ObserverFactoryStorage,
+    ) -> Result<Simulator, ExdsdevsError> {
+        let model = self.build_unchecked(
+            full_name,
+            dynamic_factory_storage,
+            Some(observer_factory_storage),
+        )?;
+        model.check_named(full_name)?;
+        self.observed_simulator(full_name, model, Some(observer_factory_storage))
+    }
+
+    fn observed_simulator(
+        &self,
+        full_name: &str,
+        model: Model,
+        observer_factory_storage: Option<&ObserverFactoryStorage>,
+    ) -> Result<Simulator, ExdsdevsError> {
+        let mut simulator = Simulator::new(full_name, model, Resources::default());
+        if let Some(observer_factory_storage) = observer_factory_storage {
+            for observer_class in &self.observers {
+                simulator.add_observer(observer_class.create(observer_factory_storage)?);
+            }
+        }
+        Ok(simulator)
+    }
+
+    fn build_unchecked(
+        &self,
+        full_name: &str,
+        dynamic_factory_storage: &DynamicFactoryStorage,
+        observer_factory_storage: Option<&ObserverFactoryStorage>,
+    ) -> Result<Model, ExdsdevsError> {
+        let dynamic = dynamic_factory_storage.get_dynamic(&self.dynamic_type)?;
+        let mut sub_simulators = BTreeMap::new();
+        for (name, definition) in &self.submodels {
+            let sub_full_name = format!("{}/{}", full_name, name);
+            let submodel = definition.build_unchecked(
+                &sub_full_name,
+                dynamic_factory_storage,
+                observer_factory_storage,
+            )?;
+            sub_simulators.insert(
+                name.clone(),
+                definition.observed_simulator(
+                    &sub_full_name,
+                    submodel,
+                    observer_factory_storage,
+                )?,
+            );
+        }
+        let structure = Structure {
+            input_ports: self.input_ports.clone(),
+            input_port_types: self.input_port_types.clone(),
+            input_port_capacities: self.input_port_capacities.clone(),
+            sort_x_bags: self.sort_x_bags,
+            restore_from_init: self.restore_from_init,
+            resources: Resources::default(),
+            priority: self.priority,
+            select: None,
+            output_ports: self.output_ports.clone(),
+            sub_simulators,
+            external_input_couplings: self
+                .external_input_couplings
+                .iter()
+                .map(From::from)
+                .collect(),
+            internal_couplings: self.internal_couplings.iter().map(From::from).collect(),
+            external_output_couplings: self
+                .external_output_couplings
+                .iter()
+                .map(From::from)
+                .collect(),
+        };
+        Ok(Model::new(structure, dynamic))
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ModelClass {
     model_class: String,
     input_ports: Vec<String>,
+    #[serde(default)]
+    input_port_types: BTreeMap<String, PortType>,
+    #[serde(default)]
+    input_port_capacities: BTreeMap<String, usize>,
+    #[serde(default)]
+    sort_x_bags: bool,
+    #[serde(default)]
+    restore_from_init: bool,
     output_ports: Vec<String>,
     dynamic_type: String,
+    #[serde(deserialize_with = "deserialize_submodels")]
     submodels: BTreeMap<String, Submodel>,
     external_input_couplings: Vec<ModelClassExtInCoupl>,
     internal_couplings: Vec<ModelClassIntCoupl>,
     external_output_couplings: Vec<ModelClassExtOutCoupl>,
     default_init: Value,
     root_init_variants: Option<BTreeMap<String, Value>>,
+    #[serde(default)]
+    root_init_sweep: BTreeMap<String, Value>,
     local_resources: Value,
     observers: Vec<ObserverClass>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ObserverClass {
     observer_class: String,
     observer_config: Value,
 }
 
+impl ObserverClass {
+    /// Creates the observer from `observer_factory_storage` and configures it.
+    pub(crate) fn create(
+        &self,
+        observer_factory_storage: &ObserverFactoryStorage,
+    ) -> Result<Box<dyn Observer>, ExdsdevsError> {
+        let mut observer = observer_factory_storage.get_observer(&self.observer_class)?;
+        observer.config(&self.observer_config);
+        Ok(observer)
+    }
+}
+
 impl ModelClass {
     fn model_class(&self) -> String {
         self.model_class.clone()
@@ -533,7 +1649,7 @@ impl ModelClass {
         self.dynamic_type.as_str()
     }
 
-    pub(crate) fn submodels_iter(&self) -> Iter<std::string::String, Submodel> {
+    pub(crate) fn submodels_iter(&self) -> Iter<'_, std::string::String, Submodel> {
         self.submodels.iter()
     }
 
@@ -565,6 +1681,10 @@ impl ModelClass {
         self.root_init_variants.as_ref().cloned()
     }
 
+    pub(crate) fn root_init_sweep(&self) -> &BTreeMap<String, Value> {
+        &self.root_init_sweep
+    }
+
     fn observers(&self) -> &[ObserverClass] {
         self.observers.as_slice()
     }
@@ -573,6 +1693,526 @@ impl ModelClass {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dynamic::DynamicFactory;
+    use crate::metrics::Metrics;
+    use crate::observer::ObserverFactory;
+    use crate::test_utils::{experiment_factories, temp_dir, write_experiment};
+
+    struct TestDynamic;
+
+    impl Dynamic for TestDynamic {
+        fn new() -> Self {
+            TestDynamic
+        }
+
+        fn dynamic_type(&self) -> String {
+            "test".to_owned()
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Inf
+        }
+
+        fn state(&self) -> Value {
+            Value::Null
+        }
+    }
+
+    fn atomic(full_name: &str) -> Simulator {
+        let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+        let model = Model::new(structure, Box::new(TestDynamic));
+        Simulator::new(full_name, model, Resources::default())
+    }
+
+    fn coupled(
+        external_input_couplings: &[(&str, &str, &str)],
+        internal_couplings: &[(&str, &str, &str, &str)],
+        external_output_couplings: &[(&str, &str, &str)],
+    ) -> Model {
+        let submodels = ["a", "b"]
+            .iter()
+            .map(|&name| (name.to_owned(), atomic(&format!("root/{}", name))))
+            .collect();
+        let structure = Structure::new(
+            &["in"],
+            &["out"],
+            submodels,
+            external_input_couplings,
+            internal_couplings,
+            external_output_couplings,
+        );
+        Model::new(structure, Box::new(TestDynamic))
+    }
+
+    #[test]
+    fn test_introspection() {
+        let model = coupled(
+            &[("in", "a", "in")],
+            &[("a", "out", "b", "in")],
+            &[("b", "out", "out")],
+        );
+        assert_eq!(model.submodel_names(), vec!["a", "b"]);
+        assert_eq!(model.dynamic_type(), "test");
+        assert!(model.submodel("a").unwrap().submodel_names().is_empty());
+        assert!(model.submodel("c").is_none());
+        let couplings = model.couplings();
+        assert_eq!(couplings.len(), 3);
+        assert_eq!(
+            couplings.couplings()[1],
+            Coupling {
+                kind: CouplingKind::Internal,
+                source_model: Some("a"),
+                source_port: "out",
+                destination_model: Some("b"),
+                destination_port: "in",
+            }
+        );
+        let kinds: Vec<CouplingKind> = couplings.iter().map(|coupling| coupling.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                CouplingKind::ExternalInput,
+                CouplingKind::Internal,
+                CouplingKind::ExternalOutput
+            ]
+        );
+        assert_eq!(couplings.couplings()[2].destination_model, None);
+    }
+
+    #[test]
+    fn test_broadcast_couplings() {
+        let mut submodels: BTreeMap<String, Simulator> = ["hub", "a", "b"]
+            .iter()
+            .map(|&name| (name.to_owned(), atomic(&format!("root/{}", name))))
+            .collect();
+        let structure = Structure::new(&[], &["out"], BTreeMap::new(), &[], &[], &[]);
+        submodels.insert(
+            "sink".to_owned(),
+            Simulator::new(
+                "root/sink",
+                Model::new(structure, Box::new(TestDynamic)),
+                Resources::default(),
+            ),
+        );
+        let structure = Structure::new(&[], &[], submodels, &[], &[], &[]);
+        let model = Model::new(structure, Box::new(TestDynamic))
+            .with_broadcast_to_port("hub", "out", "in")
+            .with_broadcast_coupling("a", "out", &[("hub", "in"), ("sink", "extra")]);
+        assert_eq!(
+            model.fan_out("hub", "out"),
+            vec![(Some("a"), "in"), (Some("b"), "in")]
+        );
+        assert_eq!(
+            model.fan_out("a", "out"),
+            vec![(Some("hub"), "in"), (Some("sink"), "extra")]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Model validation error: duplicate input port 'in'")]
+    fn test_duplicate_port() {
+        Structure::new(&["in", "out", "in"], &[], BTreeMap::new(), &[], &[], &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Model validation error: empty output port name")]
+    fn test_empty_port() {
+        Structure::new(&["in"], &["out", ""], BTreeMap::new(), &[], &[], &[]);
+    }
+
+    #[test]
+    fn test_try_builders() {
+        assert_eq!(
+            Structure::try_new(&["in"], &["in", "in"], BTreeMap::new(), &[], &[], &[]).err(),
+            Some(ExdsdevsError::ErrorModelValidation(
+                "duplicate output port 'in'".to_owned()
+            ))
+        );
+        let structure = Structure::try_new(&["in"], &[], BTreeMap::new(), &[], &[], &[]).unwrap();
+        let model = Model::new(structure, Box::new(TestDynamic));
+        let model = model
+            .try_with_input_port_typed("in", PortType::Number)
+            .unwrap();
+        assert!(matches!(
+            model.try_with_input_port_capacity("", 1),
+            Err(ExdsdevsError::ErrorModelValidation(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_port_names() {
+        let mut model = coupled(&[], &[], &[]);
+        model.structure.output_ports.push("out".to_owned());
+        assert_eq!(
+            model.check_named("root").unwrap_err().to_string(),
+            ExdsdevsError::ErrorModelValidation(
+                "duplicate output port 'out' of model 'root'".to_owned()
+            )
+            .to_string()
+        );
+    }
+
+    #[test]
+    fn test_definition_round_trip() {
+        let model = coupled(
+            &[("in", "a", "in")],
+            &[("a", "out", "b", "in")],
+            &[("b", "out", "out")],
+        )
+        .with_input_port_typed("in", PortType::Number)
+        .with_priority(2);
+        let definition = model.to_definition();
+        let json = serde_json::to_value(&definition).unwrap();
+        assert_eq!(json["dynamic_type"], "test");
+        assert_eq!(
+            json["submodels"]["a"]["output_ports"],
+            serde_json::json!(["out"])
+        );
+        assert_eq!(
+            json["internal_couplings"][0],
+            serde_json::json!({"src_model": "a", "src_port": "out", "dst_model": "b", "dst_port": "in"})
+        );
+
+        let loaded: ModelDefinition = serde_json::from_value(json).unwrap();
+        let storage = DynamicFactoryStorage::new()
+            .with_dynamic_factory("test", DynamicFactory::<TestDynamic>::new());
+        let rebuilt = loaded.build("root", &storage).unwrap();
+        assert_eq!(rebuilt.priority(), 2);
+        assert_eq!(rebuilt.to_definition(), definition);
+
+        let error = loaded.build("root", &DynamicFactoryStorage::new()).err();
+        assert!(matches!(error, Some(ExdsdevsError::ErrorBuildSimulator(_))));
+    }
+
+    #[test]
+    fn test_definition_with_observers() {
+        let metrics = serde_json::json!({"observer_class": "metrics", "observer_config": {}});
+        let mut json = serde_json::to_value(coupled(&[], &[], &[]).to_definition()).unwrap();
+        json["observers"] = serde_json::json!([metrics]);
+        json["submodels"]["a"]["observers"] = serde_json::json!([metrics, metrics]);
+        let definition: ModelDefinition = serde_json::from_value(json).unwrap();
+
+        let dynamic_factory_storage = DynamicFactoryStorage::new()
+            .with_dynamic_factory("test", DynamicFactory::<TestDynamic>::new());
+        let observer_factory_storage = ObserverFactoryStorage::new()
+            .with_observer_factory("metrics", ObserverFactory::<Metrics>::new());
+        let simulator = definition
+            .build_simulator("root", &dynamic_factory_storage, &observer_factory_storage)
+            .unwrap();
+        let sub_simulators = &simulator.model.structure.sub_simulators;
+        assert_eq!(simulator.observers.len(), 1);
+        assert_eq!(sub_simulators["a"].observers.len(), 2);
+        assert_eq!(sub_simulators["a"].full_name, "root/a");
+        assert!(sub_simulators["b"].observers.is_empty());
+        // Building the bare model leaves the observers out.
+        let model = definition.build("root", &dynamic_factory_storage).unwrap();
+        assert!(model.structure.sub_simulators["a"].observers.is_empty());
+
+        let error = definition
+            .build_simulator(
+                "root",
+                &dynamic_factory_storage,
+                &ObserverFactoryStorage::new(),
+            )
+            .err();
+        assert!(matches!(error, Some(ExdsdevsError::ErrorBuildSimulator(_))));
+    }
+
+    #[test]
+    fn test_without_submodel() {
+        let mut model = coupled(
+            &[("in", "a", "in"), ("in", "b", "in")],
+            &[("a", "out", "b", "in"), ("b", "out", "a", "in")],
+            &[("a", "out", "out"), ("b", "out", "out")],
+        );
+        let removed = model.without_submodel("a").unwrap();
+        assert_eq!(removed.dynamic_type(), "test");
+        assert_eq!(model.submodel_names(), vec!["b"]);
+        let couplings: Vec<(Option<&str>, Option<&str>)> = model
+            .couplings()
+            .iter()
+            .map(|coupling| (coupling.source_model, coupling.destination_model))
+            .collect();
+        assert_eq!(couplings, vec![(None, Some("b")), (Some("b"), None)]);
+        assert!(model.check().is_ok());
+
+        assert!(model.without_submodel("a").is_none());
+        assert_eq!(model.couplings().len(), 2);
+    }
+
+    #[test]
+    fn test_without_coupling() {
+        let mut model = coupled(
+            &[("in", "a", "in"), ("in", "b", "in")],
+            &[("a", "out", "b", "in"), ("a", "out", "b", "in")],
+            &[("b", "out", "out")],
+        );
+        assert_eq!(model.without_external_input_coupling(("in", "a", "in")), 1);
+        assert_eq!(model.without_internal_coupling(("a", "out", "b", "in")), 2);
+        assert_eq!(
+            model.without_external_output_coupling(("b", "out", "out")),
+            1
+        );
+        let couplings = model.couplings();
+        assert_eq!(couplings.len(), 1);
+        assert_eq!(couplings.couplings()[0].destination_model, Some("b"));
+
+        assert_eq!(model.without_external_input_coupling(("in", "a", "in")), 0);
+        assert_eq!(model.without_internal_coupling(("b", "out", "a", "in")), 0);
+        assert_eq!(
+            model.without_external_output_coupling(("a", "out", "out")),
+            0
+        );
+        assert_eq!(model.couplings().len(), 1);
+    }
+
+    #[test]
+    fn test_external_coupling_renames_ports() {
+        let mut submodels = BTreeMap::new();
+        submodels.insert("server".to_owned(), atomic("root/server"));
+        let structure = Structure::new(&[], &[], submodels, &[], &[], &[]);
+        let model = Model::new(structure, Box::new(TestDynamic))
+            .with_input_port_typed("request", PortType::Number)
+            .with_external_input_coupling(("request", "server", "in"))
+            .with_external_output_coupling(("server", "out", "reply"));
+        assert_eq!(model.structure.input_ports, vec!["request"]);
+        assert_eq!(model.structure.output_ports, vec!["reply"]);
+        assert_eq!(model.fan_out("server", "out"), vec![(None, "reply")]);
+        assert_eq!(
+            model.external_input_couplings(),
+            &[ExternalInputCoupling::new("request", "server", "in")]
+        );
+        assert!(model.check().is_ok());
+
+        let error = Model::new(
+            Structure::new(&[], &[], BTreeMap::new(), &[], &[], &[]),
+            Box::new(TestDynamic),
+        )
+        .try_with_external_output_coupling(("server", "out", ""))
+        .err()
+        .unwrap();
+        assert_eq!(
+            error.to_string(),
+            ExdsdevsError::ErrorModelValidation("empty output port name".to_owned()).to_string()
+        );
+    }
+
+    #[test]
+    fn test_fan_out() {
+        let model = coupled(
+            &[("in", "a", "in")],
+            &[("a", "out", "b", "in"), ("a", "out", "a", "in")],
+            &[("b", "out", "out"), ("a", "out", "out")],
+        );
+        assert_eq!(
+            model.fan_out("a", "out"),
+            vec![(Some("b"), "in"), (Some("a"), "in"), (None, "out")]
+        );
+        assert_eq!(model.fan_out("b", "out"), vec![(None, "out")]);
+        assert!(model.fan_out("a", "in").is_empty());
+    }
+
+    struct Invalid;
+
+    impl Dynamic for Invalid {
+        fn new() -> Self {
+            Invalid
+        }
+
+        fn dynamic_type(&self) -> String {
+            "invalid".to_owned()
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Inf
+        }
+
+        fn state(&self) -> Value {
+            Value::Null
+        }
+
+        fn validate(&self) -> Result<(), String> {
+            Err("capacity must be positive".to_owned())
+        }
+    }
+
+    #[test]
+    fn test_check_validates_dynamics() {
+        let mut model = coupled(&[], &[], &[]);
+        let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+        let invalid = Model::new(structure, Box::new(Invalid));
+        model.structure.sub_simulators.insert(
+            "c".to_owned(),
+            Simulator::new("root/c", invalid, Resources::default()),
+        );
+        assert_eq!(
+            model.check(),
+            Err(ExdsdevsError::ErrorModelValidation(
+                "model 'root/c': capacity must be positive".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_port_type() {
+        assert!(PortType::Any.matches(&Value::Null));
+        assert!(PortType::Number.matches(&Value::from(1.5)));
+        assert!(!PortType::Number.matches(&Value::from("1.5")));
+        assert!(PortType::Object.matches(&serde_json::json!({})));
+        assert_eq!(
+            serde_json::from_value::<BTreeMap<String, PortType>>(serde_json::json!({"in": "bool"}))
+                .unwrap()["in"],
+            PortType::Bool
+        );
+
+        let model = Model::new(
+            Structure::new(&["in"], &[], BTreeMap::new(), &[], &[], &[]),
+            Box::new(TestDynamic),
+        )
+        .with_input_port_typed("in", PortType::Array)
+        .with_input_port_typed("extra", PortType::Null);
+        assert_eq!(model.structure.input_ports, vec!["in", "extra"]);
+        assert_eq!(model.structure.input_port_types["in"], PortType::Array);
+    }
+
+    #[test]
+    fn test_resources_shared_with_submodels() {
+        let mut resources = BTreeMap::new();
+        resources.insert("table".to_owned(), serde_json::json!([1, 2, 3]));
+        let mut ignored = BTreeMap::new();
+        ignored.insert("table".to_owned(), Value::Null);
+        let model = coupled(&[], &[], &[]).with_resources(resources);
+        let mut simulator = Simulator::new(
+            "root",
+            model,
+            Resources {
+                local: Value::from("local"),
+                global: Arc::new(ignored),
+            },
+        );
+        let root_resources = simulator.model.structure.resources.clone();
+        assert_eq!(root_resources.local, Value::from("local"));
+        assert_eq!(root_resources.global["table"], serde_json::json!([1, 2, 3]));
+
+        let init_variant = ["root", "root/a", "root/b"]
+            .iter()
+            .map(|&name| (name.to_owned(), Value::Null))
+            .collect();
+        simulator.init_static("root", &PathBuf::new(), &init_variant, 0, 0);
+        for name in ["a", "b"] {
+            let submodel = simulator.model.submodel(name).unwrap();
+            assert!(Arc::ptr_eq(
+                &submodel.structure.resources.global,
+                &root_resources.global
+            ));
+            assert!(submodel.structure.resources.local.is_null());
+        }
+    }
+
+    #[test]
+    fn test_check_valid() {
+        let model = coupled(
+            &[("in", "a", "in")],
+            &[("a", "out", "b", "in")],
+            &[("b", "out", "out")],
+        );
+        assert_eq!(model.check(), Ok(()));
+    }
+
+    #[test]
+    fn test_check_unknown_submodel() {
+        let model = coupled(&[], &[("a", "out", "c", "in")], &[]);
+        assert!(matches!(
+            model.check(),
+            Err(ExdsdevsError::ErrorModelValidation(msg)) if msg.contains("unknown submodel 'c'")
+        ));
+    }
+
+    #[test]
+    fn test_check_undeclared_ports() {
+        let model = coupled(&[("input", "a", "in")], &[], &[]);
+        assert!(matches!(
+            model.check(),
+            Err(ExdsdevsError::ErrorModelValidation(msg)) if msg.contains("port 'input'")
+        ));
+        let model = coupled(&[], &[("a", "in", "b", "in")], &[]);
+        assert!(model.check().is_err());
+        let model = coupled(&[], &[], &[("b", "out", "output")]);
+        assert!(model.check().is_err());
+    }
+
+    #[test]
+    fn test_check_duplicate_submodel() {
+        let mut submodels = BTreeMap::new();
+        submodels.insert("a".to_owned(), atomic("root/a"));
+        submodels.insert("b".to_owned(), atomic("root/a"));
+        let structure = Structure::new(&[], &[], submodels, &[], &[], &[]);
+        let model = Model::new(structure, Box::new(TestDynamic));
+        assert_eq!(
+            model.check(),
+            Err(ExdsdevsError::ErrorModelValidation(
+                "duplicate submodel name 'a' of model".to_owned()
+            ))
+        );
+
+        let json = r#"{"dynamic_type": "test", "input_ports": [], "output_ports": [],
+            "submodels": {
+                "a": {"dynamic_type": "test", "input_ports": [], "output_ports": []},
+                "a": {"dynamic_type": "test", "input_ports": ["in"], "output_ports": []}
+            }}"#;
+        let err = serde_json::from_str::<ModelDefinition>(json).unwrap_err();
+        assert!(err.to_string().contains("duplicate submodel name 'a'"));
+    }
+
+    #[test]
+    fn test_check_nested_path() {
+        let inner = coupled(&[], &[("a", "out", "x", "in")], &[]);
+        let mut submodels = BTreeMap::new();
+        submodels.insert(
+            "inner".to_owned(),
+            Simulator::new("root/inner", inner, Resources::default()),
+        );
+        let structure = Structure::new(&[], &[], submodels, &[], &[], &[]);
+        let model = Model::new(structure, Box::new(TestDynamic));
+        assert!(matches!(
+            model.check(),
+            Err(ExdsdevsError::ErrorModelValidation(msg)) if msg.contains("model 'root/inner'")
+        ));
+    }
+
+    #[test]
+    fn test_build_simulator_errors() {
+        let dir = temp_dir("model_build_errors");
+        write_experiment(&dir, 1);
+        let build = |dynamic_factory, observer_factory, class: &str| {
+            ModelFactory::try_new(&dir.join("model"), dynamic_factory, observer_factory)
+                .unwrap()
+                .build_simulator(class, "root".to_owned(), &Arc::new(BTreeMap::new()))
+                .err()
+        };
+        let (dynamic_factory, observer_factory) = experiment_factories();
+        assert!(matches!(
+            build(dynamic_factory, observer_factory, "unknown"),
+            Some(ExdsdevsError::ErrorModelValidation(msg)) if msg.contains("'unknown'")
+        ));
+        let (_, observer_factory) = experiment_factories();
+        let dynamic_factory = DynamicFactoryStorage::new()
+            .with_dynamic_factory("passive", DynamicFactory::<TestDynamic>::new());
+        assert!(matches!(
+            build(dynamic_factory, observer_factory, "root"),
+            Some(ExdsdevsError::ErrorBuildSimulator(msg))
+                if msg.contains("the dynamic 'clock' has not been registered")
+        ));
+        let (dynamic_factory, _) = experiment_factories();
+        assert!(matches!(
+            build(dynamic_factory, ObserverFactoryStorage::new(), "root"),
+            Some(ExdsdevsError::ErrorBuildSimulator(msg))
+                if msg.contains("the observer 'metrics' has not been registered")
+        ));
+        let (dynamic_factory, observer_factory) = experiment_factories();
+        assert!(build(dynamic_factory, observer_factory, "root").is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 
     #[test]
     fn test_collect_paths() {
@@ -9,8 +9,9 @@
 #![allow(unused_variables)]
 use crate::{
     containers::{Bag, Mail, Value},
+    errors::ExdsdevsError,
     factory::Factory,
-    model::Model,
+    model::{Coupling, Model, PortType},
     time::Time,
 };
 
@@ -23,8 +24,16 @@ pub trait Observer {
     fn config(&mut self, observer_config: &Value) {}
     fn init_observer(&mut self, init_config: &Value) {}
     fn on_init(&mut self, model: &Model, init_time: Time, init_value: &Value, t_next: Time) {}
+    fn before_output(&mut self, model: &Model, sim_time: Time) {}
+    /// Called by a coupled model with its imminent submodels in the order they
+    /// are processed, see `Model::with_priority`.
+    fn on_imminent(&mut self, model: &Model, sim_time: Time, imminent: &[String]) {}
+    /// Called after the outputs of the model are computed, pairs with
+    /// `before_output`.
     fn on_outputs(&mut self, model: &Model, sim_time: Time, bag: &Bag) {}
-    fn before_internal_transition(&mut self, model: &Model, sim_time: Time) {}
+    /// `t_last` is the time of the previous transition of the model, so
+    /// `sim_time - t_last` is the time spent in the current state.
+    fn before_internal_transition(&mut self, model: &Model, sim_time: Time, t_last: Time) {}
     fn after_internal_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {}
     fn before_external_transition(
         &mut self,
@@ -44,9 +53,31 @@ pub trait Observer {
     ) {
     }
     fn after_external_mail_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {}
-    fn before_confluent_transition(&mut self, model: &Model, sim_time: Time, x_bag: &Bag) {}
+    /// `t_last` is the time of the previous transition of the model.
+    fn before_confluent_transition(
+        &mut self,
+        model: &Model,
+        sim_time: Time,
+        x_bag: &Bag,
+        t_last: Time,
+    ) {
+    }
     fn after_confluent_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {}
     fn after_submodels_transition(&mut self, model: &Model, sim_time: Time, t_next: Time) {}
+    /// Called by a coupled model for every message it routes to one of its
+    /// submodels, through an external input or an internal coupling.
+    fn on_route(&mut self, model: &Model, sim_time: Time, coupling: &Coupling, value: &Value) {}
+    /// Called by a coupled model, after `on_route`, when a routed value does not
+    /// match the type of the destination port. The message is still delivered.
+    fn on_port_type_mismatch(
+        &mut self,
+        model: &Model,
+        sim_time: Time,
+        coupling: &Coupling,
+        value: &Value,
+        expected: PortType,
+    ) {
+    }
     fn before_finish(&mut self, model: &Model, sim_time: Time) {}
     fn after_finish(&mut self, model: &Model, sim_time: Time) {}
     fn result(&self) -> Option<Value> {
@@ -54,6 +85,55 @@ pub trait Observer {
     }
 }
 
+/// An observer of a simulator, with whether it may be moved to another thread.
+pub(crate) enum ObserverBox {
+    Local(Box<dyn Observer>),
+    Send(Box<dyn Observer + Send>),
+}
+
+impl ObserverBox {
+    pub(crate) fn into_inner(self) -> Box<dyn Observer> {
+        match self {
+            Self::Local(observer) => observer,
+            Self::Send(observer) => observer,
+        }
+    }
+}
+
+impl std::ops::Deref for ObserverBox {
+    type Target = dyn Observer;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Local(observer) => observer.as_ref(),
+            Self::Send(observer) => observer.as_ref(),
+        }
+    }
+}
+
+impl std::ops::DerefMut for ObserverBox {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Self::Local(observer) => observer.as_mut(),
+            Self::Send(observer) => observer.as_mut(),
+        }
+    }
+}
+
+/// Observes a whole run rather than a single model; registered on the
+/// `RootSimulator`.
+pub trait GlobalObserver {
+    /// Called once all models are initialized.
+    fn on_simulation_start(&mut self, init_time: Time) {}
+    /// Called right after `on_simulation_start` when no model has an event
+    /// scheduled, so that the run ends at once, see
+    /// `RootSimulator::never_scheduled`.
+    fn on_nothing_scheduled(&mut self, init_time: Time) {}
+    /// Called once all models are finished, with the results returned by
+    /// `RootSimulator::results`.
+    fn on_simulation_end(&mut self, sim_time: Time, results: &BTreeMap<String, Vec<Value>>) {}
+}
+
 #[derive(Debug, Default)]
 pub struct ObserverFactory<T>(PhantomData<T>);
 impl<T: Observer> ObserverFactory<T> {
@@ -83,32 +163,42 @@ impl ObserverFactoryStorage {
         Default::default()
     }
 
-    fn add_observer_factory<T: Observer + 'static>(
+    /// Registers the observers created by `observer_factory`, usually an
+    /// `ObserverFactory`, under `observer_class_name`, the `observer_class`
+    /// used by model classes to attach them.
+    pub fn add_observer_factory<F: Factory<Item = Box<dyn Observer>> + 'static>(
         &mut self,
         observer_class_name: &str,
-        observer_factory: ObserverFactory<T>,
+        observer_factory: F,
     ) {
         self.factories
             .insert(observer_class_name.to_owned(), Box::new(observer_factory));
     }
 
-    pub fn with_observer_factory<T: Observer + 'static>(
+    pub fn with_observer_factory<F: Factory<Item = Box<dyn Observer>> + 'static>(
         mut self,
         observer_class_name: &str,
-        observer_factory: ObserverFactory<T>,
+        observer_factory: F,
     ) -> Self {
         self.add_observer_factory(observer_class_name, observer_factory);
         self
     }
 
-    pub fn get_observer(&self, observer_class: &str) -> Result<Box<dyn Observer>, String> {
+    /// Creates a new observer registered under `observer_class`.
+    pub fn get_observer(&self, observer_class: &str) -> Result<Box<dyn Observer>, ExdsdevsError> {
         if let Some(observer_factory) = self.factories.get(observer_class) {
             Ok(observer_factory.create())
         } else {
-            Err(format!(
-                "The Observer '{}' have not been registered",
-                observer_class
-            ))
+            Err(ExdsdevsError::ErrorBuildSimulator(format!(
+                "the observer '{}' has not been registered, registered observers are {:?}",
+                observer_class,
+                self.observer_classes()
+            )))
         }
     }
+
+    /// Names of the registered observers, sorted.
+    pub fn observer_classes(&self) -> Vec<&str> {
+        self.factories.keys().map(String::as_str).collect()
+    }
 }
@@ -6,30 +6,78 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms
 
-use std::cell::RefCell;
 use std::collections::BTreeMap;
-use std::path::PathBuf;
-use std::rc::Rc;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use rand::rngs::StdRng;
-use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 
 use crate::containers::{Bag, Value};
+use crate::errors::ExdsdevsError;
 use crate::model::ModelFactory;
+use crate::observer::GlobalObserver;
 
+use crate::simulator::{SimulatorCheckpoint, StateSnapshot};
+use crate::utils::{read_json_from_file, write_json_to_file};
 use crate::{simulator::Simulator, time::Time};
 
+const DEFAULT_MAX_ZERO_TIME_STEPS: usize = 1_000_000;
+/// Number of steps between two wall-clock reads when a timeout is set.
+const TIMEOUT_CHECK_INTERVAL: usize = 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Checkpoint {
+    sim_time: Value,
+    #[serde(default)]
+    event_count: u64,
+    models: BTreeMap<String, SimulatorCheckpoint>,
+}
+
+/// Why a run ended, see `RootSimulator::termination_reason`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminationReason {
+    /// The next event comes at or after `finish_time`.
+    ReachedFinishTime,
+    /// No model has an event scheduled anymore.
+    Quiescent,
+    /// A model returned `Time::StopSim` from `time_advance`.
+    Stopped,
+    /// The wall-clock timeout set by `with_timeout` was exceeded.
+    Timeout,
+}
+
+/// Runs a simulator tree from `init_time` to `finish_time`.
+///
+/// A root simulator is built with `new`, `from_simulator` or `new_with_seed`,
+/// then `init_static` sets the init values, the results directory and the seed
+/// of the run, and `init` initializes the models at `init_time`. `init` never
+/// reseeds the random streams: they are seeded from `random_seed` by
+/// `new_with_seed` or `init_static` only, and from 0 otherwise.
 pub struct RootSimulator {
     pub root_model_full_name: String,
     pub simulator: Simulator,
     pub init_time: Time,
     pub finish_time: Time,
     pub sim_time: Time,
-    pub rng: Rc<RefCell<StdRng>>,
+    pub random_seed: u64,
+    pub iteration: u64,
+    max_zero_time_steps: usize,
+    zero_time_steps: usize,
+    event_count: u64,
+    allow_no_events: bool,
+    never_scheduled: bool,
+    stopped: bool,
+    timed_out: bool,
+    timeout: Option<Duration>,
+    global_observers: Vec<Box<dyn GlobalObserver>>,
 }
 
 impl RootSimulator {
+    /// Panics when a model class, a dynamic or an observer of the model is not
+    /// registered, see `try_new`.
     pub fn new(
         model_factory: Arc<ModelFactory>,
         root_model_class_name: String,
@@ -38,72 +86,2049 @@ impl RootSimulator {
         init_time: Time,
         finish_time: Time,
     ) -> RootSimulator {
-        let rng = Rc::new(RefCell::new(StdRng::seed_from_u64(0)));
+        Self::try_new(
+            model_factory,
+            root_model_class_name,
+            root_model_full_name,
+            global_resources,
+            init_time,
+            finish_time,
+        )
+        .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn try_new(
+        model_factory: Arc<ModelFactory>,
+        root_model_class_name: String,
+        root_model_full_name: String,
+        global_resources: Arc<BTreeMap<String, Value>>,
+        init_time: Time,
+        finish_time: Time,
+    ) -> Result<RootSimulator, ExdsdevsError> {
         let simulator = model_factory.build_simulator(
             &root_model_class_name,
-            root_model_full_name.clone(),
+            root_model_full_name,
             &global_resources,
-        );
+        )?;
+        Ok(Self::from_simulator(simulator, init_time, finish_time))
+    }
 
+    /// Wraps an already built simulator tree, e.g. one assembled by hand from
+    /// `Model`s instead of loaded through a `ModelFactory`.
+    pub fn from_simulator(simulator: Simulator, init_time: Time, finish_time: Time) -> Self {
         RootSimulator {
-            root_model_full_name,
+            root_model_full_name: simulator.full_name.clone(),
             simulator,
             init_time,
             finish_time,
             sim_time: init_time,
-            rng,
+            random_seed: 0,
+            iteration: 0,
+            max_zero_time_steps: DEFAULT_MAX_ZERO_TIME_STEPS,
+            zero_time_steps: 0,
+            event_count: 0,
+            allow_no_events: false,
+            never_scheduled: false,
+            stopped: false,
+            timed_out: false,
+            timeout: None,
+            global_observers: Vec::new(),
         }
     }
 
+    /// Same as `from_simulator`, with the random streams of the models seeded
+    /// from `random_seed` up front, so the run does not depend on
+    /// `init_static` to get its seed.
+    pub fn new_with_seed(
+        simulator: Simulator,
+        init_time: Time,
+        finish_time: Time,
+        iteration: u64,
+        random_seed: u64,
+    ) -> Self {
+        let mut root = Self::from_simulator(simulator, init_time, finish_time);
+        root.iteration = iteration;
+        root.random_seed = random_seed;
+        root.simulator.seed(&root.root_model_full_name, random_seed);
+        root
+    }
+
+    /// Sets the init values and the results directory of the models, and
+    /// reseeds their random streams from `random_seed`.
     pub fn init_static(
         &mut self,
         sim_dir: &PathBuf,
         init_variant: &BTreeMap<String, Value>,
         random_seed: u64,
     ) {
-        self.rng = Rc::new(RefCell::new(StdRng::seed_from_u64(random_seed)));
+        self.random_seed = random_seed;
+        self.simulator.set_finish_time(self.finish_time);
         self.simulator.init_static(
             &self.root_model_full_name,
             sim_dir,
             init_variant,
-            self.rng.clone(),
+            random_seed,
+            self.iteration,
         );
     }
 
+    /// Panics when a model cannot be initialized, see `try_init`.
     pub fn init(&mut self) {
-        self.simulator.init(self.init_time);
-        self.sim_time = self.simulator.t_next();
+        self.try_init().unwrap_or_else(|err| panic!("{}", err))
     }
 
-    fn collect_outputs(&mut self) {
-        self.simulator.collect_outputs(self.sim_time);
+    /// Initializes the models at the init time. Fails when a model built
+    /// `with_restore_from_init` cannot restore its init value.
+    pub fn try_init(&mut self) -> Result<(), ExdsdevsError> {
+        self.simulator.init(self.init_time)?;
+        self.sim_time = self.init_time;
+        self.stopped = false;
+        self.timed_out = false;
+        self.event_count = 0;
+        self.advance_time();
+        self.never_scheduled = !self.allow_no_events && !self.stopped && self.sim_time.is_inf();
+        for global_observer in self.global_observers.iter_mut() {
+            global_observer.on_simulation_start(self.init_time);
+            if self.never_scheduled {
+                global_observer.on_nothing_scheduled(self.init_time);
+            }
+        }
+        Ok(())
     }
 
-    fn process_y_messages(&mut self) {
-        self.simulator.process_y_messages(self.sim_time);
+    /// Starts the run over with the random streams seeded from `random_seed`,
+    /// without rebuilding the model tree, see `Simulator::reset`; replaces
+    /// `init`. Fails when a dynamic cannot be reset.
+    pub fn reset(&mut self, random_seed: u64) -> Result<(), ExdsdevsError> {
+        self.random_seed = random_seed;
+        self.rewind()?;
+        self.simulator.seed(&self.root_model_full_name, random_seed);
+        self.simulator.restart_observers();
+        self.try_init()
     }
 
-    fn process_x_messages(&mut self) {
+    /// Puts the models back in their state before the first `init`; seeding
+    /// them and `init` follow, e.g. through `init_static`.
+    pub(crate) fn rewind(&mut self) -> Result<(), ExdsdevsError> {
+        self.simulator.rewind()
+    }
+
+    /// Global observers must be added before `init`.
+    pub fn with_global_observer(mut self, global_observer: Box<dyn GlobalObserver>) -> Self {
+        self.add_global_observer(global_observer);
+        self
+    }
+
+    pub fn add_global_observer(&mut self, global_observer: Box<dyn GlobalObserver>) {
+        self.global_observers.push(global_observer);
+    }
+
+    /// Moves `sim_time` to the next scheduled event, unless a model asked to
+    /// stop the simulation with `Time::StopSim`.
+    fn advance_time(&mut self) {
+        let t_next = self.simulator.t_next();
+        if t_next.is_stop() {
+            self.stopped = true;
+        } else {
+            self.sim_time = t_next;
+        }
+    }
+
+    /// Whether a model ended the simulation early by returning `Time::StopSim`
+    /// from `time_advance`.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Why the run ended, `None` while it can go on, e.g. between two
+    /// `run_until` calls. A run that went quiescent before `finish_time` is
+    /// told apart from a completed one.
+    pub fn termination_reason(&self) -> Option<TerminationReason> {
+        if self.timed_out {
+            Some(TerminationReason::Timeout)
+        } else if self.stopped {
+            Some(TerminationReason::Stopped)
+        } else if self.sim_time.is_inf() {
+            Some(TerminationReason::Quiescent)
+        } else if self.sim_time >= self.finish_time {
+            Some(TerminationReason::ReachedFinishTime)
+        } else {
+            None
+        }
+    }
+
+    /// Writes the state and timing of every model to `path` as JSON, without
+    /// changing the simulation.
+    ///
+    /// The random streams are stored with their position, so a run restored
+    /// from the checkpoint makes the same random draws as this run after the
+    /// checkpoint.
+    pub fn save_checkpoint(&self, path: &Path) -> Result<(), ExdsdevsError> {
+        let mut models = BTreeMap::new();
+        self.simulator.checkpoint(&mut models);
+        let checkpoint = Checkpoint {
+            sim_time: Value::from(&self.sim_time),
+            event_count: self.event_count,
+            models,
+        };
+        write_json_to_file(path, &checkpoint)
+    }
+
+    /// Resumes a simulation saved by `save_checkpoint`.
+    ///
+    /// `root_simulator` must be built from the same models and statically
+    /// initialized with `init_static`, but not initialized: instead of `init`,
+    /// every model gets its saved state (through `Dynamic::restore_state`) and
+    /// timing. The observers thus get no `on_init` and see the run from the
+    /// checkpoint on.
+    pub fn load_checkpoint(
+        path: &Path,
+        mut root_simulator: RootSimulator,
+    ) -> Result<RootSimulator, ExdsdevsError> {
+        let checkpoint: Checkpoint = read_json_from_file(path)?;
+        root_simulator.simulator.restore(&checkpoint.models)?;
+        root_simulator.sim_time =
+            Time::try_from(&checkpoint.sim_time).map_err(ExdsdevsError::ErrorCheckpoint)?;
+        root_simulator.stopped = root_simulator.simulator.t_next().is_stop();
+        root_simulator.timed_out = false;
+        root_simulator.zero_time_steps = 0;
+        root_simulator.event_count = checkpoint.event_count;
+        root_simulator.never_scheduled = false;
+        Ok(root_simulator)
+    }
+
+    fn collect_outputs(&mut self) -> Result<(), ExdsdevsError> {
+        self.simulator.collect_outputs(self.sim_time).map(|_| ())
+    }
+
+    fn process_x_messages(&mut self) -> Result<(), ExdsdevsError> {
         let x_bag = Bag::new();
-        self.simulator.process_x_messages(self.sim_time, x_bag);
+        self.simulator.process_x_messages(self.sim_time, x_bag)
     }
 
     fn finish(&mut self, sim_time: Time) -> Value {
         self.simulator.finish(sim_time);
+        if !self.global_observers.is_empty() {
+            let results = self.results();
+            for global_observer in self.global_observers.iter_mut() {
+                global_observer.on_simulation_end(sim_time, &results);
+            }
+        }
         serde_json::Value::Bool(true)
     }
 
-    fn step(&mut self) {
-        self.collect_outputs();
-        self.process_y_messages();
-        self.process_x_messages();
+    fn step(&mut self) -> Result<(), ExdsdevsError> {
+        self.collect_outputs()?;
+        self.process_x_messages()?;
+        self.event_count += 1;
+        Ok(())
+    }
+
+    /// Bounds the wall-clock duration of each `run_until`/`run` call, which then
+    /// fails with `ErrorSimTime`. The clock is read every
+    /// `TIMEOUT_CHECK_INTERVAL` steps, so the limit may be slightly exceeded.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how many consecutive events may happen at the same simulation time
+    /// before `run` aborts, which protects against zero-time coupling cycles.
+    pub fn set_max_zero_time_steps(&mut self, max_zero_time_steps: usize) {
+        self.max_zero_time_steps = max_zero_time_steps;
+    }
+
+    /// By default `init` warns when no model has an event scheduled, which
+    /// usually means a first event was forgotten, see `never_scheduled`.
+    /// Allowing it silences the warning for models that are passive on
+    /// purpose. Must be set before `init`.
+    pub fn set_allow_no_events(&mut self, allow_no_events: bool) {
+        self.allow_no_events = allow_no_events;
+    }
+
+    /// Whether `init` found no event scheduled in any model, so that the run
+    /// ends at once; the global observers are then told through
+    /// `GlobalObserver::on_nothing_scheduled`. Always false when runs without
+    /// events are allowed, see `set_allow_no_events`.
+    pub fn never_scheduled(&self) -> bool {
+        self.never_scheduled
+    }
+
+    /// Performs exactly one event cycle and returns the new simulation time,
+    /// which is `Time::Inf` when nothing is scheduled anymore. Does nothing and
+    /// returns the current time once `finish_time` is reached or the simulation
+    /// is stopped.
+    ///
+    /// Panics when the event cycle fails, e.g. on a port capacity overflow or
+    /// after too many events at the same time (see `set_max_zero_time_steps`),
+    /// see `try_step_once`.
+    pub fn step_once(&mut self) -> Time {
+        self.try_step_once()
+            .unwrap_or_else(|error| panic!("{}", error))
     }
 
-    pub fn run(&mut self) {
-        while self.sim_time < self.finish_time {
-            self.step();
-            self.sim_time = self.simulator.t_next();
+    /// Same as `step_once`, failing instead of panicking.
+    pub fn try_step_once(&mut self) -> Result<Time, ExdsdevsError> {
+        if self.stopped || self.sim_time >= self.finish_time {
+            return Ok(self.sim_time);
+        }
+        self.checked_step()?;
+        Ok(self.sim_time)
+    }
+
+    /// Returns the non-empty observer results of every model, keyed by model
+    /// full name.
+    pub fn results(&self) -> BTreeMap<String, Vec<Value>> {
+        let mut results = BTreeMap::new();
+        self.simulator.results(&mut results);
+        results
+    }
+
+    pub fn snapshot(&self) -> StateSnapshot {
+        self.simulator.snapshot()
+    }
+
+    /// The simulator of the model named `full_name`, e.g. `root/s1`, to read
+    /// its state in a debugger or a test; see `Simulator::find`.
+    pub fn find_model(&self, full_name: &str) -> Option<&Simulator> {
+        self.simulator.find(full_name)
+    }
+
+    pub fn current_time(&self) -> Time {
+        self.sim_time
+    }
+
+    /// Number of event cycles processed since `init`, every event at the same
+    /// time counting on its own. Divided by the wall-clock duration of the run
+    /// it gives the throughput of the simulation.
+    pub fn event_count(&self) -> u64 {
+        self.event_count
+    }
+
+    /// Share of the time from `init_time` to `finish_time` simulated so far,
+    /// between 0 and 1, e.g. for a progress bar while stepping with
+    /// `run_until`. `None` when `finish_time` is `Inf` or `StopSim`, the end of
+    /// the run being unknown. A run ended by `Time::StopSim` is complete, as
+    /// is one with no event left.
+    pub fn progress(&self) -> Option<f64> {
+        let init_time = self.init_time.as_f64()?;
+        let finish_time = self.finish_time.as_f64()?;
+        if self.stopped || finish_time <= init_time {
+            return Some(1.0);
+        }
+        let progress = match self.sim_time.as_f64() {
+            Some(sim_time) => (sim_time - init_time) / (finish_time - init_time),
+            None => 1.0,
+        };
+        Some(progress.clamp(0.0, 1.0))
+    }
+
+    /// Performs one event cycle and advances `sim_time`, failing when too many
+    /// consecutive events happen at the same time or a port capacity is exceeded.
+    fn checked_step(&mut self) -> Result<(), ExdsdevsError> {
+        self.step()?;
+        let t_next = self.simulator.t_next();
+        if t_next.is_stop() {
+            self.stopped = true;
+            return Ok(());
+        }
+        if t_next == self.sim_time {
+            self.zero_time_steps += 1;
+            if self.zero_time_steps > self.max_zero_time_steps {
+                let mut imminent = Vec::new();
+                self.simulator.imminent_names(t_next, &mut imminent);
+                return Err(ExdsdevsError::ErrorSimTime(format!(
+                    "simulation time is stuck at {} after {} zero-time steps, imminent models: {}",
+                    t_next,
+                    self.zero_time_steps,
+                    imminent.join(", ")
+                )));
+            }
+        } else {
+            self.zero_time_steps = 0;
+        }
+        self.sim_time = t_next;
+        Ok(())
+    }
+
+    /// Processes all events scheduled strictly before `time` (but not later than
+    /// `finish_time`) and leaves the simulator resumable: a subsequent call
+    /// continues from the same state. Returns the intermediate results, as
+    /// `results` does; the models are not finished.
+    pub fn run_until(&mut self, time: Time) -> Result<BTreeMap<String, Vec<Value>>, ExdsdevsError> {
+        self.advance_until(time)?;
+        Ok(self.results())
+    }
+
+    /// `run_until` without collecting the results.
+    fn advance_until(&mut self, time: Time) -> Result<(), ExdsdevsError> {
+        let until = time.min(self.finish_time);
+        let start = Instant::now();
+        let mut steps: usize = 0;
+        while !self.stopped && self.sim_time < until {
+            if let Some(timeout) = self.timeout {
+                steps += 1;
+                if steps % TIMEOUT_CHECK_INTERVAL == 0 && start.elapsed() > timeout {
+                    self.timed_out = true;
+                    return Err(ExdsdevsError::ErrorSimTime(format!(
+                        "wall-clock timeout of {:?} exceeded at simulation time {}",
+                        timeout, self.sim_time
+                    )));
+                }
+            }
+            self.checked_step()?;
+        }
+        Ok(())
+    }
+
+    /// Runs the simulation to `finish_time` and finishes the models. A model
+    /// returning `Time::StopSim` from `time_advance` ends the run right after the
+    /// current event, the models are then finished at the time of that event.
+    /// Returns why the run ended; a timeout is an error, after which
+    /// `termination_reason` gives `Timeout`.
+    pub fn run(&mut self) -> Result<TerminationReason, ExdsdevsError> {
+        self.advance_until(self.finish_time)?;
+        self.finish(self.sim_time);
+        Ok(self.end_reason())
+    }
+
+    /// The termination reason of a run that went to its end.
+    fn end_reason(&self) -> TerminationReason {
+        self.termination_reason()
+            .unwrap_or(TerminationReason::ReachedFinishTime)
+    }
+
+    /// Same as `run`, but the imminent top-level submodels compute their
+    /// outputs and make their transitions concurrently on `num_threads`
+    /// threads. Results are the same as with `run`, the observers of these
+    /// submodels are called from the worker threads. A submodel panicking in
+    /// a worker thread makes the run fail with `ErrorSimTime`.
+    ///
+    /// The dynamics and observers of the top-level submodels must be `Send`,
+    /// see `Model::new_send` and `Simulator::add_send_observer`, otherwise the
+    /// run fails with `ErrorBuildSimulator`.
+    pub fn run_parallel(&mut self, num_threads: usize) -> Result<TerminationReason, ExdsdevsError> {
+        self.simulator.check_send_submodels()?;
+        let pool = threadpool::Builder::new().num_threads(num_threads).build();
+        self.simulator.set_pool(Some(pool));
+        let result = self.run();
+        self.simulator.set_pool(None);
+        result
+    }
+
+    /// Same as `run`, but sleeps before each event so that one unit of
+    /// simulation time lasts `scale` seconds of wall-clock time. Events at the
+    /// same simulation time are processed without sleeping.
+    pub fn run_realtime(&mut self, scale: f64) -> Result<TerminationReason, ExdsdevsError> {
+        let mut paced_time = self.sim_time.min(self.init_time);
+        while !self.stopped && self.sim_time < self.finish_time {
+            if let Some(delay) = (self.sim_time - paced_time).as_f64() {
+                let seconds = delay * scale;
+                if seconds > 0.0 {
+                    thread::sleep(Duration::from_secs_f64(seconds));
+                }
+            }
+            paced_time = self.sim_time;
+            self.checked_step()?;
         }
         self.finish(self.sim_time);
+        Ok(self.end_reason())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::containers::{Mail, Msg, SimRng};
+    use crate::dynamic::Dynamic;
+    use crate::metrics::Metrics;
+    use crate::model::{
+        Coupling, ExternalInputCoupling, InternalCoupling, Model, PortType, Resources, Structure,
+        StructureChange,
+    };
+    use crate::observer::Observer;
+    use crate::test_utils::{
+        atomic_structure, build_root, build_root_observed, child_state, init_root,
+        null_init_variant, root_model, Clock, Passive,
+    };
+    use crate::trajectory::TrajectoryObserver;
+    use std::{cell::RefCell, rc::Rc};
+
+    struct ZeroTime;
+
+    impl Dynamic for ZeroTime {
+        fn new() -> Self {
+            ZeroTime
+        }
+
+        fn dynamic_type(&self) -> String {
+            "zero_time".to_owned()
+        }
+
+        fn output(&self, _: &Structure, _: Time) -> Bag {
+            vec![Msg::new("out", Value::Null)]
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Value(0)
+        }
+
+        fn state(&self) -> Value {
+            Value::Null
+        }
+    }
+
+    /// Stops the whole simulation at its first internal event, at time 5.
+    struct Stopper {
+        fired: bool,
+    }
+
+    impl Dynamic for Stopper {
+        fn new() -> Self {
+            Stopper { fired: false }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "stopper".to_owned()
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
+            self.fired = true;
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            if self.fired {
+                Time::StopSim
+            } else {
+                Time::Value(5)
+            }
+        }
+
+        fn state(&self) -> Value {
+            Value::Bool(self.fired)
+        }
+    }
+
+    /// Fires once at time 1 and then asks for the largest representable delay.
+    struct Huge {
+        fired: bool,
+    }
+
+    impl Dynamic for Huge {
+        fn new() -> Self {
+            Huge { fired: false }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "huge".to_owned()
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
+            self.fired = true;
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            if self.fired {
+                Time::Value(i128::MAX)
+            } else {
+                Time::Value(1)
+            }
+        }
+
+        fn state(&self) -> Value {
+            Value::Bool(self.fired)
+        }
+    }
+
+    #[test]
+    fn test_zero_time_loop_is_detected() {
+        let mut root = build_root(
+            vec![("loop", Box::new(ZeroTime)), ("idle", Box::new(Passive))],
+            Time::Value(10),
+        );
+        root.set_max_zero_time_steps(100);
+        match root.run() {
+            Err(ExdsdevsError::ErrorSimTime(msg)) => {
+                assert!(msg.contains("root/loop"));
+                assert!(!msg.contains("root/idle"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_zero_time_loop_is_detected_when_stepping() {
+        let mut root = build_root(vec![("loop", Box::new(ZeroTime))], Time::Value(10));
+        root.set_max_zero_time_steps(100);
+        let error = (0..=100)
+            .map(|_| root.try_step_once())
+            .find_map(Result::err);
+        assert!(matches!(
+            error,
+            Some(ExdsdevsError::ErrorSimTime(msg)) if msg.contains("root/loop")
+        ));
+    }
+
+    #[test]
+    fn test_run_until_is_resumable() {
+        let clock = || {
+            build_root_observed(
+                vec![("clock", Box::new(Clock::new()))],
+                Time::Value(30),
+                &PathBuf::new(),
+                &|| vec![Box::new(TrajectoryObserver::new().with_pointer("/t_next"))],
+            )
+        };
+        let mut single = clock();
+        single.run().unwrap();
+
+        let mut stepped = clock();
+        let intermediate = stepped.run_until(Time::Value(10)).unwrap();
+        assert!(stepped.current_time() >= Time::Value(10));
+        let trajectory = |results: &BTreeMap<String, Vec<Value>>| {
+            results["root/clock"][0].as_array().unwrap().clone()
+        };
+        let first = trajectory(&intermediate);
+        let full = trajectory(&single.results());
+        assert!(first.len() > 1 && first.len() < full.len());
+        assert!(full.starts_with(&first));
+        let last = stepped.run_until(Time::Value(100)).unwrap();
+        assert!(stepped.current_time() >= Time::Value(30));
+        assert_eq!(trajectory(&last), full);
+
+        assert_eq!(
+            child_state(&single, "clock"),
+            child_state(&stepped, "clock")
+        );
+        assert!(!child_state(&single, "clock")["events"]
+            .as_array()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_step_once() {
+        let mut single = build_root(vec![("clock", Box::new(Clock::new()))], Time::Value(30));
+        single.run().unwrap();
+
+        let mut stepped = build_root(vec![("clock", Box::new(Clock::new()))], Time::Value(30));
+        let mut sim_time = stepped.current_time();
+        let mut steps = 0;
+        while sim_time < Time::Value(30) {
+            let next_time = stepped.step_once();
+            assert!(next_time > sim_time);
+            sim_time = next_time;
+            steps += 1;
+        }
+        assert_eq!(stepped.step_once(), sim_time);
+        assert_eq!(stepped.current_time(), sim_time);
+        assert_eq!(
+            steps,
+            child_state(&single, "clock")["events"]
+                .as_array()
+                .unwrap()
+                .len()
+        );
+        assert_eq!(
+            child_state(&single, "clock"),
+            child_state(&stepped, "clock")
+        );
+    }
+
+    #[test]
+    fn test_stop_sim() {
+        let mut root = build_root(
+            vec![
+                ("clock", Box::new(Clock::new())),
+                ("stopper", Box::new(Stopper::new())),
+            ],
+            Time::Value(100),
+        );
+        assert_eq!(root.run().unwrap(), TerminationReason::Stopped);
+        assert!(root.is_stopped());
+        assert_eq!(root.current_time(), Time::Value(5));
+        assert_eq!(child_state(&root, "stopper"), Value::Bool(true));
+        let events = child_state(&root, "clock")["events"].clone();
+        assert!(!events.as_array().unwrap().is_empty());
+        assert!(events
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|time| Time::try_from(time).unwrap() <= Time::Value(5)));
+        assert_eq!(root.step_once(), Time::Value(5));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut root = build_root(vec![("clock", Box::new(Clock::new()))], Time::Inf)
+            .with_timeout(Duration::from_millis(10));
+        match root.run_until(Time::Inf) {
+            Err(ExdsdevsError::ErrorSimTime(msg)) => assert!(msg.contains("timeout")),
+            other => panic!("unexpected result {:?}", other),
+        }
+        let sim_time = root.current_time();
+        assert!(sim_time > Time::Value(0) && sim_time < Time::Inf);
+        assert_eq!(root.termination_reason(), Some(TerminationReason::Timeout));
+    }
+
+    #[test]
+    fn test_run_realtime() {
+        let clocks = || vec![("clock", Box::new(Clock::new()) as Box<dyn Dynamic + Send>)];
+        let mut root = build_root(clocks(), Time::Value(20));
+        root.run().unwrap();
+        let mut paced = build_root(clocks(), Time::Value(20));
+        let start = Instant::now();
+        paced.run_realtime(0.001).unwrap();
+        let elapsed = start.elapsed();
+
+        let events = child_state(&paced, "clock")["events"].clone();
+        assert_eq!(events, child_state(&root, "clock")["events"]);
+        let last_event = Time::try_from(events.as_array().unwrap().last().unwrap()).unwrap();
+        assert!(elapsed >= Duration::from_secs_f64(last_event.as_f64().unwrap() * 0.001));
+    }
+
+    #[test]
+    fn test_run_parallel() {
+        let names = ["a", "b", "c", "d", "e", "f"];
+        let build = || {
+            build_root(
+                names
+                    .iter()
+                    .map(|&name| (name, Box::new(Clock::new()) as Box<dyn Dynamic + Send>))
+                    .collect(),
+                Time::Value(50),
+            )
+        };
+        let mut sequential = build();
+        sequential.run().unwrap();
+        let mut parallel = build();
+        parallel.run_parallel(3).unwrap();
+        assert_eq!(parallel.current_time(), sequential.current_time());
+        for name in names {
+            assert_eq!(child_state(&parallel, name), child_state(&sequential, name));
+        }
+    }
+
+    /// Fires every 3 time units, panics when `armed`.
+    struct Bomb {
+        armed: bool,
+    }
+
+    impl Dynamic for Bomb {
+        fn new() -> Self {
+            Bomb { armed: false }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "bomb".to_owned()
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
+            assert!(!self.armed, "boom");
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Value(3)
+        }
+
+        fn state(&self) -> Value {
+            Value::from(self.armed)
+        }
+    }
+
+    #[test]
+    fn test_run_parallel_worker_panic() {
+        let mut root = build_root(
+            vec![
+                ("bomb", Box::new(Bomb { armed: true })),
+                ("dud", Box::new(Bomb::new())),
+            ],
+            Time::Value(10),
+        );
+        match root.run_parallel(2) {
+            Err(ExdsdevsError::ErrorSimTime(msg)) => assert_eq!(
+                msg,
+                "submodel 'root/bomb' panicked in a worker thread at 3: boom"
+            ),
+            other => panic!("unexpected result {:?}", other),
+        }
+        // The tree is whole again.
+        assert_eq!(root.snapshot().submodels.len(), 2);
+        assert_eq!(child_state(&root, "bomb"), Value::from(true));
+    }
+
+    #[test]
+    fn test_run_parallel_needs_send() {
+        let expect_not_send = |mut root: RootSimulator, expected: &str| match root.run_parallel(2) {
+            Err(ExdsdevsError::ErrorBuildSimulator(msg)) => {
+                assert!(msg.starts_with(expected), "{}", msg)
+            }
+            other => panic!("unexpected result {:?}", other),
+        };
+
+        let local = Model::new(atomic_structure(&[]), Box::new(Clock::new()));
+        let model = root_model(vec![("local", local)], &[], Box::new(Passive));
+        let simulator = Simulator::new("root", model, Resources::default());
+        let root = init_root(simulator, Time::Value(10), Path::new(""));
+        expect_not_send(root, "the dynamic of model 'root/local' is not Send");
+
+        // An observer sharing its records through an `Rc` is fine with `run`.
+        let root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(10),
+            &PathBuf::new(),
+            &|| vec![Box::new(Routes::new())],
+        );
+        expect_not_send(root, "an observer of model 'root/clock' is not Send");
+    }
+
+    #[test]
+    fn test_run_parallel_keeps_the_tree_when_not_send() {
+        let inner = Simulator::new(
+            "root/b/inner",
+            Model::new(atomic_structure(&[]), Box::new(Clock::new())),
+            Resources::default(),
+        );
+        let mut submodels = BTreeMap::new();
+        submodels.insert("inner".to_owned(), inner);
+        let b = Model::new_send(
+            Structure::new(&[], &[], submodels, &[], &[], &[]),
+            Box::new(Passive),
+        );
+        let a = Model::new_send(atomic_structure(&[]), Box::new(Clock::new()));
+        let model = root_model(vec![("a", a), ("b", b)], &[], Box::new(Passive));
+        let simulator = Simulator::new("root", model, Resources::default());
+        let mut root = init_root(simulator, Time::Value(10), Path::new(""));
+
+        match root.run_parallel(2) {
+            Err(ExdsdevsError::ErrorBuildSimulator(msg)) => assert!(
+                msg.starts_with("the dynamic of model 'root/b/inner' is not Send"),
+                "{}",
+                msg
+            ),
+            other => panic!("unexpected result {:?}", other),
+        }
+        for full_name in ["root/a", "root/b", "root/b/inner"] {
+            assert!(root.find_model(full_name).is_some());
+        }
+        root.run().unwrap();
+    }
+
+    struct Tag(i64);
+
+    impl Observer for Tag {
+        fn new() -> Self {
+            Tag(0)
+        }
+
+        fn result(&self) -> Option<Value> {
+            Some(Value::from(self.0))
+        }
+    }
+
+    #[test]
+    fn test_add_and_remove_observers() {
+        let mut root = build_root(vec![("clock", Box::new(Clock::new()))], Time::Value(10));
+        let clock = root
+            .simulator
+            .model
+            .structure
+            .sub_simulators
+            .get_mut("clock")
+            .unwrap();
+        for tag in 1..=3 {
+            clock.add_observer(Box::new(Tag(tag)));
+        }
+        assert_eq!(
+            clock.remove_observer(1).unwrap().result(),
+            Some(Value::from(2))
+        );
+        assert!(clock.remove_observer(2).is_none());
+        root.run().unwrap();
+        assert_eq!(
+            root.results()["root/clock"],
+            vec![Value::from(1), Value::from(3)]
+        );
+    }
+
+    #[test]
+    fn test_global_observer() {
+        struct Calls(Rc<RefCell<Vec<String>>>);
+
+        impl GlobalObserver for Calls {
+            fn on_simulation_start(&mut self, init_time: Time) {
+                self.0.borrow_mut().push(format!("start {}", init_time));
+            }
+
+            fn on_simulation_end(
+                &mut self,
+                sim_time: Time,
+                results: &BTreeMap<String, Vec<Value>>,
+            ) {
+                self.0
+                    .borrow_mut()
+                    .push(format!("end {} {:?}", sim_time, results.keys()));
+            }
+        }
+
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let children: Vec<(&str, Box<dyn Dynamic + Send>)> = vec![
+            ("clock", Box::new(Clock::new())),
+            ("stopper", Box::new(Stopper::new())),
+        ];
+        let mut root = build_root_observed(children, Time::Value(100), &PathBuf::new(), &|| {
+            vec![Box::new(Metrics::new())]
+        });
+        root.add_global_observer(Box::new(Calls(calls.clone())));
+        // `build_root_observed` returns an initialized root, initialize it again
+        // now that the global observer is registered.
+        root.init();
+        root.run().unwrap();
+        assert_eq!(
+            *calls.borrow(),
+            vec![
+                "start 0".to_owned(),
+                "end 5 [\"root/clock\", \"root/stopper\"]".to_owned()
+            ]
+        );
+    }
+
+    /// Sends its event count on `out` at times 1, 2 and 3.
+    struct Ticker {
+        ticks: i64,
+    }
+
+    impl Dynamic for Ticker {
+        fn new() -> Self {
+            Ticker { ticks: 0 }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "ticker".to_owned()
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
+            self.ticks += 1;
+        }
+
+        fn output(&self, _: &Structure, _: Time) -> Bag {
+            vec![Msg::new("out", Value::from(self.ticks))]
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            if self.ticks < 3 {
+                Time::Value(1)
+            } else {
+                Time::Inf
+            }
+        }
+
+        fn state(&self) -> Value {
+            Value::from(self.ticks)
+        }
+    }
+
+    /// Records its initialization time and the time and elapsed time of every
+    /// message it receives.
+    struct Elapsed {
+        init_time: Time,
+        received: Vec<(Time, Time)>,
+    }
+
+    impl Dynamic for Elapsed {
+        fn new() -> Self {
+            Elapsed {
+                init_time: Time::Inf,
+                received: Vec::new(),
+            }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "elapsed".to_owned()
+        }
+
+        fn init(
+            &mut self,
+            _: &mut Structure,
+            init_time: Time,
+            _: &Value,
+            _: &Resources,
+            _: &mut SimRng,
+        ) {
+            self.init_time = init_time;
+        }
+
+        fn external_transition(
+            &mut self,
+            _: &Structure,
+            sim_time: Time,
+            elapsed: Time,
+            _: &Bag,
+            _: &mut SimRng,
+        ) {
+            self.received.push((sim_time, elapsed));
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Inf
+        }
+
+        fn state(&self) -> Value {
+            let received: Vec<Value> = self
+                .received
+                .iter()
+                .map(|(sim_time, elapsed)| {
+                    Value::Array(vec![Value::from(sim_time), Value::from(elapsed)])
+                })
+                .collect();
+            serde_json::json!({"init_time": Value::from(&self.init_time), "received": received})
+        }
+    }
+
+    #[test]
+    fn test_negative_init_time() {
+        let atomic = |full_name: &str, dynamic: Box<dyn Dynamic>| {
+            let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+            Simulator::new(
+                full_name,
+                Model::new(structure, dynamic),
+                Resources::default(),
+            )
+        };
+        let mut submodels = BTreeMap::new();
+        submodels.insert(
+            "ticker".to_owned(),
+            atomic("root/ticker", Box::new(Ticker::new())),
+        );
+        submodels.insert(
+            "elapsed".to_owned(),
+            atomic("root/elapsed", Box::new(Elapsed::new())),
+        );
+        let structure = Structure::new(
+            &[],
+            &[],
+            submodels,
+            &[],
+            &[("ticker", "out", "elapsed", "in")],
+            &[],
+        );
+        let root = Simulator::new(
+            "root",
+            Model::new(structure, Box::new(Passive)),
+            Resources::default(),
+        );
+        let init_time = Time::try_from("-1000").unwrap();
+        let mut root = RootSimulator::new_with_seed(root, init_time, Time::Value(-500), 0, 0);
+        root.init();
+        assert_eq!(root.current_time(), Time::Value(-999));
+        // The ticker is done before the finish time.
+        assert_eq!(root.run().unwrap(), TerminationReason::Quiescent);
+
+        assert_eq!(
+            child_state(&root, "elapsed"),
+            serde_json::json!({
+                "init_time": -1000,
+                "received": [[-999, 1], [-998, 1], [-997, 1]],
+            })
+        );
+        assert_eq!(root.event_count(), 3);
+    }
+
+    struct Routes(Rc<RefCell<Vec<String>>>);
+
+    impl Observer for Routes {
+        fn new() -> Self {
+            Routes(Rc::default())
+        }
+
+        fn on_route(&mut self, _: &Model, sim_time: Time, coupling: &Coupling, value: &Value) {
+            self.0.borrow_mut().push(format!(
+                "{} {:?}.{} -> {:?}.{} = {}",
+                sim_time,
+                coupling.source_model,
+                coupling.source_port,
+                coupling.destination_model,
+                coupling.destination_port,
+                value
+            ));
+        }
+
+        fn on_port_type_mismatch(
+            &mut self,
+            _: &Model,
+            sim_time: Time,
+            coupling: &Coupling,
+            value: &Value,
+            expected: PortType,
+        ) {
+            self.0.borrow_mut().push(format!(
+                "{} {:?}.{} expects {:?}, got {}",
+                sim_time, coupling.destination_model, coupling.destination_port, expected, value
+            ));
+        }
+    }
+
+    /// Runs a `Ticker` `a` coupled to a passive `b` whose `in` port has type
+    /// `port_type`; returns the calls received by a `Routes` observer of the root.
+    fn run_routed(port_type: PortType) -> Vec<String> {
+        let typed = |dynamic: Box<dyn Dynamic>| {
+            Model::new(atomic_structure(&["in"]), dynamic).with_input_port_typed("in", port_type)
+        };
+        let submodels = vec![
+            ("a", typed(Box::new(Ticker::new()))),
+            ("b", typed(Box::new(Passive))),
+        ];
+        let model = root_model(submodels, &[("a", "out", "b", "in")], Box::new(Passive));
+        let routes = Rc::new(RefCell::new(Vec::new()));
+        let simulator = Simulator::new("root", model, Resources::default())
+            .with_observer(Box::new(Routes(routes.clone())));
+        let mut root = init_root(simulator, Time::Value(10), Path::new(""));
+        root.run().unwrap();
+        let routes = routes.borrow().clone();
+        routes
+    }
+
+    #[test]
+    fn test_on_route() {
+        assert_eq!(
+            run_routed(PortType::Any),
+            vec![
+                "1 Some(\"a\").out -> Some(\"b\").in = 0",
+                "2 Some(\"a\").out -> Some(\"b\").in = 1",
+                "3 Some(\"a\").out -> Some(\"b\").in = 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_port_type_mismatch() {
+        assert_eq!(run_routed(PortType::Number).len(), 3);
+        let routes = run_routed(PortType::String);
+        assert_eq!(routes.len(), 6);
+        assert_eq!(routes[1], "1 Some(\"b\").in expects String, got 0");
+    }
+
+    /// Remembers the ports of the last bag it received.
+    struct Receiver {
+        ports: Vec<String>,
+    }
+
+    impl Dynamic for Receiver {
+        fn new() -> Self {
+            Receiver { ports: Vec::new() }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "receiver".to_owned()
+        }
+
+        fn external_transition(
+            &mut self,
+            _: &Structure,
+            _: Time,
+            _: Time,
+            x_bag: &Bag,
+            _: &mut SimRng,
+        ) {
+            self.ports = x_bag.iter().map(|msg| msg.port().to_owned()).collect();
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Inf
+        }
+
+        fn state(&self) -> Value {
+            Value::from(self.ports.clone())
+        }
+    }
+
+    fn received_ports(sort_x_bags: bool) -> Value {
+        let submodels = vec![
+            ("a", Box::new(Ticker::new()) as Box<dyn Dynamic>),
+            ("b", Box::new(Receiver::new())),
+            ("c", Box::new(Ticker::new())),
+        ]
+        .into_iter()
+        .map(|(name, dynamic)| {
+            (
+                name,
+                Model::new(atomic_structure(&["in_a", "in_z"]), dynamic),
+            )
+        })
+        .collect();
+        let couplings = [("a", "out", "b", "in_z"), ("c", "out", "b", "in_a")];
+        let model =
+            root_model(submodels, &couplings, Box::new(Passive)).with_sorted_x_bags(sort_x_bags);
+        let simulator = Simulator::new("root", model, Resources::default());
+        let mut root = init_root(simulator, Time::Value(10), Path::new(""));
+        root.run().unwrap();
+        child_state(&root, "b")
+    }
+
+    fn run_with_capacity(capacity: usize) -> Result<TerminationReason, ExdsdevsError> {
+        let submodels = vec![
+            ("a", Box::new(Ticker::new()) as Box<dyn Dynamic>),
+            ("b", Box::new(Receiver::new())),
+            ("c", Box::new(Ticker::new())),
+        ]
+        .into_iter()
+        .map(|(name, dynamic)| {
+            let model =
+                Model::new(atomic_structure(&[]), dynamic).with_input_port_capacity("in", capacity);
+            (name, model)
+        })
+        .collect();
+        let couplings = [("a", "out", "b", "in"), ("c", "out", "b", "in")];
+        let model = root_model(submodels, &couplings, Box::new(Passive));
+        let simulator = Simulator::new("root", model, Resources::default());
+        init_root(simulator, Time::Value(10), Path::new("")).run()
+    }
+
+    #[test]
+    fn test_port_capacity() {
+        assert!(run_with_capacity(2).is_ok());
+        match run_with_capacity(1) {
+            Err(ExdsdevsError::ErrorSimTime(message)) => assert_eq!(
+                message,
+                "input port 'in' of model 'root/b' received 2 messages at time 1, its capacity is 1"
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_sorted_x_bags() {
+        assert_eq!(received_ports(false), serde_json::json!(["in_z", "in_a"]));
+        assert_eq!(received_ports(true), serde_json::json!(["in_a", "in_z"]));
+    }
+
+    /// Coupled model recording the mail of its submodels as `[time, names]`;
+    /// with a delay it wakes up `delay` after the last mail.
+    struct MailRecorder {
+        delay: Option<i128>,
+        woken: bool,
+        mails: Vec<Value>,
+        internal: Vec<Value>,
+    }
+
+    impl MailRecorder {
+        fn with_delay(delay: Option<i128>) -> Self {
+            MailRecorder {
+                delay,
+                woken: false,
+                mails: Vec::new(),
+                internal: Vec::new(),
+            }
+        }
+    }
+
+    impl Dynamic for MailRecorder {
+        fn new() -> Self {
+            MailRecorder::with_delay(None)
+        }
+
+        fn dynamic_type(&self) -> String {
+            "mail_recorder".to_owned()
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, sim_time: Time, _: &mut SimRng) {
+            self.woken = false;
+            self.internal.push(Value::from(&sim_time));
+        }
+
+        fn external_mail_transition(
+            &mut self,
+            _: &mut Structure,
+            sim_time: Time,
+            _: Time,
+            mail: &Mail,
+            _: &mut SimRng,
+        ) {
+            let names: Vec<&str> = mail.iter().map(|item| item.model_name.as_str()).collect();
+            self.mails
+                .push(serde_json::json!([Value::from(&sim_time), names]));
+            self.woken = true;
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            match self.delay {
+                Some(delay) if self.woken => Time::Value(delay),
+                _ => Time::Inf,
+            }
+        }
+
+        fn state(&self) -> Value {
+            serde_json::json!({"mails": self.mails, "internal": self.internal})
+        }
+    }
+
+    /// `root` (`MailRecorder`) > `inner` (`MailRecorder` with delay 10) >
+    /// `ticker` (`Ticker`), run until 20.
+    fn nested_mail_root() -> RootSimulator {
+        let ticker = Simulator::new(
+            "root/inner/ticker",
+            Model::new(
+                Structure::new(&[], &["out"], BTreeMap::new(), &[], &[], &[]),
+                Box::new(Ticker::new()),
+            ),
+            Resources::default(),
+        );
+        let mut submodels = BTreeMap::new();
+        submodels.insert("ticker".to_owned(), ticker);
+        let inner = Simulator::new(
+            "root/inner",
+            Model::new(
+                Structure::new(
+                    &[],
+                    &["out"],
+                    submodels,
+                    &[],
+                    &[],
+                    &[("ticker", "out", "out")],
+                ),
+                Box::new(MailRecorder::with_delay(Some(10))),
+            ),
+            Resources::default(),
+        );
+        let mut submodels = BTreeMap::new();
+        submodels.insert("inner".to_owned(), inner);
+        let root = Simulator::new(
+            "root",
+            Model::new(
+                Structure::new(&[], &[], submodels, &[], &[], &[]),
+                Box::new(MailRecorder::new()),
+            ),
+            Resources::default(),
+        );
+        let mut root = RootSimulator::new_with_seed(root, Time::Value(0), Time::Value(20), 0, 0);
+        root.init();
+        root
+    }
+
+    #[test]
+    fn test_mail_transition_in_nested_coupled_model() {
+        let mut root = nested_mail_root();
+        root.run().unwrap();
+
+        let snapshot = root.snapshot();
+        let inner = &snapshot.find("root/inner").unwrap().state;
+        assert_eq!(
+            inner["mails"],
+            serde_json::json!([[1, ["ticker"]], [2, ["ticker"]], [3, ["ticker"]]])
+        );
+        // Woken by the last mail, not by a t_next left over from the first one.
+        assert_eq!(inner["internal"], serde_json::json!([13]));
+        let root_state = &snapshot.find("root").unwrap().state;
+        assert_eq!(
+            root_state["mails"],
+            serde_json::json!([
+                [1, ["inner"]],
+                [2, ["inner"]],
+                [3, ["inner"]],
+                [13, ["inner"]]
+            ])
+        );
+    }
+
+    #[test]
+    fn test_bad_synchronization() {
+        let mut root = nested_mail_root();
+        match root.simulator.collect_outputs(Time::Value(5)) {
+            Err(ExdsdevsError::ErrorSimTime(msg)) => assert!(msg.starts_with(
+                "bad synchronization of 'root' collecting outputs at 5: its next event is at 1"
+            )),
+            other => panic!("unexpected result {:?}", other),
+        }
+        match root
+            .simulator
+            .process_x_messages(Time::Value(-1), Bag::new())
+        {
+            Err(ExdsdevsError::ErrorSimTime(msg)) => assert!(msg.starts_with(
+                "bad synchronization of 'root' processing inputs at -1: its last event was at 0"
+            )),
+            other => panic!("unexpected result {:?}", other),
+        }
+        // The failed calls changed nothing.
+        root.run().unwrap();
+        let snapshot = root.snapshot();
+        assert_eq!(
+            snapshot.find("root/inner").unwrap().state["mails"],
+            serde_json::json!([[1, ["ticker"]], [2, ["ticker"]], [3, ["ticker"]]])
+        );
+    }
+
+    #[test]
+    fn test_mail_does_not_carry_over() {
+        let mut root = nested_mail_root();
+        // Outputs computed without the transitions that follow them.
+        root.collect_outputs().unwrap();
+        root.collect_outputs().unwrap();
+        while root.step_once() < Time::Value(20) {}
+
+        let snapshot = root.snapshot();
+        let inner = &snapshot.find("root/inner").unwrap().state;
+        assert_eq!(
+            inner["mails"],
+            serde_json::json!([[1, ["ticker"]], [2, ["ticker"]], [3, ["ticker"]]])
+        );
+        let root_state = &snapshot.find("root").unwrap().state;
+        assert_eq!(root_state["mails"].as_array().unwrap().len(), 4);
+        for mail in root_state["mails"].as_array().unwrap() {
+            assert_eq!(mail[1], serde_json::json!(["inner"]));
+        }
+    }
+
+    /// Records `(t_last, sim_time)` before every internal transition.
+    struct Dwell(Rc<RefCell<Vec<(Time, Time)>>>);
+
+    impl Observer for Dwell {
+        fn new() -> Self {
+            Dwell(Rc::default())
+        }
+
+        fn before_internal_transition(&mut self, _: &Model, sim_time: Time, t_last: Time) {
+            self.0.borrow_mut().push((t_last, sim_time));
+        }
+    }
+
+    #[test]
+    fn test_t_last_in_hooks() {
+        let dwells = Rc::new(RefCell::new(Vec::new()));
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(30),
+            &PathBuf::new(),
+            &|| vec![Box::new(Dwell(dwells.clone()))],
+        );
+        root.run().unwrap();
+
+        let dwells = dwells.borrow();
+        let events = child_state(&root, "clock")["events"].clone();
+        let events: Vec<Time> = events
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|time| Time::try_from(time).unwrap())
+            .collect();
+        assert_eq!(dwells.len(), events.len());
+        assert_eq!(dwells[0].0, Time::Value(0));
+        for (dwell, event) in dwells.iter().zip(&events) {
+            assert_eq!(dwell.1, *event);
+        }
+        for pair in dwells.windows(2) {
+            assert_eq!(pair[1].0, pair[0].1);
+        }
+    }
+
+    struct Imminent(Rc<RefCell<Vec<Vec<String>>>>);
+
+    impl Observer for Imminent {
+        fn new() -> Self {
+            Imminent(Rc::default())
+        }
+
+        fn on_imminent(&mut self, _: &Model, _: Time, imminent: &[String]) {
+            self.0.borrow_mut().push(imminent.to_vec());
+        }
+    }
+
+    /// Runs tickers `a`, `b`, `c` and `d` with the given priorities under a
+    /// root built by `build`; returns the orders reported by `on_imminent`.
+    fn imminent_orders(priorities: [i32; 4], build: fn(Model) -> Model) -> Vec<Vec<String>> {
+        let submodels = ["a", "b", "c", "d"]
+            .iter()
+            .zip(priorities)
+            .map(|(&name, priority)| {
+                let model = Model::new(atomic_structure(&[]), Box::new(Ticker::new()));
+                (name, model.with_priority(priority))
+            })
+            .collect();
+        let imminent = Rc::new(RefCell::new(Vec::new()));
+        let model = build(root_model(submodels, &[], Box::new(Passive)));
+        let simulator = Simulator::new("root", model, Resources::default())
+            .with_observer(Box::new(Imminent(imminent.clone())));
+        let mut root = init_root(simulator, Time::Value(10), Path::new(""));
+        root.run().unwrap();
+        for name in ["a", "b", "c", "d"] {
+            assert_eq!(child_state(&root, name), Value::from(3));
+        }
+        let imminent = imminent.borrow().clone();
+        imminent
+    }
+
+    #[test]
+    fn test_priority() {
+        let orders = imminent_orders([0, 5, -1, 0], |model| model);
+        assert_eq!(orders.len(), 3);
+        for order in orders {
+            assert_eq!(order, vec!["b", "a", "d", "c"]);
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let orders = imminent_orders([0, 5, -1, 0], |model| {
+            model.with_select(|names| names.last().unwrap().clone())
+        });
+        let expected: Vec<Vec<String>> = ["c", "d", "a", "b"]
+            .iter()
+            .cycle()
+            .take(12)
+            .map(|name| vec![name.to_string()])
+            .collect();
+        assert_eq!(orders, expected);
+    }
+
+    #[test]
+    fn test_select_not_imminent() {
+        let mut root = build_root(
+            vec![
+                ("a", Box::new(Ticker::new())),
+                ("b", Box::new(Ticker::new())),
+            ],
+            Time::Value(10),
+        );
+        root.simulator.model.structure.select = Some(|_| "c".to_owned());
+        match root.try_step_once() {
+            Err(ExdsdevsError::ErrorModelValidation(msg)) => {
+                assert!(msg.contains("select function of 'root' returned 'c'"))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(matches!(
+            root.run(),
+            Err(ExdsdevsError::ErrorModelValidation(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut root = build_root(
+            vec![
+                ("clock", Box::new(Clock::new())),
+                ("idle", Box::new(Passive)),
+            ],
+            Time::Value(100),
+        );
+        root.run_until(Time::Value(10)).unwrap();
+        let snapshot = root.snapshot();
+        assert_eq!(snapshot.full_name, "root");
+        assert_eq!(snapshot.t_next, root.current_time());
+        assert_eq!(snapshot.submodels.len(), 2);
+        let clock = snapshot.find("root/clock").unwrap();
+        assert_eq!(clock.state, child_state(&root, "clock"));
+        assert_eq!(clock.t_next, root.current_time());
+        assert_eq!(snapshot.find("root/idle").unwrap().t_next, Time::Inf);
+        assert!(snapshot.find("root/missing").is_none());
+    }
+
+    #[test]
+    fn test_find_model() {
+        let root = nested_mail_root();
+        assert_eq!(root.find_model("root").unwrap().full_name, "root");
+        let ticker = root.find_model("root/inner/ticker").unwrap();
+        assert_eq!(ticker.full_name, "root/inner/ticker");
+        assert_eq!(ticker.model.state(), Ticker::new().state());
+        assert_eq!(
+            root.find_model("root/inner").unwrap().model.dynamic_type(),
+            "mail_recorder"
+        );
+        for missing in [
+            "",
+            "roo",
+            "root/",
+            "root/inn",
+            "root/inner/ticker/x",
+            "other/inner",
+        ] {
+            assert!(root.find_model(missing).is_none(), "{}", missing);
+        }
+    }
+
+    #[test]
+    fn test_no_events() {
+        struct Warnings(Rc<RefCell<Vec<Time>>>);
+
+        impl GlobalObserver for Warnings {
+            fn on_nothing_scheduled(&mut self, init_time: Time) {
+                self.0.borrow_mut().push(init_time);
+            }
+        }
+
+        let warnings = Rc::new(RefCell::new(Vec::new()));
+        let mut root = build_root(vec![("idle", Box::new(Passive))], Time::Value(100))
+            .with_global_observer(Box::new(Warnings(warnings.clone())));
+        root.init();
+        assert!(root.never_scheduled());
+        assert_eq!(*warnings.borrow(), vec![Time::Value(0)]);
+        // A warning only, the run still ends at once.
+        assert_eq!(root.run().unwrap(), TerminationReason::Quiescent);
+
+        root.set_allow_no_events(true);
+        root.init();
+        assert!(!root.never_scheduled());
+        assert_eq!(warnings.borrow().len(), 1);
+
+        // Models that become passive later are fine.
+        let mut root = build_root(
+            vec![("stopper", Box::new(Stopper::new()))],
+            Time::Value(100),
+        );
+        assert!(!root.never_scheduled());
+        root.run().unwrap();
+        assert!(!root.never_scheduled());
+    }
+
+    #[test]
+    fn test_event_count() {
+        let mut root = build_root(vec![("clock", Box::new(Clock::new()))], Time::Value(30));
+        assert_eq!(root.event_count(), 0);
+        root.step_once();
+        root.step_once();
+        assert_eq!(root.event_count(), 2);
+        root.run().unwrap();
+        let events = child_state(&root, "clock")["events"].clone();
+        assert_eq!(root.event_count(), events.as_array().unwrap().len() as u64);
+
+        root.reset(0).unwrap();
+        assert_eq!(root.event_count(), 0);
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut root = build_root(vec![("clock", Box::new(Clock::new()))], Time::Value(100));
+        // The time of the first event, 1 to 3.
+        assert!(root.progress().unwrap() <= 0.03);
+        root.run_until(Time::Value(50)).unwrap();
+        let progress = root.progress().unwrap();
+        assert!((0.5..0.54).contains(&progress), "{}", progress);
+        assert_eq!(root.termination_reason(), None);
+        assert_eq!(root.run().unwrap(), TerminationReason::ReachedFinishTime);
+        assert_eq!(root.progress(), Some(1.0));
+
+        let mut root = build_root(vec![("idle", Box::new(Passive))], Time::Value(100));
+        assert_eq!(root.run().unwrap(), TerminationReason::Quiescent);
+        assert_eq!(root.current_time(), Time::Inf);
+        assert_eq!(root.progress(), Some(1.0));
+
+        let root = build_root(vec![("clock", Box::new(Clock::new()))], Time::Inf);
+        assert_eq!(root.progress(), None);
+    }
+
+    /// Coupled model adding a `clock` submodel at time 5 and removing it at 15.
+    struct Spawner {
+        now: Time,
+        next: Time,
+        changes: Vec<StructureChange>,
+    }
+
+    impl Dynamic for Spawner {
+        fn new() -> Self {
+            Spawner {
+                now: Time::Value(0),
+                next: Time::Value(5),
+                changes: Vec::new(),
+            }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "spawner".to_owned()
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, sim_time: Time, _: &mut SimRng) {
+            self.now = sim_time;
+            if sim_time == Time::Value(5) {
+                let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+                let model = Model::new(structure, Box::new(Clock::new()));
+                let simulator = Simulator::new("root/clock", model, Resources::default());
+                self.changes.push(StructureChange::AddSubmodel {
+                    name: "clock".to_owned(),
+                    simulator: Box::new(simulator),
+                });
+                self.changes
+                    .push(StructureChange::AddInternalCoupling(InternalCoupling::new(
+                        "clock", "out", "idle", "in",
+                    )));
+                self.next = Time::Value(15);
+            } else {
+                self.changes
+                    .push(StructureChange::RemoveSubmodel("clock".to_owned()));
+                self.next = Time::Inf;
+            }
+        }
+
+        fn external_transition(
+            &mut self,
+            _: &Structure,
+            sim_time: Time,
+            _: Time,
+            _: &Bag,
+            _: &mut SimRng,
+        ) {
+            self.now = sim_time;
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            self.next - self.now
+        }
+
+        fn state(&self) -> Value {
+            Value::Null
+        }
+
+        fn structure_changes(&mut self) -> Vec<StructureChange> {
+            std::mem::take(&mut self.changes)
+        }
+    }
+
+    #[test]
+    fn test_structure_changes() {
+        let mut submodels = BTreeMap::new();
+        let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+        let idle = Simulator::new(
+            "root/idle",
+            Model::new(structure, Box::new(Passive)),
+            Resources::default(),
+        );
+        submodels.insert("idle".to_owned(), idle);
+        let structure = Structure::new(&[], &[], submodels, &[], &[], &[]);
+        let simulator = Simulator::new(
+            "root",
+            Model::new(structure, Box::new(Spawner::new())),
+            Resources::default(),
+        );
+        let mut root = RootSimulator::from_simulator(simulator, Time::Value(0), Time::Value(30));
+        let init_variant = ["root", "root/idle"]
+            .iter()
+            .map(|&name| (name.to_owned(), Value::Null))
+            .collect();
+        root.init_static(&PathBuf::new(), &init_variant, 0);
+        root.init();
+
+        root.run_until(Time::Value(5)).unwrap();
+        assert!(root.snapshot().find("root/clock").is_none());
+        root.run_until(Time::Value(15)).unwrap();
+        let clock = root.snapshot().find("root/clock").unwrap().clone();
+        let events = clock.state["events"].as_array().unwrap().clone();
+        assert!(!events.is_empty());
+        assert!(events
+            .iter()
+            .all(|time| Time::try_from(time).unwrap() > Time::Value(5)));
+        assert_eq!(root.simulator.model.couplings().len(), 1);
+
+        root.run().unwrap();
+        assert!(root.snapshot().find("root/clock").is_none());
+        assert!(root.simulator.model.couplings().is_empty());
+        assert_eq!(root.current_time(), Time::Inf);
+    }
+
+    /// Coupled model making the structure changes built by `changes` at time 1.
+    struct Changer {
+        changes: fn() -> Vec<StructureChange>,
+        done: bool,
+    }
+
+    impl Dynamic for Changer {
+        fn new() -> Self {
+            Changer {
+                changes: Vec::new,
+                done: false,
+            }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "changer".to_owned()
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
+            self.done = true;
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            if self.done {
+                Time::Inf
+            } else {
+                Time::Value(1)
+            }
+        }
+
+        fn state(&self) -> Value {
+            Value::Null
+        }
+
+        fn structure_changes(&mut self) -> Vec<StructureChange> {
+            if self.done {
+                std::mem::replace(&mut self.changes, Vec::new)()
+            } else {
+                Vec::new()
+            }
+        }
+    }
+
+    /// Runs a root holding a passive `idle` submodel and making `changes`.
+    fn run_changes(
+        changes: fn() -> Vec<StructureChange>,
+    ) -> (RootSimulator, Result<(), ExdsdevsError>) {
+        let idle = Model::new(atomic_structure(&["in"]), Box::new(Passive));
+        let changer = Changer {
+            changes,
+            done: false,
+        };
+        let model = root_model(vec![("idle", idle)], &[], Box::new(changer));
+        let simulator = Simulator::new("root", model, Resources::default());
+        let mut root = init_root(simulator, Time::Value(10), Path::new(""));
+        let result = root.run().map(|_| ());
+        (root, result)
+    }
+
+    fn clock_simulator(full_name: &str) -> Box<Simulator> {
+        let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+        let model = Model::new(structure, Box::new(Clock::new()));
+        Box::new(Simulator::new(full_name, model, Resources::default()))
+    }
+
+    #[test]
+    fn test_invalid_structure_changes() {
+        let expect_error =
+            |changes: fn() -> Vec<StructureChange>, expected: &str| match run_changes(changes).1 {
+                Err(ExdsdevsError::ErrorModelValidation(msg)) => {
+                    assert!(msg.contains(expected), "{}", msg)
+                }
+                other => panic!("unexpected result {:?}", other),
+            };
+        expect_error(
+            || {
+                vec![StructureChange::AddSubmodel {
+                    name: "idle".to_owned(),
+                    simulator: clock_simulator("root/idle"),
+                }]
+            },
+            "adds submodel 'idle', which already exists",
+        );
+        expect_error(
+            || vec![StructureChange::RemoveSubmodel("ghost".to_owned())],
+            "removes unknown submodel 'ghost'",
+        );
+        expect_error(
+            || {
+                vec![StructureChange::AddInternalCoupling(InternalCoupling::new(
+                    "ghost", "out", "idle", "in",
+                ))]
+            },
+            "references unknown submodel 'ghost'",
+        );
+        expect_error(
+            || {
+                vec![StructureChange::AddExternalInputCoupling(
+                    ExternalInputCoupling::new("in", "idle", "in"),
+                )]
+            },
+            "references undeclared port 'in'",
+        );
+        expect_error(
+            || {
+                vec![StructureChange::AddInternalCoupling(InternalCoupling::new(
+                    "idle", "out", "idle", "nope",
+                ))]
+            },
+            "references undeclared port 'nope'",
+        );
+    }
+
+    #[test]
+    fn test_added_submodel_is_renamed() {
+        let (root, result) = run_changes(|| {
+            vec![StructureChange::AddSubmodel {
+                name: "clock".to_owned(),
+                simulator: clock_simulator("elsewhere"),
+            }]
+        });
+        result.unwrap();
+        assert_eq!(
+            root.find_model("root/clock").unwrap().full_name,
+            "root/clock"
+        );
+        assert!(root.find_model("elsewhere").is_none());
+    }
+
+    #[test]
+    fn test_step_once_passive() {
+        let mut root = build_root(vec![("idle", Box::new(Passive))], Time::Inf);
+        assert_eq!(root.step_once(), Time::Inf);
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("exdsdevs_checkpoint_{}.json", std::process::id()));
+        let clock = || build_root(vec![("clock", Box::new(Clock::new()))], Time::Value(60));
+        let mut original = clock();
+        original.run_until(Time::Value(20)).unwrap();
+        let saved = child_state(&original, "clock");
+        let event_count = original.event_count();
+        original.save_checkpoint(&path).unwrap();
+        original.run().unwrap();
+        // Saving does not change the run.
+        let mut unsaved = clock();
+        unsaved.run().unwrap();
+        assert_eq!(
+            child_state(&original, "clock"),
+            child_state(&unsaved, "clock")
+        );
+
+        let restore = || {
+            let mut restored = RootSimulator::load_checkpoint(&path, clock()).unwrap();
+            assert_eq!(child_state(&restored, "clock"), saved);
+            assert_eq!(restored.event_count(), event_count);
+            assert!(restored.current_time() >= Time::Value(20));
+            restored.run().unwrap();
+            restored
+        };
+        let restored = restore();
+        // The restored run makes the same random draws as the uninterrupted one.
+        assert_eq!(
+            child_state(&restored, "clock"),
+            child_state(&unsaved, "clock")
+        );
+        assert_eq!(restored.event_count(), unsaved.event_count());
+        assert_eq!(
+            child_state(&restored, "clock"),
+            child_state(&restore(), "clock")
+        );
+        std::fs::remove_file(&path).unwrap();
+        // The restored run goes on from the saved events.
+        let events = child_state(&restored, "clock")["events"].clone();
+        let events = events.as_array().unwrap();
+        let saved_events = saved["events"].as_array().unwrap();
+        assert!(events.len() > saved_events.len());
+        assert!(events.starts_with(saved_events));
+    }
+
+    #[test]
+    fn test_random_streams_do_not_depend_on_siblings() {
+        let mut alone = build_root(vec![("b", Box::new(Clock::new()))], Time::Value(60));
+        alone.run().unwrap();
+
+        let mut with_sibling = build_root(
+            vec![("a", Box::new(Clock::new())), ("b", Box::new(Clock::new()))],
+            Time::Value(60),
+        );
+        with_sibling.run().unwrap();
+
+        assert_eq!(child_state(&alone, "b"), child_state(&with_sibling, "b"));
+        assert_ne!(
+            child_state(&with_sibling, "a"),
+            child_state(&with_sibling, "b")
+        );
+    }
+
+    #[test]
+    fn test_reordered_submodels_keep_their_random_draws() {
+        let run = |priorities: [i32; 3]| {
+            let submodels = ["a", "b", "c"]
+                .iter()
+                .zip(priorities)
+                .map(|(&name, priority)| {
+                    let model = Model::new(atomic_structure(&["in"]), Box::new(Clock::new()));
+                    (name, model.with_priority(priority))
+                })
+                .collect();
+            let model = root_model(submodels, &[], Box::new(Passive));
+            let simulator = Simulator::new("root", model, Resources::default());
+            let mut root = init_root(simulator, Time::Value(60), Path::new(""));
+            root.run().unwrap();
+            ["a", "b", "c"].map(|name| child_state(&root, name)["events"].clone())
+        };
+
+        let forward = run([3, 2, 1]);
+        // The clocks have simultaneous events, whose order is reversed here.
+        assert!(forward[0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|time| forward[1].as_array().unwrap().contains(time)));
+        assert_eq!(run([1, 2, 3]), forward);
+    }
+
+    #[test]
+    fn test_reset() {
+        let run = |root: &mut RootSimulator| {
+            root.run().unwrap();
+            (child_state(root, "clock"), root.results())
+        };
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(30),
+            &PathBuf::new(),
+            &|| vec![Box::new(TrajectoryObserver::new().with_pointer("/t_next"))],
+        );
+        let first = run(&mut root);
+        assert!(!first.1.is_empty());
+        root.reset(0).unwrap();
+        assert_eq!(root.current_time(), root.simulator.t_next());
+        assert_eq!(run(&mut root), first);
+
+        root.reset(1).unwrap();
+        let other_seed = run(&mut root);
+        assert_ne!(other_seed.0, first.0);
+        root.reset(1).unwrap();
+        assert_eq!(run(&mut root), other_seed);
+
+        // The stopper does not implement `Dynamic::reset`.
+        let mut root = build_root(vec![("stopper", Box::new(Stopper::new()))], Time::Value(30));
+        root.run().unwrap();
+        let error = root.reset(0).unwrap_err();
+        assert!(matches!(error, ExdsdevsError::ErrorModelValidation(_)));
+        assert!(error.to_string().contains("'root/stopper' cannot be reset"));
+    }
+
+    #[test]
+    fn test_new_with_seed() {
+        let clocks = || {
+            let submodels = ["a", "b"]
+                .iter()
+                .map(|&name| {
+                    (
+                        name,
+                        Model::new(atomic_structure(&["in"]), Box::new(Clock::new())),
+                    )
+                })
+                .collect();
+            let model = root_model(submodels, &[], Box::new(Passive));
+            Simulator::new("root", model, Resources::default())
+        };
+        let run = |mut root: RootSimulator| {
+            root.init();
+            root.run().unwrap();
+            (child_state(&root, "a"), child_state(&root, "b"))
+        };
+
+        let seeded = RootSimulator::new_with_seed(clocks(), Time::Value(0), Time::Value(60), 3, 7);
+        assert_eq!((seeded.iteration, seeded.random_seed), (3, 7));
+        let seeded = run(seeded);
+        let init_variant = null_init_variant(&clocks());
+        let mut init_static =
+            RootSimulator::from_simulator(clocks(), Time::Value(0), Time::Value(60));
+        init_static.init_static(&PathBuf::new(), &init_variant, 7);
+        assert_eq!(seeded, run(init_static));
+        assert_ne!(
+            seeded,
+            run(RootSimulator::new_with_seed(
+                clocks(),
+                Time::Value(0),
+                Time::Value(60),
+                3,
+                8
+            ))
+        );
+    }
+
+    #[test]
+    fn test_overflowing_time_advance_is_passive() {
+        let mut root = build_root(vec![("huge", Box::new(Huge::new()))], Time::Inf);
+        assert_eq!(root.step_once(), Time::Inf);
+        root.run().unwrap();
+        assert_eq!(child_state(&root, "huge"), Value::Bool(true));
     }
 }
@@ -0,0 +1,267 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use std::convert::TryFrom;
+
+use crate::{containers::Value, model::Model, observer::Observer, time::Time};
+
+/// Records the model state at regular times `init_time`, `init_time + period`,
+/// ... up to the finish time, for plots against a uniform time axis. The state
+/// of a sample is the one in effect at its time, i.e. after the last
+/// transition at or before it. The result is the list of `[time, state]`
+/// samples; without a period nothing is recorded.
+///
+/// The finish time is the one of the run, given by `init_static`. A run that
+/// ends quiescent is sampled up to it; without it the samples stop at the last
+/// transition.
+pub struct SamplingObserver {
+    period: Option<Time>,
+    init_time: Time,
+    finish_time: Time,
+    state: Value,
+    /// Number of the next sample.
+    next_sample: u64,
+    samples: Vec<Value>,
+}
+
+impl SamplingObserver {
+    pub fn new() -> Self {
+        SamplingObserver {
+            period: None,
+            init_time: Time::Value(0),
+            finish_time: Time::Inf,
+            state: Value::Null,
+            next_sample: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    /// `period` must be a positive `Time::Value` or `Time::Real`.
+    pub fn with_period(mut self, period: Time) -> Self {
+        self.set_period(period);
+        self
+    }
+
+    fn set_period(&mut self, period: Time) {
+        assert!(
+            period.is_finite() && period > Time::Value(0),
+            "Sampling period must be positive, got {}",
+            period
+        );
+        self.period = Some(period);
+    }
+
+    fn sample_time(&self, period: Time, sample: u64) -> Time {
+        match period {
+            Time::Value(period) => self.init_time + Time::Value(period * sample as i128),
+            Time::Real(period) => self.init_time + Time::Real(period * sample as f64),
+            Time::Inf | Time::StopSim => Time::Inf,
+        }
+    }
+
+    /// Records the current state for the samples before `sim_time`, or up to
+    /// `sim_time` with `inclusive`. Nothing is recorded for an infinite
+    /// `sim_time`.
+    fn sample_until(&mut self, sim_time: Time, inclusive: bool) {
+        let period = match self.period {
+            Some(period) if sim_time.is_finite() => period,
+            _ => return,
+        };
+        loop {
+            let time = self.sample_time(period, self.next_sample);
+            if !time.is_finite() || time > sim_time || (time == sim_time && !inclusive) {
+                break;
+            }
+            self.samples
+                .push(Value::Array(vec![Value::from(&time), self.state.clone()]));
+            self.next_sample += 1;
+        }
+    }
+
+    fn transition(&mut self, model: &Model, sim_time: Time) {
+        self.sample_until(sim_time, false);
+        self.state = model.state();
+    }
+}
+
+impl Default for SamplingObserver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Observer for SamplingObserver {
+    fn new() -> Self {
+        SamplingObserver::new()
+    }
+
+    /// Reads the period from the `period` option of the model class.
+    fn config(&mut self, observer_config: &Value) {
+        if let Some(period) = observer_config.get("period") {
+            let period =
+                Time::try_from(period).unwrap_or_else(|err| panic!("Sampling period: {}", err));
+            self.set_period(period);
+        }
+    }
+
+    fn init_observer(&mut self, init_config: &Value) {
+        self.finish_time = init_config
+            .get("finish_time")
+            .and_then(|finish_time| Time::try_from(finish_time).ok())
+            .unwrap_or(Time::Inf);
+        self.samples.clear();
+    }
+
+    fn on_init(&mut self, model: &Model, init_time: Time, _init_value: &Value, _t_next: Time) {
+        self.init_time = init_time;
+        self.next_sample = 0;
+        self.state = model.state();
+    }
+
+    fn after_internal_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.transition(model, sim_time);
+    }
+
+    fn after_external_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.transition(model, sim_time);
+    }
+
+    fn after_external_mail_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.transition(model, sim_time);
+    }
+
+    fn after_confluent_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.transition(model, sim_time);
+    }
+
+    fn after_submodels_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.transition(model, sim_time);
+    }
+
+    /// A quiescent run finishes at `Time::Inf`, its samples go up to the
+    /// finish time.
+    fn after_finish(&mut self, _model: &Model, sim_time: Time) {
+        self.sample_until(sim_time.min(self.finish_time), true);
+    }
+
+    fn result(&self) -> Option<Value> {
+        Some(Value::Array(self.samples.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::containers::SimRng;
+    use crate::dynamic::Dynamic;
+    use crate::model::Structure;
+    use crate::test_utils::{build_root_observed, child_state, Clock};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_sampling() {
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(20),
+            &PathBuf::new(),
+            &|| {
+                vec![Box::new(
+                    SamplingObserver::new().with_period(Time::Value(4)),
+                )]
+            },
+        );
+        root.run().unwrap();
+
+        let events = child_state(&root, "clock")["events"].clone();
+        let events: Vec<Time> = events
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|time| Time::try_from(time).unwrap())
+            .collect();
+        let samples = root.results()["root/clock"][0].clone();
+        let samples = samples.as_array().unwrap();
+        assert_eq!(samples.len(), 6);
+        for (sample, time) in samples.iter().zip((0..=20).step_by(4)) {
+            assert_eq!(sample[0], Value::from(time));
+            // The events up to the sample time have happened.
+            let happened = events.iter().filter(|event| **event <= Time::Value(time));
+            assert_eq!(
+                sample[1]["events"].as_array().unwrap().len(),
+                happened.count()
+            );
+        }
+    }
+
+    /// Fires once at 1 and stays passive.
+    struct Once {
+        fired: bool,
+    }
+
+    impl Dynamic for Once {
+        fn new() -> Self {
+            Once { fired: false }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "once".to_owned()
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
+            self.fired = true;
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            if self.fired {
+                Time::Inf
+            } else {
+                Time::Value(1)
+            }
+        }
+
+        fn state(&self) -> Value {
+            Value::from(self.fired)
+        }
+    }
+
+    #[test]
+    fn test_quiescent_run() {
+        let mut root = build_root_observed(
+            vec![("once", Box::new(Once::new()))],
+            Time::Value(10),
+            &PathBuf::new(),
+            &|| {
+                vec![Box::new(
+                    SamplingObserver::new().with_period(Time::Value(1)),
+                )]
+            },
+        );
+        root.run().unwrap();
+        assert!(root.current_time().is_inf());
+
+        let samples = root.results()["root/once"][0].clone();
+        let expected: Vec<Value> = (0..=10)
+            .map(|time| serde_json::json!([time, time >= 1]))
+            .collect();
+        assert_eq!(samples, Value::Array(expected));
+    }
+
+    #[test]
+    fn test_config() {
+        let mut observer = SamplingObserver::new();
+        Observer::config(&mut observer, &serde_json::json!({"period": 0.5}));
+        assert_eq!(observer.period, Some(Time::Real(0.5)));
+        assert_eq!(observer.sample_time(Time::Real(0.5), 3), Time::Real(1.5));
+    }
+
+    #[test]
+    #[should_panic(expected = "Sampling period must be positive")]
+    fn test_zero_period() {
+        SamplingObserver::new().with_period(Time::Value(0));
+    }
+}
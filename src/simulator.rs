@@ -7,53 +7,201 @@
 // except according to those terms
 
 use std::{
-    cell::RefCell,
-    collections::{BTreeMap, HashSet},
+    any::Any,
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    iter::once,
+    panic::{self, AssertUnwindSafe},
     path::PathBuf,
-    rc::Rc,
+    sync::mpsc,
 };
 
-use rand::rngs::StdRng;
-use rand::SeedableRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use serde_json::Map;
+use threadpool::ThreadPool;
 
 use crate::{
-    containers::{Bag, Mail, MailItem, Msg, Value},
-    model::{ExternalInputCoupling, InternalCoupling, Model, Resources},
-    observer::Observer,
+    containers::{Bag, Mail, MailItem, Msg, SimRng, Value},
+    dynamic::Dynamic,
+    errors::ExdsdevsError,
+    model::{
+        Coupling, CouplingKind, DynamicBox, ExternalInputCoupling, ExternalOutputCoupling,
+        InternalCoupling, Model, PortType, Resources, Structure, StructureChange,
+    },
+    observer::{Observer, ObserverBox},
     time::Time,
+    utils::splitmix64,
 };
 
+/// Derives the seed of a model's random stream from the experiment seed and the
+/// model full name, so the stream does not depend on sibling models.
+fn model_seed(random_seed: u64, model_full_name: &str) -> u64 {
+    // FNV-1a keeps the name hash stable across platforms and Rust versions.
+    let name_hash = model_full_name
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    splitmix64(random_seed ^ name_hash)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SimulatorCheckpoint {
+    state: Value,
+    rng: SimRng,
+    t_last: Value,
+    t_next_self: Value,
+    t_next: Value,
+    /// Pending reaction as `[port, value]` pairs and postponed `t_next_self`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reaction: Option<(Vec<(String, Value)>, Value)>,
+}
+
+/// State of a model and of its submodels at some point of a run.
+#[derive(Debug, Clone)]
+pub struct StateSnapshot {
+    pub full_name: String,
+    pub state: Value,
+    pub t_next: Time,
+    pub submodels: Vec<StateSnapshot>,
+}
+
+impl StateSnapshot {
+    /// Looks up the snapshot of the model `full_name` in this tree.
+    pub fn find(&self, full_name: &str) -> Option<&StateSnapshot> {
+        if self.full_name == full_name {
+            Some(self)
+        } else {
+            self.submodels
+                .iter()
+                .find_map(|submodel| submodel.find(full_name))
+        }
+    }
+}
+
+/// The message of a panic payload, when it is a string.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_owned())
+}
+
+/// A simulator tree moved to a worker thread, see `Simulator::map_submodels`.
+/// It holds the parts of the simulators with their dynamics and observers as
+/// `Send` boxes, so the compiler checks that the whole tree may be sent.
+struct SendSimulator {
+    simulator: SimulatorParts,
+    structure: StructureParts,
+    sub_simulators: BTreeMap<String, SendSimulator>,
+    dynamic: Box<dyn Dynamic + Send>,
+    observers: Vec<Box<dyn Observer + Send>>,
+}
+
+/// The fields of a `Simulator` besides its model and observers.
+struct SimulatorParts {
+    full_name: String,
+    init_value: Value,
+    rng: SimRng,
+    imminent: BTreeSet<String>,
+    mail: Mail,
+    t_last: Time,
+    t_next_self: Time,
+    t_next: Time,
+    sim_dir: PathBuf,
+    iteration: u64,
+    finish_time: Time,
+    initial_state: Option<Value>,
+    reaction: Option<(Bag, Time)>,
+    pool: Option<ThreadPool>,
+}
+
+/// The fields of a `Structure` besides its submodels.
+struct StructureParts {
+    input_ports: Vec<String>,
+    input_port_types: BTreeMap<String, PortType>,
+    input_port_capacities: BTreeMap<String, usize>,
+    output_ports: Vec<String>,
+    external_input_couplings: Vec<ExternalInputCoupling>,
+    internal_couplings: Vec<InternalCoupling>,
+    external_output_couplings: Vec<ExternalOutputCoupling>,
+    sort_x_bags: bool,
+    restore_from_init: bool,
+    resources: Resources,
+    priority: i32,
+    select: Option<fn(&[String]) -> String>,
+}
+
+impl SendSimulator {
+    fn into_simulator(self) -> Simulator {
+        let sub_simulators = self
+            .sub_simulators
+            .into_iter()
+            .map(|(name, sub_simulator)| (name, sub_simulator.into_simulator()))
+            .collect();
+        Simulator::from_parts(
+            self.simulator,
+            self.structure,
+            sub_simulators,
+            DynamicBox::Send(self.dynamic),
+            self.observers.into_iter().map(ObserverBox::Send).collect(),
+        )
+    }
+}
+
 pub struct Simulator {
     pub full_name: String,
     pub model: Model,
     pub init_value: Value,
-    pub rng: Rc<RefCell<StdRng>>,
-    pub resources: Resources,
-    pub imminent: HashSet<String>,
+    /// Random stream of the model alone, seeded from the run seed and
+    /// `full_name`.
+    pub rng: SimRng,
+    /// Submodels whose outputs were collected at the current event, kept in
+    /// name order so that iterating them is reproducible.
+    pub imminent: BTreeSet<String>,
     pub mail: Mail,
     pub t_last: Time,
     pub t_next_self: Time,
     pub t_next: Time,
     pub sim_dir: PathBuf,
-    pub observers: Vec<Box<dyn Observer>>,
+    pub iteration: u64,
+    pub(crate) observers: Vec<ObserverBox>,
+    /// End of the run, given to the observers as `finish_time`.
+    finish_time: Time,
+    /// State of the dynamic before its first `init`, restored by `reset`.
+    initial_state: Option<Value>,
+    /// Outputs of `Dynamic::output_with_input` emitted at `t_next_self`, and
+    /// the time of the internal event they postponed.
+    reaction: Option<(Bag, Time)>,
+    /// Pool advancing the submodels concurrently, see
+    /// `RootSimulator::run_parallel`.
+    pool: Option<ThreadPool>,
 }
 
 impl Simulator {
-    pub fn new(full_name: &str, model: Model, resources: Resources) -> Self {
+    /// `resources` fill in the ones the model does not set itself, see
+    /// `Model::with_resources`.
+    pub fn new(full_name: &str, mut model: Model, resources: Resources) -> Self {
+        model.structure.resources = std::mem::take(&mut model.structure.resources).or(&resources);
         Simulator {
             full_name: full_name.to_owned(),
             model,
             init_value: Value::Null,
-            rng: Rc::new(RefCell::new(StdRng::seed_from_u64(0))),
-            resources,
+            rng: SimRng::seed_from_u64(0),
             imminent: Default::default(),
             mail: Default::default(),
             t_last: Time::Value(0),
             t_next_self: Time::Inf,
             t_next: Time::Inf,
             sim_dir: Default::default(),
+            iteration: 0,
             observers: Default::default(),
+            finish_time: Time::Inf,
+            initial_state: None,
+            reaction: None,
+            pool: None,
         }
     }
 
@@ -62,20 +210,33 @@ impl Simulator {
         model_full_name: &str,
         sim_dir: &PathBuf,
         init_variant: &BTreeMap<String, Value>,
-        rng: Rc<RefCell<StdRng>>,
+        random_seed: u64,
+        iteration: u64,
     ) {
         self.sim_dir = sim_dir.to_owned();
+        self.iteration = iteration;
         self.init_value = init_variant.get(model_full_name).unwrap().clone();
-        self.rng = rng;
+        self.rng = SimRng::seed_from_u64(model_seed(random_seed, model_full_name));
+        let global_resources = self.model.structure.resources.global.clone();
         for (sub_simulator_name, sub_simulator) in self.model.sub_simulators() {
             let sub_simulator_full_name = format!("{}/{}", model_full_name, sub_simulator_name);
+            sub_simulator
+                .model
+                .structure
+                .resources
+                .inherit_global(&global_resources);
             sub_simulator.init_static(
                 &sub_simulator_full_name,
                 sim_dir,
                 init_variant,
-                self.rng.clone(),
+                random_seed,
+                iteration,
             );
         }
+        self.init_observers(model_full_name);
+    }
+
+    fn init_observers(&mut self, model_full_name: &str) {
         let mut observer_config = Value::Object(Map::new());
         observer_config.as_object_mut().unwrap().extend([
             (
@@ -84,8 +245,10 @@ impl Simulator {
             ),
             (
                 "sim_dir".to_owned(),
-                Value::String(sim_dir.to_str().unwrap().to_string()),
+                Value::String(self.sim_dir.to_str().unwrap().to_string()),
             ),
+            ("iteration".to_owned(), Value::from(self.iteration)),
+            ("finish_time".to_owned(), Value::from(&self.finish_time)),
         ]);
 
         for observer in self.observers.iter_mut() {
@@ -93,23 +256,88 @@ impl Simulator {
         }
     }
 
-    pub(crate) fn init(&mut self, init_time: Time) {
-        self.model.init(
-            init_time,
-            &self.init_value,
-            &self.resources,
-            &mut self.rng.borrow_mut(),
-        );
+    /// Sets the end of the run reported to the observers of the model and of
+    /// its submodels by `init_static`.
+    pub(crate) fn set_finish_time(&mut self, finish_time: Time) {
+        self.finish_time = finish_time;
+        for (_, sub_simulator) in self.model.sub_simulators() {
+            sub_simulator.set_finish_time(finish_time);
+        }
+    }
+
+    /// Seeds the random streams of the model and of its submodels from the
+    /// experiment seed, as `init_static` does.
+    pub(crate) fn seed(&mut self, model_full_name: &str, random_seed: u64) {
+        self.rng = SimRng::seed_from_u64(model_seed(random_seed, model_full_name));
+        for (sub_simulator_name, sub_simulator) in self.model.sub_simulators() {
+            let sub_simulator_full_name = format!("{}/{}", model_full_name, sub_simulator_name);
+            sub_simulator.seed(&sub_simulator_full_name, random_seed);
+        }
+    }
+
+    /// Puts the model and its submodels back in their state before the first
+    /// `init`, see `Dynamic::reset`, reseeds their random streams from
+    /// `random_seed` and initializes them again at `init_time`. The observers
+    /// start over as after `init_static`. Structure changes made by the
+    /// previous run are not undone.
+    ///
+    /// Fails with `ErrorModelValidation` when a dynamic of the tree cannot be
+    /// reset; the tree is then left partly reset and must be built again.
+    pub fn reset(&mut self, init_time: Time, random_seed: u64) -> Result<(), ExdsdevsError> {
+        self.rewind()?;
+        let full_name = self.full_name.clone();
+        self.seed(&full_name, random_seed);
+        self.restart_observers();
+        self.init(init_time)
+    }
+
+    /// The model part of `reset`, the random streams and the observers are
+    /// left as they are.
+    pub(crate) fn rewind(&mut self) -> Result<(), ExdsdevsError> {
+        if let Some(initial_state) = &self.initial_state {
+            self.model.reset(initial_state).map_err(|error| {
+                ExdsdevsError::ErrorModelValidation(format!(
+                    "model '{}' cannot be reset: {}",
+                    self.full_name, error
+                ))
+            })?;
+        }
+        self.mail.clear();
+        self.imminent.clear();
+        self.reaction = None;
+        for (_, sub_simulator) in self.model.sub_simulators() {
+            sub_simulator.rewind()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn restart_observers(&mut self) {
+        for (_, sub_simulator) in self.model.sub_simulators() {
+            sub_simulator.restart_observers();
+        }
+        let full_name = self.full_name.clone();
+        self.init_observers(&full_name);
+    }
+
+    pub(crate) fn init(&mut self, init_time: Time) -> Result<(), ExdsdevsError> {
+        if self.initial_state.is_none() {
+            self.initial_state = Some(self.model.state());
+        }
+        self.model
+            .init(init_time, &self.init_value, &mut self.rng)?;
 
         self.t_last = init_time;
-        self.t_next_self = self.t_last + self.model.time_advance(&mut self.rng.borrow_mut());
+        self.t_next_self = self.next_self_time();
+        self.reaction = None;
         self.t_next = self
             .model
             .sub_simulators()
             .map(|(_, sub_simulator)| {
-                sub_simulator.init(init_time);
-                sub_simulator.t_next()
+                sub_simulator.init(init_time)?;
+                Ok(sub_simulator.t_next())
             })
+            .collect::<Result<Vec<Time>, ExdsdevsError>>()?
+            .into_iter()
             .min()
             .unwrap_or(Time::Inf)
             .min(self.t_next_self);
@@ -117,6 +345,7 @@ impl Simulator {
         for observer in self.observers.iter_mut() {
             observer.on_init(&self.model, init_time, &self.init_value, self.t_next)
         }
+        Ok(())
     }
 
     pub fn with_observer(mut self, observer: Box<dyn Observer>) -> Self {
@@ -124,76 +353,412 @@ impl Simulator {
         self
     }
 
+    /// Observers are kept in the order they are added, which is the order of
+    /// their results. `init_static` configures them, so they are added before
+    /// it.
     pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
-        self.observers.push(observer);
+        self.observers.push(ObserverBox::Local(observer));
+    }
+
+    pub fn with_send_observer(mut self, observer: Box<dyn Observer + Send>) -> Self {
+        self.add_send_observer(observer);
+        self
+    }
+
+    /// Same as `add_observer` with an observer that may be moved to another
+    /// thread, which `RootSimulator::run_parallel` requires.
+    pub fn add_send_observer(&mut self, observer: Box<dyn Observer + Send>) {
+        self.observers.push(ObserverBox::Send(observer));
+    }
+
+    /// Removes the observer at `index` in the order the observers were added,
+    /// the later observers move down by one.
+    pub fn remove_observer(&mut self, index: usize) -> Option<Box<dyn Observer>> {
+        if index < self.observers.len() {
+            Some(self.observers.remove(index).into_inner())
+        } else {
+            None
+        }
+    }
+
+    /// Fails when the dynamics and observers of the submodels may not be
+    /// moved to another thread, see `Model::new_send` and `add_send_observer`.
+    pub(crate) fn check_send_submodels(&mut self) -> Result<(), ExdsdevsError> {
+        let names: Vec<String> = self
+            .model
+            .structure
+            .sub_simulators
+            .keys()
+            .cloned()
+            .collect();
+        for name in names {
+            let sub_simulators = &mut self.model.structure.sub_simulators;
+            let sub_simulator = sub_simulators.remove(&name).unwrap();
+            match sub_simulator.into_send() {
+                Ok(sub_simulator) => {
+                    sub_simulators.insert(name, sub_simulator.into_simulator());
+                }
+                Err(failed) => {
+                    let (sub_simulator, error) = *failed;
+                    sub_simulators.insert(name, sub_simulator);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the tree apart into a `SendSimulator`, failing with the tree
+    /// unchanged and the full name of the first model whose dynamic or one of
+    /// whose observers may not be moved to another thread.
+    fn into_send(self) -> Result<SendSimulator, Box<(Simulator, ExdsdevsError)>> {
+        let not_send = |what: &str, full_name: &str| {
+            ExdsdevsError::ErrorBuildSimulator(format!(
+                "{} of model '{}' is not Send, it cannot run on a worker thread; \
+                 build the model with Model::new_send and add its observers with \
+                 add_send_observer",
+                what, full_name
+            ))
+        };
+        let (simulator, structure, sub_simulators, dynamic, observers) = self.into_parts();
+        let dynamic = match dynamic {
+            DynamicBox::Send(dynamic) => dynamic,
+            dynamic => {
+                let error = not_send("the dynamic", &simulator.full_name);
+                let simulator =
+                    Self::from_parts(simulator, structure, sub_simulators, dynamic, observers);
+                return Err(Box::new((simulator, error)));
+            }
+        };
+
+        let mut send_observers = Vec::new();
+        let mut observers = observers.into_iter();
+        while let Some(observer) = observers.next() {
+            match observer {
+                ObserverBox::Send(observer) => send_observers.push(observer),
+                observer => {
+                    let error = not_send("an observer", &simulator.full_name);
+                    let observers = send_observers
+                        .into_iter()
+                        .map(ObserverBox::Send)
+                        .chain(once(observer))
+                        .chain(observers)
+                        .collect();
+                    let dynamic = DynamicBox::Send(dynamic);
+                    let simulator =
+                        Self::from_parts(simulator, structure, sub_simulators, dynamic, observers);
+                    return Err(Box::new((simulator, error)));
+                }
+            }
+        }
+
+        let mut send_sub_simulators = BTreeMap::new();
+        let mut sub_simulators = sub_simulators.into_iter();
+        while let Some((name, sub_simulator)) = sub_simulators.next() {
+            match sub_simulator.into_send() {
+                Ok(sub_simulator) => {
+                    send_sub_simulators.insert(name, sub_simulator);
+                }
+                Err(failed) => {
+                    let (sub_simulator, error) = *failed;
+                    let sub_simulators = send_sub_simulators
+                        .into_iter()
+                        .map(|(name, sub_simulator)| (name, sub_simulator.into_simulator()))
+                        .chain(once((name, sub_simulator)))
+                        .chain(sub_simulators)
+                        .collect();
+                    let dynamic = DynamicBox::Send(dynamic);
+                    let observers = send_observers.into_iter().map(ObserverBox::Send).collect();
+                    let simulator =
+                        Self::from_parts(simulator, structure, sub_simulators, dynamic, observers);
+                    return Err(Box::new((simulator, error)));
+                }
+            }
+        }
+
+        Ok(SendSimulator {
+            simulator,
+            structure,
+            sub_simulators: send_sub_simulators,
+            dynamic,
+            observers: send_observers,
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn into_parts(
+        self,
+    ) -> (
+        SimulatorParts,
+        StructureParts,
+        BTreeMap<String, Simulator>,
+        DynamicBox,
+        Vec<ObserverBox>,
+    ) {
+        let Simulator {
+            full_name,
+            model,
+            init_value,
+            rng,
+            imminent,
+            mail,
+            t_last,
+            t_next_self,
+            t_next,
+            sim_dir,
+            iteration,
+            observers,
+            finish_time,
+            initial_state,
+            reaction,
+            pool,
+        } = self;
+        let (structure, dynamic) = model.into_parts();
+        let Structure {
+            input_ports,
+            input_port_types,
+            input_port_capacities,
+            output_ports,
+            sub_simulators,
+            external_input_couplings,
+            internal_couplings,
+            external_output_couplings,
+            sort_x_bags,
+            restore_from_init,
+            resources,
+            priority,
+            select,
+        } = structure;
+        let simulator = SimulatorParts {
+            full_name,
+            init_value,
+            rng,
+            imminent,
+            mail,
+            t_last,
+            t_next_self,
+            t_next,
+            sim_dir,
+            iteration,
+            finish_time,
+            initial_state,
+            reaction,
+            pool,
+        };
+        let structure = StructureParts {
+            input_ports,
+            input_port_types,
+            input_port_capacities,
+            output_ports,
+            external_input_couplings,
+            internal_couplings,
+            external_output_couplings,
+            sort_x_bags,
+            restore_from_init,
+            resources,
+            priority,
+            select,
+        };
+        (simulator, structure, sub_simulators, dynamic, observers)
+    }
+
+    fn from_parts(
+        simulator: SimulatorParts,
+        structure: StructureParts,
+        sub_simulators: BTreeMap<String, Simulator>,
+        dynamic: DynamicBox,
+        observers: Vec<ObserverBox>,
+    ) -> Self {
+        let structure = Structure {
+            input_ports: structure.input_ports,
+            input_port_types: structure.input_port_types,
+            input_port_capacities: structure.input_port_capacities,
+            output_ports: structure.output_ports,
+            sub_simulators,
+            external_input_couplings: structure.external_input_couplings,
+            internal_couplings: structure.internal_couplings,
+            external_output_couplings: structure.external_output_couplings,
+            sort_x_bags: structure.sort_x_bags,
+            restore_from_init: structure.restore_from_init,
+            resources: structure.resources,
+            priority: structure.priority,
+            select: structure.select,
+        };
+        Simulator {
+            full_name: simulator.full_name,
+            model: Model::from_parts(structure, dynamic),
+            init_value: simulator.init_value,
+            rng: simulator.rng,
+            imminent: simulator.imminent,
+            mail: simulator.mail,
+            t_last: simulator.t_last,
+            t_next_self: simulator.t_next_self,
+            t_next: simulator.t_next,
+            sim_dir: simulator.sim_dir,
+            iteration: simulator.iteration,
+            observers,
+            finish_time: simulator.finish_time,
+            initial_state: simulator.initial_state,
+            reaction: simulator.reaction,
+            pool: simulator.pool,
+        }
+    }
+
+    /// Time of the next internal event after a transition at `t_last`; a
+    /// `Time::StopSim` time advance is kept as is to stop the simulation.
+    fn next_self_time(&mut self) -> Time {
+        match self.model.time_advance(&mut self.rng) {
+            Time::StopSim => Time::StopSim,
+            time_advance => self.t_last + time_advance,
+        }
     }
 
     pub(crate) fn t_next(&self) -> Time {
         self.t_next
     }
 
-    pub(crate) fn collect_outputs(&mut self, sim_time: Time) -> Bag {
+    /// Collects the full names of this simulator and its descendants that are
+    /// scheduled at `sim_time`.
+    pub(crate) fn imminent_names(&self, sim_time: Time, names: &mut Vec<String>) {
+        if self.t_next_self == sim_time {
+            names.push(self.full_name.clone());
+        }
+        for (_, sub_simulator) in self.model.sub_simulators_iter() {
+            if sub_simulator.t_next() == sim_time {
+                sub_simulator.imminent_names(sim_time, names);
+            }
+        }
+    }
+
+    /// Fails when `sim_time` is not the time of an event of this model, which
+    /// means the simulators went out of sync.
+    pub(crate) fn collect_outputs(&mut self, sim_time: Time) -> Result<Bag, ExdsdevsError> {
+        for observer in self.observers.iter_mut() {
+            observer.before_output(&self.model, sim_time)
+        }
         let bag = if sim_time == self.t_next_self {
-            self.model.output(sim_time)
+            match &self.reaction {
+                Some((y_bag, _)) => y_bag.clone(),
+                None => self
+                    .model
+                    .output(sim_time, sim_time.elapsed_since(self.t_last)),
+            }
         } else if sim_time == self.t_next {
-            for (model_name, simulator) in self.model.sub_simulators() {
-                if simulator.t_next() == sim_time {
-                    self.imminent.insert(model_name.clone());
-                    let y_bag = simulator.collect_outputs(sim_time);
-                    self.mail.push(MailItem {
-                        model_name: model_name.clone(),
-                        y_bag,
-                    });
+            // Outputs of an earlier call that did not reach the x phase, e.g.
+            // after a failed step, must not be delivered with these ones.
+            self.mail.clear();
+            self.imminent.clear();
+            let mut imminent: Vec<String> = self
+                .model
+                .sub_simulators_iter()
+                .filter(|(_, simulator)| simulator.t_next() == sim_time)
+                .map(|(model_name, _)| model_name.clone())
+                .collect();
+            self.sort_by_priority(&mut imminent);
+            if let Some(select) = self.model.structure.select {
+                if imminent.len() > 1 {
+                    let selected = select(&imminent);
+                    if !imminent.contains(&selected) {
+                        return Err(ExdsdevsError::ErrorModelValidation(format!(
+                            "select function of '{}' returned '{}', not an imminent submodel",
+                            self.full_name, selected
+                        )));
+                    }
+                    imminent = vec![selected];
                 }
             }
+            for observer in self.observers.iter_mut() {
+                observer.on_imminent(&self.model, sim_time, &imminent)
+            }
+            let jobs = imminent
+                .into_iter()
+                .map(|model_name| (model_name, ()))
+                .collect();
+            let outputs = self.map_submodels(sim_time, jobs, |simulator, sim_time, ()| {
+                simulator.collect_outputs(sim_time)
+            })?;
+            for (model_name, y_bag) in outputs {
+                self.imminent.insert(model_name.clone());
+                self.mail.push(MailItem {
+                    model_name,
+                    y_bag: y_bag?,
+                });
+            }
             self.model.get_y_bag_from_mail(&self.mail)
         } else {
-            panic!("DEVS ERROR: Bad synchronization in Simulator.collect_outputs()")
+            return Err(ExdsdevsError::ErrorSimTime(format!(
+                "bad synchronization of '{}' collecting outputs at {}: \
+                 its next event is at {} (own event at {})",
+                self.full_name, sim_time, self.t_next, self.t_next_self
+            )));
         };
 
         for observer in self.observers.iter_mut() {
             observer.on_outputs(&self.model, sim_time, &bag)
         }
-        bag
+        Ok(bag)
     }
 
-    pub(crate) fn process_y_messages(&mut self, sim_time: Time) {
-        let elapsed = sim_time - self.t_last;
+    /// External mail transition of a coupled model with the outputs of its
+    /// imminent submodels, at every level of the hierarchy. Only `t_next_self`
+    /// is updated here, `t_next` is recomputed with the submodels once they
+    /// made their transitions.
+    fn process_y_messages(&mut self, sim_time: Time) {
+        let elapsed = sim_time.elapsed_since(self.t_last);
         self.t_last = sim_time;
         for observer in self.observers.iter_mut() {
             observer.before_external_mail_transition(&self.model, sim_time, &self.mail, elapsed)
         }
-        self.model.external_mail_transition(
-            sim_time,
-            elapsed,
-            &self.mail,
-            &mut self.rng.borrow_mut(),
-        );
-        // warning!: The following two lines of code may violate the exdevs formalism.
-        // This must be taken into account when obtaining erroneous simulation results.
-        self.t_next_self = self.t_last + self.model.time_advance(&mut self.rng.borrow_mut());
-        self.t_next = self.t_next_self.min(self.t_next);
+        self.model
+            .external_mail_transition(sim_time, elapsed, &self.mail, &mut self.rng);
+        self.t_next_self = self.next_self_time();
 
         for observer in self.observers.iter_mut() {
             observer.after_external_mail_transition(&self.model, sim_time, self.t_next_self)
         }
     }
 
-    pub(crate) fn process_x_messages(&mut self, sim_time: Time, x_bag: Bag) {
+    pub(crate) fn process_x_messages(
+        &mut self,
+        sim_time: Time,
+        x_bag: Bag,
+    ) -> Result<(), ExdsdevsError> {
+        self.process_x_messages_when(sim_time, x_bag, true)
+    }
+
+    /// A model that is not `imminent` did not compute its outputs at
+    /// `sim_time`, so it makes an external transition even when its own event
+    /// is due, see `Model::with_select`.
+    fn process_x_messages_when(
+        &mut self,
+        sim_time: Time,
+        x_bag: Bag,
+        imminent: bool,
+    ) -> Result<(), ExdsdevsError> {
+        if !self.mail.is_empty() {
+            self.process_y_messages(sim_time);
+        }
         if sim_time >= self.t_last && sim_time <= self.t_next_self {
-            let elapsed = sim_time - self.t_last;
-            self.t_last = sim_time;
+            let elapsed = sim_time.elapsed_since(self.t_last);
+            let t_last = std::mem::replace(&mut self.t_last, sim_time);
+            let reacted = imminent && sim_time == self.t_next_self && self.reaction.is_some();
+            if reacted {
+                if let Some((_, t_next_self)) = self.reaction.take() {
+                    self.t_next_self = t_next_self;
+                }
+            }
 
-            if sim_time == self.t_next_self {
+            if reacted && x_bag.is_empty() {
+                // The outputs of the reaction were sent, its event has no
+                // transition of its own.
+            } else if imminent && !reacted && sim_time == self.t_next_self {
                 // internal transition
                 if x_bag.is_empty() {
                     for observer in self.observers.iter_mut() {
-                        observer.before_internal_transition(&self.model, sim_time);
+                        observer.before_internal_transition(&self.model, sim_time, t_last);
                     }
-                    self.model
-                        .internal_transition(sim_time, &mut self.rng.borrow_mut());
-                    self.t_next_self =
-                        self.t_last + self.model.time_advance(&mut self.rng.borrow_mut());
+                    self.model.internal_transition(sim_time, &mut self.rng);
+                    self.t_next_self = self.next_self_time();
 
                     for observer in self.observers.iter_mut() {
                         observer.after_internal_transition(&self.model, sim_time, self.t_next_self);
@@ -202,13 +767,13 @@ impl Simulator {
                 // confluent transition
                 } else {
                     for observer in self.observers.iter_mut() {
-                        observer.before_confluent_transition(&self.model, sim_time, &x_bag);
+                        observer.before_confluent_transition(&self.model, sim_time, &x_bag, t_last);
                     }
 
                     self.model
-                        .confluent_transition(sim_time, &x_bag, &mut self.rng.borrow_mut());
-                    self.t_next_self =
-                        self.t_last + self.model.time_advance(&mut self.rng.borrow_mut());
+                        .confluent_transition(sim_time, elapsed, &x_bag, &mut self.rng);
+                    self.t_next_self = self.next_self_time();
+                    self.react(sim_time, &x_bag);
 
                     for observer in self.observers.iter_mut() {
                         observer.after_confluent_transition(
@@ -224,14 +789,10 @@ impl Simulator {
                 for observer in self.observers.iter_mut() {
                     observer.before_external_transition(&self.model, sim_time, &x_bag, elapsed);
                 }
-                self.model.external_transition(
-                    sim_time,
-                    elapsed,
-                    &x_bag,
-                    &mut self.rng.borrow_mut(),
-                );
-                self.t_next_self =
-                    self.t_last + self.model.time_advance(&mut self.rng.borrow_mut());
+                self.model
+                    .external_transition(sim_time, elapsed, &x_bag, &mut self.rng);
+                self.t_next_self = self.next_self_time();
+                self.react(sim_time, &x_bag);
 
                 for observer in self.observers.iter_mut() {
                     observer.after_external_transition(&self.model, sim_time, self.t_next_self);
@@ -239,7 +800,11 @@ impl Simulator {
             }
 
             if self.has_submodels() {
-                self.sent_x_bag_to_submodels(sim_time, &x_bag);
+                self.sent_x_bag_to_submodels(sim_time, &x_bag)?;
+            }
+            self.apply_structure_changes(sim_time)?;
+
+            if self.has_submodels() {
                 let submodels_t_next = self.submodels_t_next();
                 self.t_next = self.t_next_self.min(submodels_t_next);
                 for observer in self.observers.iter_mut() {
@@ -248,8 +813,28 @@ impl Simulator {
             } else {
                 self.t_next = self.t_next_self;
             }
+            Ok(())
         } else {
-            panic!("Bad synchronization in Simulator::process_x_messages()")
+            Err(ExdsdevsError::ErrorSimTime(format!(
+                "bad synchronization of '{}' processing inputs at {}: \
+                 its last event was at {} and its own next event is at {}",
+                self.full_name, sim_time, self.t_last, self.t_next_self
+            )))
+        }
+    }
+
+    /// Schedules the outputs the model gives for `x_bag` in a zero-time event
+    /// after the current one, see `Dynamic::output_with_input`. Outputs still
+    /// pending, when the model was not selected, are sent first.
+    fn react(&mut self, sim_time: Time, x_bag: &Bag) {
+        let mut y_bag = match self.reaction.take() {
+            Some((pending, _)) => pending,
+            None => Bag::new(),
+        };
+        y_bag.extend(self.model.output_with_input(sim_time, x_bag));
+        if !y_bag.is_empty() {
+            self.reaction = Some((y_bag, self.t_next_self));
+            self.t_next_self = sim_time;
         }
     }
 
@@ -261,27 +846,221 @@ impl Simulator {
             .unwrap_or(Time::Inf)
     }
 
-    fn sent_x_bag_to_submodels(&mut self, sim_time: Time, x_bag: &Bag) {
-        let x_bags_for_submodels = self.get_submodels_x_bags(x_bag);
+    /// Delivers the routed messages to the submodels, see
+    /// `Model::with_sorted_x_bags` for the order of the messages in a bag.
+    fn sent_x_bag_to_submodels(
+        &mut self,
+        sim_time: Time,
+        x_bag: &Bag,
+    ) -> Result<(), ExdsdevsError> {
+        let mut x_bags_for_submodels = self.get_submodels_x_bags(sim_time, x_bag);
+        self.check_capacities(sim_time, &x_bags_for_submodels)?;
+        if self.model.structure.sort_x_bags {
+            for x_bag in x_bags_for_submodels.values_mut() {
+                x_bag.sort_by(|left, right| left.port.cmp(&right.port));
+            }
+        }
         self.mail.clear();
 
-        let has_x_bags: HashSet<String> = x_bags_for_submodels
-            .keys()
+        let imminent = std::mem::take(&mut self.imminent);
+        let mut model_names: Vec<String> = imminent
+            .iter()
+            .chain(x_bags_for_submodels.keys())
             .cloned()
-            .collect::<HashSet<String>>();
-        for simulator_name in self.imminent.difference(&has_x_bags) {
-            let simulator = self.model.get_subsimulators(simulator_name);
-            simulator.process_x_messages(sim_time, Bag::new());
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect();
+        self.sort_by_priority(&mut model_names);
+        let classic = self.model.structure.select.is_some();
+        let jobs = model_names
+            .into_iter()
+            .map(|model_name| {
+                let tmp_x_bag = x_bags_for_submodels.remove(&model_name).unwrap_or_default();
+                let is_imminent = !classic || imminent.contains(&model_name);
+                (model_name, (tmp_x_bag, is_imminent))
+            })
+            .collect();
+        let results =
+            self.map_submodels(sim_time, jobs, |simulator, sim_time, (x_bag, imminent)| {
+                simulator.process_x_messages_when(sim_time, x_bag, imminent)
+            })?;
+        results.into_iter().try_for_each(|(_, result)| result)
+    }
+
+    /// Applies `job` to the given submodels and returns the results in the
+    /// same order. With a pool the submodels are moved to its threads: they do
+    /// not share anything during a phase of an event, their messages are only
+    /// routed by this model afterwards. Fails when a submodel may not be moved
+    /// to a thread, see `into_send`, or when it panicked in a thread; the
+    /// submodel is back in the tree in both cases. A submodel given a model
+    /// that is not `Send` in a thread, by a structure change, cannot come back
+    /// and is dropped, the run fails.
+    fn map_submodels<I, R>(
+        &mut self,
+        sim_time: Time,
+        jobs: Vec<(String, I)>,
+        job: fn(&mut Simulator, Time, I) -> R,
+    ) -> Result<Vec<(String, R)>, ExdsdevsError>
+    where
+        I: Send + 'static,
+        R: Send + 'static,
+    {
+        let pool = match &self.pool {
+            Some(pool) if jobs.len() > 1 => pool,
+            _ => {
+                return Ok(jobs
+                    .into_iter()
+                    .map(|(model_name, input)| {
+                        let result =
+                            job(self.model.get_subsimulators(&model_name), sim_time, input);
+                        (model_name, result)
+                    })
+                    .collect())
+            }
+        };
+
+        // Structure changes may have added submodels since `run_parallel`
+        // started, so every phase takes all its submodels apart before
+        // starting any of them.
+        let mut send_jobs = Vec::with_capacity(jobs.len());
+        for (model_name, input) in jobs {
+            let sub_simulators = &mut self.model.structure.sub_simulators;
+            let simulator = sub_simulators.remove(&model_name).unwrap();
+            match simulator.into_send() {
+                Ok(simulator) => send_jobs.push((model_name, simulator, input)),
+                Err(failed) => {
+                    let (simulator, error) = *failed;
+                    sub_simulators.insert(model_name, simulator);
+                    for (model_name, simulator, _) in send_jobs {
+                        sub_simulators.insert(model_name, simulator.into_simulator());
+                    }
+                    return Err(error);
+                }
+            }
         }
-        self.imminent.clear();
+        let jobs_count = send_jobs.len();
+        let (sender, receiver) = mpsc::channel();
+        for (index, (model_name, simulator, input)) in send_jobs.into_iter().enumerate() {
+            let sender = sender.clone();
+            pool.execute(move || {
+                let mut simulator = simulator.into_simulator();
+                let result =
+                    panic::catch_unwind(AssertUnwindSafe(|| job(&mut simulator, sim_time, input)))
+                        .map_err(|payload| panic_message(payload.as_ref()));
+                let simulator = simulator.into_send().map_err(|failed| failed.1);
+                sender.send((index, model_name, simulator, result)).unwrap();
+            });
+        }
+        drop(sender);
+
+        let mut results: Vec<Option<(String, Result<R, String>)>> =
+            (0..jobs_count).map(|_| None).collect();
+        let mut lost = None;
+        for (index, model_name, simulator, result) in receiver.iter() {
+            match simulator {
+                Ok(simulator) => {
+                    self.model
+                        .structure
+                        .sub_simulators
+                        .insert(model_name.clone(), simulator.into_simulator());
+                }
+                Err(error) => lost = lost.or(Some(error)),
+            }
+            results[index] = Some((model_name, result));
+        }
+        if let Some(error) = lost {
+            return Err(error);
+        }
+        results
+            .into_iter()
+            .map(|result| {
+                let (model_name, result) = result.expect("every worker sends its submodel back");
+                match result {
+                    Ok(result) => Ok((model_name, result)),
+                    Err(message) => Err(ExdsdevsError::ErrorSimTime(format!(
+                        "submodel '{}/{}' panicked in a worker thread at {}: {}",
+                        self.full_name, model_name, sim_time, message
+                    ))),
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn set_pool(&mut self, pool: Option<ThreadPool>) {
+        self.pool = pool;
+    }
 
-        for (model_name, tmp_x_bag) in x_bags_for_submodels.into_iter() {
-            let simulator = self.model.get_subsimulators(&model_name);
-            simulator.process_x_messages(sim_time, tmp_x_bag);
+    /// Fails when a bag holds more messages for a port than its capacity, see
+    /// `Model::with_input_port_capacity`.
+    fn check_capacities(
+        &self,
+        sim_time: Time,
+        x_bags_for_submodels: &BTreeMap<String, Bag>,
+    ) -> Result<(), ExdsdevsError> {
+        for (model_name, x_bag) in x_bags_for_submodels {
+            let capacities = &self
+                .model
+                .submodel(model_name)
+                .ok_or_else(|| {
+                    ExdsdevsError::ErrorModelValidation(format!(
+                        "messages routed to unknown submodel '{}' of model '{}'",
+                        model_name, self.full_name
+                    ))
+                })?
+                .structure
+                .input_port_capacities;
+            for (port, &capacity) in capacities {
+                let count = x_bag.iter().filter(|msg| &msg.port == port).count();
+                if count > capacity {
+                    return Err(ExdsdevsError::ErrorSimTime(format!(
+                        "input port '{}' of model '{}/{}' received {} messages at time {}, its capacity is {}",
+                        port, self.full_name, model_name, count, sim_time, capacity
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Stably sorts submodel names by decreasing priority.
+    fn sort_by_priority(&self, model_names: &mut [String]) {
+        model_names.sort_by_key(|model_name| {
+            std::cmp::Reverse(
+                self.model
+                    .submodel(model_name)
+                    .map_or(0, |model| model.priority()),
+            )
+        });
+    }
+
+    /// Reports a routed message to the observers and checks it against the type
+    /// of the destination port.
+    fn notify_route(
+        observers: &mut [ObserverBox],
+        model: &Model,
+        sim_time: Time,
+        coupling: &Coupling,
+        value: &Value,
+    ) {
+        if observers.is_empty() {
+            return;
+        }
+        for observer in observers.iter_mut() {
+            observer.on_route(model, sim_time, coupling, value);
+        }
+        let port_type = coupling.destination_model.and_then(|destination_model| {
+            model.submodel_input_port_type(destination_model, coupling.destination_port)
+        });
+        if let Some(port_type) = port_type {
+            if !port_type.matches(value) {
+                for observer in observers.iter_mut() {
+                    observer.on_port_type_mismatch(model, sim_time, coupling, value, port_type);
+                }
+            }
         }
     }
 
-    fn get_submodels_x_bags(&self, x_bag: &Bag) -> BTreeMap<String, Bag> {
+    fn get_submodels_x_bags(&mut self, sim_time: Time, x_bag: &Bag) -> BTreeMap<String, Bag> {
         let mut x_bags_for_submodels: BTreeMap<String, Bag> = BTreeMap::new();
         for ExternalInputCoupling {
             source_port,
@@ -291,6 +1070,20 @@ impl Simulator {
         {
             for Msg { port, value } in x_bag.iter() {
                 if source_port == port {
+                    let coupling = Coupling {
+                        kind: CouplingKind::ExternalInput,
+                        source_model: None,
+                        source_port,
+                        destination_model: Some(destination_model),
+                        destination_port: destination_model_port,
+                    };
+                    Self::notify_route(
+                        &mut self.observers,
+                        &self.model,
+                        sim_time,
+                        &coupling,
+                        value,
+                    );
                     let tmp_bag = x_bags_for_submodels
                         .entry(destination_model.clone())
                         .or_default();
@@ -313,6 +1106,20 @@ impl Simulator {
                 if source_model == model_name {
                     for Msg { port, value } in y_bag.iter() {
                         if port == source_model_port {
+                            let coupling = Coupling {
+                                kind: CouplingKind::Internal,
+                                source_model: Some(source_model),
+                                source_port: source_model_port,
+                                destination_model: Some(destination_model),
+                                destination_port: destination_model_port,
+                            };
+                            Self::notify_route(
+                                &mut self.observers,
+                                &self.model,
+                                sim_time,
+                                &coupling,
+                                value,
+                            );
                             x_bags_for_submodels
                                 .entry(destination_model.clone())
                                 .or_default()
@@ -328,10 +1135,186 @@ impl Simulator {
         x_bags_for_submodels
     }
 
+    /// Applies the structure changes requested by the dynamic. Added
+    /// submodels are statically initialized like the rest of the tree, with a
+    /// seed drawn from this simulator's generator, and initialized at `sim_time`.
+    /// Fails on the first invalid change, see `Model::check_structure_change`;
+    /// the changes before it are applied.
+    fn apply_structure_changes(&mut self, sim_time: Time) -> Result<(), ExdsdevsError> {
+        for change in self.model.structure_changes() {
+            self.model
+                .check_structure_change(&self.full_name, &change)?;
+            let change = match change {
+                StructureChange::AddSubmodel {
+                    name,
+                    mut simulator,
+                } => {
+                    let full_name = format!("{}/{}", self.full_name, name);
+                    simulator.set_full_name(&full_name);
+                    let mut init_variant = BTreeMap::new();
+                    simulator.collect_init_values(&full_name, &mut init_variant);
+                    simulator
+                        .model
+                        .structure
+                        .resources
+                        .inherit_global(&self.model.structure.resources.global);
+                    simulator.set_finish_time(self.finish_time);
+                    simulator.init_static(
+                        &full_name,
+                        &self.sim_dir,
+                        &init_variant,
+                        self.rng.gen(),
+                        self.iteration,
+                    );
+                    simulator.init(sim_time)?;
+                    StructureChange::AddSubmodel { name, simulator }
+                }
+                change => change,
+            };
+            if let Some(mut removed) = self.model.apply_structure_change(change) {
+                removed.finish(sim_time);
+            }
+        }
+        Ok(())
+    }
+
+    /// Names the model `full_name` and its submodels after it.
+    fn set_full_name(&mut self, full_name: &str) {
+        self.full_name = full_name.to_owned();
+        for (sub_simulator_name, sub_simulator) in self.model.sub_simulators() {
+            sub_simulator.set_full_name(&format!("{}/{}", full_name, sub_simulator_name));
+        }
+    }
+
+    fn collect_init_values(
+        &self,
+        model_full_name: &str,
+        init_values: &mut BTreeMap<String, Value>,
+    ) {
+        init_values.insert(model_full_name.to_owned(), self.init_value.clone());
+        for (sub_simulator_name, sub_simulator) in self.model.sub_simulators_iter() {
+            let sub_simulator_full_name = format!("{}/{}", model_full_name, sub_simulator_name);
+            sub_simulator.collect_init_values(&sub_simulator_full_name, init_values);
+        }
+    }
+
     fn has_submodels(&self) -> bool {
         self.model.has_submodels()
     }
 
+    /// Stores the state and timing of this simulator and its descendants,
+    /// keyed by full name, with the position of each random stream. The mail
+    /// and imminent submodels are not stored: they are empty between two
+    /// events.
+    pub(crate) fn checkpoint(&self, checkpoints: &mut BTreeMap<String, SimulatorCheckpoint>) {
+        checkpoints.insert(
+            self.full_name.clone(),
+            SimulatorCheckpoint {
+                state: self.model.state(),
+                rng: self.rng.clone(),
+                t_last: Value::from(&self.t_last),
+                t_next_self: Value::from(&self.t_next_self),
+                t_next: Value::from(&self.t_next),
+                reaction: self.reaction.as_ref().map(|(y_bag, t_next_self)| {
+                    (
+                        y_bag
+                            .iter()
+                            .map(|msg| (msg.port.clone(), msg.value().clone()))
+                            .collect(),
+                        Value::from(t_next_self),
+                    )
+                }),
+            },
+        );
+        for (_, sub_simulator) in self.model.sub_simulators_iter() {
+            sub_simulator.checkpoint(checkpoints);
+        }
+    }
+
+    pub(crate) fn restore(
+        &mut self,
+        checkpoints: &BTreeMap<String, SimulatorCheckpoint>,
+    ) -> Result<(), ExdsdevsError> {
+        let checkpoint = checkpoints.get(&self.full_name).ok_or_else(|| {
+            ExdsdevsError::ErrorCheckpoint(format!("no checkpoint for model '{}'", self.full_name))
+        })?;
+        let full_name = self.full_name.clone();
+        let parse_time = |value: &Value| {
+            Time::try_from(value).map_err(|err| {
+                ExdsdevsError::ErrorCheckpoint(format!("model '{}': {}", full_name, err))
+            })
+        };
+        self.t_last = parse_time(&checkpoint.t_last)?;
+        self.t_next_self = parse_time(&checkpoint.t_next_self)?;
+        self.t_next = parse_time(&checkpoint.t_next)?;
+        self.reaction = match &checkpoint.reaction {
+            Some((y_bag, t_next_self)) => Some((
+                y_bag
+                    .iter()
+                    .map(|(port, value)| Msg::new(port, value.clone()))
+                    .collect(),
+                parse_time(t_next_self)?,
+            )),
+            None => None,
+        };
+        self.rng = checkpoint.rng.clone();
+        if self.initial_state.is_none() {
+            self.initial_state = Some(self.model.state());
+        }
+        self.model.restore_state(&checkpoint.state)?;
+        for (_, sub_simulator) in self.model.sub_simulators() {
+            sub_simulator.restore(checkpoints)?;
+        }
+        Ok(())
+    }
+
+    /// Collects the results of the observers of this simulator and its
+    /// descendants, keyed by model full name.
+    pub(crate) fn results(&self, results: &mut BTreeMap<String, Vec<Value>>) {
+        let observer_results: Vec<Value> = self
+            .observers
+            .iter()
+            .filter_map(|observer| observer.result())
+            .collect();
+        if !observer_results.is_empty() {
+            results.insert(self.full_name.clone(), observer_results);
+        }
+        for (_, sub_simulator) in self.model.sub_simulators_iter() {
+            sub_simulator.results(results);
+        }
+    }
+
+    /// The simulator of the model named `full_name`, this one or a descendant.
+    /// The path is followed one submodel name at a time, since the full name of
+    /// a submodel is `<full name of its parent>/<name>`.
+    pub fn find(&self, full_name: &str) -> Option<&Simulator> {
+        let path = full_name.strip_prefix(self.full_name.as_str())?;
+        if path.is_empty() {
+            return Some(self);
+        }
+        let name = path.strip_prefix('/')?.split('/').next()?;
+        self.model
+            .structure
+            .sub_simulators
+            .get(name)?
+            .find(full_name)
+    }
+
+    /// Captures the current state and next event time of this simulator and its
+    /// descendants without modifying them.
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            full_name: self.full_name.clone(),
+            state: self.model.state(),
+            t_next: self.t_next,
+            submodels: self
+                .model
+                .sub_simulators_iter()
+                .map(|(_, sub_simulator)| sub_simulator.snapshot())
+                .collect(),
+        }
+    }
+
     pub(crate) fn finish(&mut self, sim_time: Time) {
         for (_, model) in self.model.sub_simulators() {
             model.finish(sim_time);
@@ -0,0 +1,279 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+//! Models and helpers shared by the unit tests.
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    path::{Path, PathBuf},
+};
+
+use rand::Rng;
+
+use crate::{
+    containers::{SimRng, Value},
+    dynamic::{Dynamic, DynamicFactory, DynamicFactoryStorage},
+    errors::ExdsdevsError,
+    metrics::Metrics,
+    model::{Model, Resources, Structure},
+    observer::{Observer, ObserverFactory, ObserverFactoryStorage},
+    root_simulator::RootSimulator,
+    simulator::Simulator,
+    time::Time,
+};
+
+pub(crate) struct Passive;
+
+impl Dynamic for Passive {
+    fn new() -> Self {
+        Passive
+    }
+
+    fn dynamic_type(&self) -> String {
+        "passive".to_owned()
+    }
+
+    fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+        Time::Inf
+    }
+
+    fn state(&self) -> Value {
+        Value::Null
+    }
+
+    fn reset(&mut self, _: &Value) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Schedules itself at random intervals and remembers every event time.
+pub(crate) struct Clock {
+    t_next: Time,
+    events: Vec<Time>,
+}
+
+impl Dynamic for Clock {
+    fn new() -> Self {
+        Clock {
+            t_next: Time::Inf,
+            events: Vec::new(),
+        }
+    }
+
+    fn dynamic_type(&self) -> String {
+        "clock".to_owned()
+    }
+
+    fn init(&mut self, _: &mut Structure, _: Time, _: &Value, _: &Resources, rng: &mut SimRng) {
+        self.t_next = Time::Value(rng.gen_range(1..4));
+    }
+
+    fn internal_transition(&mut self, _: &mut Structure, sim_time: Time, rng: &mut SimRng) {
+        self.events.push(sim_time);
+        self.t_next = Time::Value(rng.gen_range(1..4));
+    }
+
+    fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+        self.t_next
+    }
+
+    fn state(&self) -> Value {
+        serde_json::json!({
+            "t_next": Value::from(&self.t_next),
+            "events": Value::Array(self.events.iter().map(Value::from).collect()),
+        })
+    }
+
+    fn restore_state(&mut self, state: &Value) -> Result<(), ExdsdevsError> {
+        self.t_next = Time::try_from(&state["t_next"]).unwrap();
+        self.events = state["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|time| Time::try_from(time).unwrap())
+            .collect();
+        Ok(())
+    }
+
+    fn reset(&mut self, initial_state: &Value) -> Result<(), String> {
+        self.restore_state(initial_state)
+            .map_err(|err| err.to_string())
+    }
+}
+
+pub(crate) fn child_state(root: &RootSimulator, name: &str) -> Value {
+    root.simulator.model.structure.sub_simulators[name]
+        .model
+        .state()
+}
+
+pub(crate) fn build_root(
+    children: Vec<(&str, Box<dyn Dynamic + Send>)>,
+    finish_time: Time,
+) -> RootSimulator {
+    build_root_observed(children, finish_time, &PathBuf::new(), &Vec::new)
+}
+
+/// Builds an initialized root coupled model holding one atomic model with ports
+/// `in`/`out` per child; every child gets the observers returned by `observe`.
+/// The children may run on worker threads, see `Model::new_send`.
+pub(crate) fn build_root_observed(
+    children: Vec<(&str, Box<dyn Dynamic + Send>)>,
+    finish_time: Time,
+    sim_dir: &Path,
+    observe: &dyn Fn() -> Vec<Box<dyn Observer>>,
+) -> RootSimulator {
+    build_root_coupled(children, &[], finish_time, sim_dir, observe)
+}
+
+/// Same as `build_root_observed`, with the children connected by the
+/// `(source, source_port, destination, destination_port)` couplings.
+pub(crate) fn build_root_coupled(
+    children: Vec<(&str, Box<dyn Dynamic + Send>)>,
+    couplings: &[(&str, &str, &str, &str)],
+    finish_time: Time,
+    sim_dir: &Path,
+    observe: &dyn Fn() -> Vec<Box<dyn Observer>>,
+) -> RootSimulator {
+    let submodels = children
+        .into_iter()
+        .map(|(name, dynamic)| (name, Model::new_send(atomic_structure(&["in"]), dynamic)))
+        .collect();
+    let model = root_model(submodels, couplings, Box::new(Passive));
+    let mut simulator = Simulator::new("root", model, Resources::default());
+    for child in simulator.model.structure.sub_simulators.values_mut() {
+        for observer in observe() {
+            child.add_observer(observer);
+        }
+    }
+    init_root(simulator, finish_time, sim_dir)
+}
+
+/// Structure of an atomic model with the given input ports and an `out`
+/// output port.
+pub(crate) fn atomic_structure(input_ports: &[&str]) -> Structure {
+    Structure::new(input_ports, &["out"], BTreeMap::new(), &[], &[], &[])
+}
+
+/// Coupled model of the `root` simulator: holds the `submodels`, simulated as
+/// `root/<name>`, connected by the internal `couplings`.
+pub(crate) fn root_model(
+    submodels: Vec<(&str, Model)>,
+    couplings: &[(&str, &str, &str, &str)],
+    dynamic: Box<dyn Dynamic>,
+) -> Model {
+    let submodels = submodels
+        .into_iter()
+        .map(|(name, model)| {
+            let full_name = format!("root/{}", name);
+            let simulator = Simulator::new(&full_name, model, Resources::default());
+            (name.to_owned(), simulator)
+        })
+        .collect();
+    let structure = Structure::new(&[], &[], submodels, &[], couplings, &[]);
+    Model::new(structure, dynamic)
+}
+
+/// Init variant giving a null init value to `simulator` and to every model
+/// under it.
+pub(crate) fn null_init_variant(simulator: &Simulator) -> BTreeMap<String, Value> {
+    fn collect(simulator: &Simulator, init_variant: &mut BTreeMap<String, Value>) {
+        init_variant.insert(simulator.full_name.clone(), Value::Null);
+        for child in simulator.model.structure.sub_simulators.values() {
+            collect(child, init_variant);
+        }
+    }
+    let mut init_variant = BTreeMap::new();
+    collect(simulator, &mut init_variant);
+    init_variant
+}
+
+/// Statically initializes and initializes a root simulator of `simulator`,
+/// running from 0 to `finish_time`.
+pub(crate) fn init_root(simulator: Simulator, finish_time: Time, sim_dir: &Path) -> RootSimulator {
+    let init_variant = null_init_variant(&simulator);
+    let mut root = RootSimulator::from_simulator(simulator, Time::Value(0), finish_time);
+    root.init_static(&sim_dir.to_path_buf(), &init_variant, 0);
+    root.init();
+    root
+}
+
+/// Returns a fresh directory under the system temporary directory.
+pub(crate) fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("exdsdevs_{}_{}", name, std::process::id()));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    dir
+}
+
+/// Writes an experiment running `root` (passive) with two `clock` submodels
+/// observed by `Metrics`; `clock_1` has two init variants. Returns the path of
+/// the experiment config.
+pub(crate) fn write_experiment(dir: &Path, iterations: u64) -> PathBuf {
+    let model_dir = dir.join("model");
+    std::fs::create_dir_all(&model_dir).unwrap();
+    let root_class = serde_json::json!({
+        "model_class": "root",
+        "dynamic_type": "passive",
+        "input_ports": [],
+        "output_ports": [],
+        "submodels": {
+            "clock_1": {"model_class": "clock", "init_variants": {"1": {}, "2": {}}},
+            "clock_2": {"model_class": "clock", "init_variants": {"1": {}}}
+        },
+        "external_input_couplings": [],
+        "internal_couplings": [],
+        "external_output_couplings": [],
+        "default_init": {},
+        "root_init_variants": {"default": {}},
+        "local_resources": {},
+        "observers": []
+    });
+    let clock_class = serde_json::json!({
+        "model_class": "clock",
+        "dynamic_type": "clock",
+        "input_ports": ["in"],
+        "output_ports": ["out"],
+        "submodels": {},
+        "external_input_couplings": [],
+        "internal_couplings": [],
+        "external_output_couplings": [],
+        "default_init": {},
+        "root_init_variants": {},
+        "local_resources": {},
+        "observers": [{"observer_class": "metrics", "observer_config": {}}]
+    });
+    let experiment = serde_json::json!({
+        "name": "test",
+        "results_directory": "results",
+        "model_directory": "model",
+        "root_model_class": "root",
+        "init_time": "0",
+        "finish_time": "20",
+        "random_seed": 7,
+        "iterations": iterations,
+        "global_resources": {}
+    });
+    std::fs::write(model_dir.join("root.json"), root_class.to_string()).unwrap();
+    std::fs::write(model_dir.join("clock.json"), clock_class.to_string()).unwrap();
+    let experiment_path = dir.join("experiment.json");
+    std::fs::write(&experiment_path, experiment.to_string()).unwrap();
+    experiment_path
+}
+
+/// Dynamic and observer factories for the models of `write_experiment`.
+pub(crate) fn experiment_factories() -> (DynamicFactoryStorage, ObserverFactoryStorage) {
+    let dynamic_factory = DynamicFactoryStorage::new()
+        .with_dynamic_factory("passive", DynamicFactory::<Passive>::new())
+        .with_dynamic_factory("clock", DynamicFactory::<Clock>::new());
+    let observer_factory = ObserverFactoryStorage::new()
+        .with_observer_factory("metrics", ObserverFactory::<Metrics>::new());
+    (dynamic_factory, observer_factory)
+}
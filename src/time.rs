@@ -6,7 +6,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms
 
-use std::fmt::Debug;
+use std::{cmp::Ordering, convert::TryFrom, fmt::Debug, str::FromStr, time::Duration};
 pub use std::{
     collections::HashSet,
     fmt::Display,
@@ -14,22 +14,158 @@ pub use std::{
     rc::Rc,
 };
 
-use crate::containers::Value;
+use crate::{containers::Value, errors::ExdsdevsError};
 
 type Inner = i128;
 
 #[derive(Clone, Copy)]
 pub enum Time {
     Value(Inner),
+    Real(f64),
     Inf,
     StopSim,
 }
 
+const MILLIS_PER_SEC: Inner = 1000;
+
+/// The unit conversions take one unit of `Time::Value` as a millisecond.
+impl Time {
+    pub fn from_secs(secs: u64) -> Self {
+        Self::Value(Inner::from(secs) * MILLIS_PER_SEC)
+    }
+
+    pub fn from_millis(millis: u64) -> Self {
+        Self::Value(Inner::from(millis))
+    }
+
+    /// Truncated to whole milliseconds.
+    pub fn from_duration(duration: Duration) -> Self {
+        Self::Value(duration.as_millis() as Inner)
+    }
+
+    /// Milliseconds of a finite time, real values are rounded; `None` for
+    /// `Inf`, `StopSim` and non-finite real values.
+    pub fn as_millis(&self) -> Option<Inner> {
+        match self {
+            Self::Value(value) => Some(*value),
+            Self::Real(value) if value.is_finite() => Some(value.round() as Inner),
+            _ => None,
+        }
+    }
+
+    /// Seconds of a finite time, `None` for `Inf` and `StopSim`.
+    pub fn as_secs_f64(&self) -> Option<f64> {
+        self.as_f64().map(|millis| millis / MILLIS_PER_SEC as f64)
+    }
+
+    /// Time elapsed since `earlier`, failing with `ErrorSimTime` when `earlier`
+    /// comes after this time.
+    pub fn checked_elapsed_since(self, earlier: Time) -> Result<Time, ExdsdevsError> {
+        if self < earlier {
+            Err(ExdsdevsError::ErrorSimTime(format!(
+                "elapsed time from {} to {} is negative",
+                earlier, self
+            )))
+        } else {
+            Ok(self - earlier)
+        }
+    }
+
+    /// Same as `checked_elapsed_since`, but a negative elapsed time, which means
+    /// the simulator is out of sync, panics in debug builds and is clamped to
+    /// zero in release builds.
+    pub fn elapsed_since(self, earlier: Time) -> Time {
+        self.checked_elapsed_since(earlier).unwrap_or_else(|err| {
+            debug_assert!(false, "{}", err);
+            Time::Value(0)
+        })
+    }
+
+    /// Whether the time is a number, neither `Inf`, `StopSim` nor a
+    /// non-finite real value.
+    pub fn is_finite(&self) -> bool {
+        match self {
+            Self::Value(_) => true,
+            Self::Real(value) => value.is_finite(),
+            Self::Inf | Self::StopSim => false,
+        }
+    }
+
+    pub fn is_inf(&self) -> bool {
+        matches!(self, Self::Inf)
+    }
+
+    /// Whether the time is `StopSim`, the time advance stopping the simulation.
+    pub fn is_stop(&self) -> bool {
+        matches!(self, Self::StopSim)
+    }
+
+    /// The integer of a `Time::Value`, `None` for the other variants; see
+    /// `as_millis` to round real values too.
+    pub fn value(&self) -> Option<Inner> {
+        match self {
+            Self::Value(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Numeric value of a finite time, `None` for `Inf` and `StopSim`.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Value(value) => Some(*value as f64),
+            Self::Real(value) => Some(*value),
+            Self::Inf | Self::StopSim => None,
+        }
+    }
+
+    /// Compares the numeric variants exactly on a common axis, without casting
+    /// integers to `f64`. Every `NaN` is the same time, after every number and
+    /// before `Inf`, so that `Eq` stays reflexive; `-0.0` equals `0.0`.
+    fn cmp_finite(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Value(left), Self::Value(right)) => left.cmp(right),
+            (Self::Real(left), Self::Real(right)) => cmp_real(*left, *right),
+            (Self::Value(left), Self::Real(right)) => cmp_int_real(*left, *right),
+            (Self::Real(left), Self::Value(right)) => cmp_int_real(*right, *left).reverse(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Orders reals with `NaN` after every number.
+fn cmp_real(left: f64, right: f64) -> Ordering {
+    match (left.is_nan(), right.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => left.partial_cmp(&right).unwrap(),
+    }
+}
+
+/// Compares an integer and a real exactly: the integer part of the real is
+/// compared as an integer, then its fractional part breaks the tie.
+fn cmp_int_real(int: Inner, real: f64) -> Ordering {
+    // 2^127, the first real above every `Inner`; -2^127 is `Inner::MIN`.
+    const INNER_BOUND: f64 = 170_141_183_460_469_231_731_687_303_715_884_105_728.0;
+    if real.is_nan() || real >= INNER_BOUND {
+        return Ordering::Less;
+    }
+    if real < -INNER_BOUND {
+        return Ordering::Greater;
+    }
+    let whole = real.trunc();
+    int.cmp(&(whole as Inner))
+        .then_with(|| cmp_real(0.0, real - whole))
+}
+
 impl From<&Time> for Value {
     fn from(time: &Time) -> Self {
         match time {
             Time::Inf => Value::String("Inf".to_owned()),
             Time::Value(value) => Value::Number(From::from(*value)),
+            Time::Real(value) => serde_json::Number::from_f64(*value)
+                .map(Value::Number)
+                .unwrap_or_else(|| Value::String(value.to_string())),
             Time::StopSim => Value::String("StopSim".to_owned()),
         }
     }
@@ -44,20 +180,21 @@ impl PartialEq for Time {
 impl Eq for Time {}
 
 impl Ord for Time {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
-            (Self::Value(left), Self::Value(right)) => left.cmp(right),
-            (Self::Inf, Self::Value(_)) => std::cmp::Ordering::Greater,
-            (Self::Value(_), Self::Inf) => std::cmp::Ordering::Less,
-            (Self::Inf, Self::Inf) => std::cmp::Ordering::Equal,
-            (Self::StopSim, _) => std::cmp::Ordering::Less,
-            (_, Self::StopSim) => std::cmp::Ordering::Greater,
+            (Self::StopSim, Self::StopSim) => Ordering::Equal,
+            (Self::StopSim, _) => Ordering::Less,
+            (_, Self::StopSim) => Ordering::Greater,
+            (Self::Inf, Self::Inf) => Ordering::Equal,
+            (Self::Inf, _) => Ordering::Greater,
+            (_, Self::Inf) => Ordering::Less,
+            (left, right) => left.cmp_finite(right),
         }
     }
 }
 
 impl PartialOrd for Time {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -67,6 +204,7 @@ impl Display for Time {
         match self {
             Self::Inf => write!(f, "Inf"),
             Self::Value(value) => write!(f, "{}", value),
+            Self::Real(value) => write!(f, "{}", value),
             Self::StopSim => write!(f, "StopSim"),
         }
     }
@@ -78,6 +216,9 @@ impl Debug for Time {
     }
 }
 
+/// Integer overflow towards the future saturates to `Time::Inf`: a time too far
+/// in the future to be represented means the model is passive. Overflow towards
+/// the past saturates to `Time::Value(i128::MIN)`.
 impl Add for Time {
     type Output = Self;
 
@@ -85,14 +226,20 @@ impl Add for Time {
         match (self, rhs) {
             (Self::Inf, _) => Self::Inf,
             (_, Self::Inf) => Self::Inf,
-            (Self::Value(left), Self::Value(right)) => Self::Value(left + right),
+            (Self::Value(left), Self::Value(right)) => left
+                .checked_add(right)
+                .map_or_else(|| saturate(right > 0), Self::Value),
+            (Self::Real(left), Self::Real(right)) => Self::Real(left + right),
+            (Self::Value(left), Self::Real(right)) => Self::Real(left as f64 + right),
+            (Self::Real(left), Self::Value(right)) => Self::Real(left + right as f64),
             (Self::StopSim, Self::StopSim) => Self::StopSim,
-            (Self::Value(val), Self::StopSim) => Self::Value(val),
-            (Self::StopSim, Self::Value(val)) => Self::Value(val),
+            (val, Self::StopSim) => val,
+            (Self::StopSim, val) => val,
         }
     }
 }
 
+/// Saturates as `Add` does.
 impl Sub for Time {
     type Output = Self;
 
@@ -100,10 +247,234 @@ impl Sub for Time {
         match (self, rhs) {
             (Self::Inf, _) => Self::Inf,
             (_, Self::Inf) => Self::Inf,
-            (Self::Value(left), Self::Value(right)) => Self::Value(left - right),
+            (Self::Value(left), Self::Value(right)) => left
+                .checked_sub(right)
+                .map_or_else(|| saturate(right < 0), Self::Value),
+            (Self::Real(left), Self::Real(right)) => Self::Real(left - right),
+            (Self::Value(left), Self::Real(right)) => Self::Real(left as f64 - right),
+            (Self::Real(left), Self::Value(right)) => Self::Real(left - right as f64),
             (Self::StopSim, Self::StopSim) => Self::StopSim,
-            (Self::Value(val), Self::StopSim) => Self::Value(val),
-            (Self::StopSim, Self::Value(val)) => Self::Value(val),
+            (val, Self::StopSim) => val,
+            (Self::StopSim, val) => val,
+        }
+    }
+}
+
+/// The result of an integer overflow, towards the future or the past.
+fn saturate(towards_future: bool) -> Time {
+    if towards_future {
+        Time::Inf
+    } else {
+        Time::Value(Inner::MIN)
+    }
+}
+
+impl TryFrom<&str> for Time {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value.trim() {
+            "Inf" | "Infinity" => Ok(Self::Inf),
+            "StopSim" => Ok(Self::StopSim),
+            t => Inner::from_str(t)
+                .map(Self::Value)
+                .or_else(|_| f64::from_str(t).map(Self::Real))
+                .map_err(|_| format!("Cannot convert value {} to Time", t)),
         }
     }
 }
+
+impl TryFrom<&Value> for Time {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Number(number) => Self::try_from(number.to_string().as_str()),
+            Value::String(string) => Self::try_from(string.as_str()),
+            _ => Err(format!("Cannot convert value {} to Time", value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_arithmetic() {
+        assert_eq!(Time::Value(1) + Time::Real(0.5), Time::Real(1.5));
+        assert_eq!(Time::Real(2.5) - Time::Value(1), Time::Real(1.5));
+        assert_eq!(Time::Real(1.0) + Time::Inf, Time::Inf);
+        assert_eq!(Time::StopSim + Time::Real(1.0), Time::Real(1.0));
+        assert_eq!(Time::Real(1.0) - Time::StopSim, Time::Real(1.0));
+    }
+
+    #[test]
+    fn test_overflow_saturates() {
+        assert_eq!(Time::Value(1) + Time::Value(i128::MAX), Time::Inf);
+        assert_eq!(Time::Value(1) - Time::Value(i128::MIN), Time::Inf);
+        assert_eq!(
+            Time::Value(-2) - Time::Value(i128::MAX),
+            Time::Value(i128::MIN)
+        );
+        assert_eq!(
+            Time::Value(-2) + Time::Value(i128::MIN),
+            Time::Value(i128::MIN)
+        );
+        assert_eq!(
+            Time::Value(i128::MAX) - Time::Value(1),
+            Time::Value(i128::MAX - 1)
+        );
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert_eq!(Time::Value(1), Time::Real(1.0));
+        assert_eq!(Time::Value(0), Time::Real(-0.0));
+        assert!(Time::Real(-0.5) < Time::Value(0));
+        assert!(Time::Value(-1) < Time::Real(-0.5));
+        assert!(Time::Value(1) < Time::Real(1.5));
+        assert!(Time::Real(1.5) < Time::Value(2));
+        assert!(Time::Real(f64::INFINITY) < Time::Inf);
+        assert!(Time::Real(f64::NAN) < Time::Inf);
+        assert!(Time::Real(f64::INFINITY) < Time::Real(f64::NAN));
+        assert_eq!(Time::Real(f64::NAN), Time::Real(f64::NAN));
+        assert!(Time::StopSim < Time::Real(f64::NEG_INFINITY));
+        assert_eq!(
+            Time::Real(3.0).min(Time::Value(2)).min(Time::Inf),
+            Time::Value(2)
+        );
+    }
+
+    #[test]
+    fn test_ordering_is_exact() {
+        // 2^53 + 1 has no `f64`, casting it would make it equal to 2^53.
+        let big: Inner = (1 << 53) + 1;
+        let real = Time::Real((1_u64 << 53) as f64);
+        assert!(real < Time::Value(big));
+        assert!(Time::Value(big) > real);
+        assert_ne!(Time::Value(big), real);
+        assert_eq!(Time::Value(big - 1), real);
+
+        assert!(Time::Value(Inner::MAX) < Time::Real(2f64.powi(127)));
+        assert!(Time::Value(Inner::MAX) < Time::Real(f64::INFINITY));
+        assert_eq!(Time::Value(Inner::MIN), Time::Real(-(2f64.powi(127))));
+        assert!(Time::Value(Inner::MIN) > Time::Real(-(2f64.powi(128))));
+        assert!(Time::Value(Inner::MIN) > Time::Real(f64::NEG_INFINITY));
+        assert!(Time::Value(Inner::MAX) < Time::Real(f64::NAN));
+
+        // The order is total: sorting mixed variants is consistent.
+        let mut times = [
+            Time::Inf,
+            Time::Real(f64::NAN),
+            Time::Value(big),
+            real,
+            Time::Real(-0.0),
+            Time::Value(0),
+            Time::StopSim,
+            Time::Real(0.5),
+        ];
+        times.sort();
+        for pair in times.windows(2) {
+            assert!(pair[0] <= pair[1], "{:?}", pair);
+        }
+        assert_eq!(times.first(), Some(&Time::StopSim));
+        assert_eq!(times.last(), Some(&Time::Inf));
+    }
+
+    #[test]
+    fn test_try_from_str() {
+        assert_eq!(Time::try_from("10"), Ok(Time::Value(10)));
+        assert_eq!(Time::try_from("0.25"), Ok(Time::Real(0.25)));
+        assert_eq!(Time::try_from("1e3"), Ok(Time::Real(1000.0)));
+        assert_eq!(Time::try_from("Infinity"), Ok(Time::Inf));
+        assert_eq!(Time::try_from("StopSim"), Ok(Time::StopSim));
+        assert!(Time::try_from("ten").is_err());
+    }
+
+    #[test]
+    fn test_value_round_trip() {
+        for time in [
+            Time::Value(-7),
+            Time::Value(i128::MAX),
+            Time::Real(0.5),
+            Time::Inf,
+            Time::StopSim,
+        ] {
+            assert_eq!(Time::try_from(&Value::from(&time)), Ok(time));
+        }
+        assert!(Time::try_from(&Value::Null).is_err());
+    }
+
+    #[test]
+    fn test_as_f64() {
+        assert_eq!(Time::Value(3).as_f64(), Some(3.0));
+        assert_eq!(Time::Real(0.5).as_f64(), Some(0.5));
+        assert_eq!(Time::Inf.as_f64(), None);
+        assert_eq!(Time::StopSim.as_f64(), None);
+    }
+
+    #[test]
+    fn test_predicates() {
+        assert!(Time::Value(3).is_finite());
+        assert!(Time::Real(0.5).is_finite());
+        assert!(!Time::Real(f64::INFINITY).is_finite());
+        assert!(!Time::Real(f64::NAN).is_finite());
+        assert!(!Time::Inf.is_finite());
+        assert!(!Time::StopSim.is_finite());
+        assert!(Time::Inf.is_inf());
+        assert!(!Time::Real(f64::INFINITY).is_inf());
+        assert!(Time::StopSim.is_stop());
+        assert!(!Time::Inf.is_stop());
+        assert_eq!(Time::Value(-7).value(), Some(-7));
+        assert_eq!(Time::Real(2.0).value(), None);
+        assert_eq!(Time::Inf.value(), None);
+    }
+
+    #[test]
+    fn test_unit_conversions() {
+        assert_eq!(Time::from_secs(3), Time::Value(3000));
+        assert_eq!(Time::from_millis(250), Time::Value(250));
+        assert_eq!(
+            Time::from_duration(Duration::from_micros(1_500_900)),
+            Time::Value(1500)
+        );
+        assert_eq!(Time::from_secs(2).as_millis(), Some(2000));
+        assert_eq!(Time::Real(2.6).as_millis(), Some(3));
+        assert_eq!(Time::Real(f64::NAN).as_millis(), None);
+        assert_eq!(Time::from_millis(1500).as_secs_f64(), Some(1.5));
+        assert_eq!(Time::Inf.as_millis(), None);
+        assert_eq!(Time::StopSim.as_secs_f64(), None);
+    }
+
+    #[test]
+    fn test_elapsed_since() {
+        assert_eq!(Time::Value(5).elapsed_since(Time::Value(2)), Time::Value(3));
+        assert_eq!(
+            Time::Real(2.5).elapsed_since(Time::Value(1)),
+            Time::Real(1.5)
+        );
+        assert_eq!(Time::Inf.elapsed_since(Time::Value(1)), Time::Inf);
+        assert_eq!(
+            Time::Value(1).checked_elapsed_since(Time::Value(2)),
+            Err(ExdsdevsError::ErrorSimTime(
+                "elapsed time from 2 to 1 is negative".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    #[cfg_attr(debug_assertions, should_panic(expected = "elapsed time from 2 to 1"))]
+    fn test_negative_elapsed_is_clamped() {
+        assert_eq!(Time::Value(1).elapsed_since(Time::Value(2)), Time::Value(0));
+    }
+
+    #[test]
+    fn test_real_to_value() {
+        assert_eq!(Value::from(&Time::Real(0.5)), serde_json::json!(0.5));
+        assert_eq!(
+            Value::from(&Time::Real(f64::NAN)),
+            Value::String("NaN".to_owned())
+        );
+    }
+}
@@ -0,0 +1,478 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use std::{collections::BTreeMap, convert::TryFrom, path::Path};
+
+use crate::{
+    containers::{Bag, Msg, SimRng, Value},
+    dynamic::Dynamic,
+    errors::ExdsdevsError,
+    model::{Model, Resources, Structure},
+    simulator::Simulator,
+    time::Time,
+    utils::read_json_from_file,
+};
+
+/// Version of the traces written by `TraceRecorder`.
+pub const TRACE_VERSION: u64 = 1;
+
+/// A recorded input message: `value` sent on `port` at `time`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent {
+    pub time: Time,
+    pub port: String,
+    pub value: Value,
+}
+
+impl TraceEvent {
+    pub fn new(time: Time, port: &str, value: Value) -> Self {
+        TraceEvent {
+            time,
+            port: port.to_owned(),
+            value,
+        }
+    }
+}
+
+impl From<&TraceEvent> for Value {
+    fn from(event: &TraceEvent) -> Self {
+        Value::Array(vec![
+            Value::from(&event.time),
+            Value::String(event.port.clone()),
+            event.value.clone(),
+        ])
+    }
+}
+
+/// Replays a recorded input trace: an atomic model sending every message of
+/// the trace on its port at its time, to test one model against captured
+/// inputs without the coupled model it belongs to, see `harness`.
+///
+/// A trace is written in JSON as a list of `[time, port, value]` events, or as
+/// a trace recorded by `TraceRecorder`, whose `inputs` are replayed. The
+/// events are sent in time order, the ones at the same time in one bag, and
+/// the ones before the initialization time are dropped. The state is
+/// `{"trace": [...], "next": <index of the next event>}`, so a model class
+/// can give the trace in its init value; an invalid trace there panics at
+/// `init`.
+pub struct TraceDriver {
+    trace: Vec<TraceEvent>,
+    next: usize,
+    now: Time,
+}
+
+impl TraceDriver {
+    pub fn new() -> Self {
+        TraceDriver {
+            trace: Vec::new(),
+            next: 0,
+            now: Time::Value(0),
+        }
+    }
+
+    pub fn with_trace(mut self, mut trace: Vec<TraceEvent>) -> Self {
+        trace.sort_by_key(|event| event.time);
+        self.trace = trace;
+        self.next = 0;
+        self
+    }
+
+    /// Loads the trace written at `path`. The trace errors give `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ExdsdevsError> {
+        let path = path.as_ref();
+        let trace: Value = read_json_from_file(path)?;
+        Self::from_value(&trace).map_err(|err| match err {
+            ExdsdevsError::ErrorTrace(None, msg) => {
+                ExdsdevsError::ErrorTrace(Some(path.to_owned()), msg)
+            }
+            ExdsdevsError::ErrorParseJson(None, err) => {
+                ExdsdevsError::ErrorParseJson(Some(path.to_owned()), err)
+            }
+            err => err,
+        })
+    }
+
+    /// Reads a trace, e.g. the result of a `TraceRecorder`.
+    pub fn from_value(trace: &Value) -> Result<Self, ExdsdevsError> {
+        Ok(Self::new().with_trace(parse_trace(trace)?))
+    }
+
+    /// The ports of the trace, in order of first use.
+    pub fn output_ports(&self) -> Vec<&str> {
+        let mut ports: Vec<&str> = Vec::new();
+        for event in &self.trace {
+            if !ports.contains(&event.port.as_str()) {
+                ports.push(&event.port);
+            }
+        }
+        ports
+    }
+
+    /// Builds a `root` coupled model holding this driver, as `trace_driver`,
+    /// and the tested `model`, as `name`: every port of the trace feeds the
+    /// input port of the same name of `model`, and the output ports of
+    /// `model` are the output ports of `root`.
+    pub fn harness(self, name: &str, model: Model) -> Simulator {
+        let ports: Vec<String> = self.output_ports().into_iter().map(str::to_owned).collect();
+        let port_names: Vec<&str> = ports.iter().map(String::as_str).collect();
+        let output_ports = model.structure.output_ports.clone();
+        let output_port_names: Vec<&str> = output_ports.iter().map(String::as_str).collect();
+
+        let driver_structure = Structure::new(&[], &port_names, BTreeMap::new(), &[], &[], &[]);
+        let mut submodels = BTreeMap::new();
+        submodels.insert(
+            "trace_driver".to_owned(),
+            Simulator::new(
+                "root/trace_driver",
+                Model::new(driver_structure, Box::new(self)),
+                Resources::default(),
+            ),
+        );
+        submodels.insert(
+            name.to_owned(),
+            Simulator::new(&format!("root/{}", name), model, Resources::default()),
+        );
+        let internal_couplings: Vec<(&str, &str, &str, &str)> = port_names
+            .iter()
+            .map(|&port| ("trace_driver", port, name, port))
+            .collect();
+        let external_output_couplings: Vec<(&str, &str, &str)> = output_port_names
+            .iter()
+            .map(|&port| (name, port, port))
+            .collect();
+        let structure = Structure::new(
+            &[],
+            &output_port_names,
+            submodels,
+            &[],
+            &internal_couplings,
+            &external_output_couplings,
+        );
+        Simulator::new(
+            "root",
+            Model::new(structure, Box::new(Harness)),
+            Resources::default(),
+        )
+    }
+
+    /// Index of the first event after the ones at the time of the next event.
+    fn next_time_end(&self) -> usize {
+        match self.trace.get(self.next) {
+            Some(next) => {
+                self.next
+                    + self.trace[self.next..]
+                        .iter()
+                        .take_while(|event| event.time == next.time)
+                        .count()
+            }
+            None => self.next,
+        }
+    }
+}
+
+impl Default for TraceDriver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads the `[time, port, value]` events of `trace`, or the inputs of a
+/// recorded trace.
+fn parse_trace(trace: &Value) -> Result<Vec<TraceEvent>, ExdsdevsError> {
+    let events = match trace {
+        Value::Object(recorded) => {
+            let version = recorded.get("version").and_then(Value::as_u64);
+            if version != Some(TRACE_VERSION) {
+                return Err(ExdsdevsError::ErrorTrace(
+                    None,
+                    format!(
+                        "trace version {} is not supported, expected {}",
+                        recorded.get("version").unwrap_or(&Value::Null),
+                        TRACE_VERSION
+                    ),
+                ));
+            }
+            recorded.get("inputs").cloned().unwrap_or(Value::Null)
+        }
+        events => events.clone(),
+    };
+    let events: Vec<(Value, String, Value)> = serde_json::from_value(events)?;
+    events
+        .into_iter()
+        .enumerate()
+        .map(|(index, (time, port, value))| {
+            let time = Time::try_from(&time).map_err(|err| {
+                ExdsdevsError::ErrorSimTime(format!("trace event {}: {}", index, err))
+            })?;
+            Ok(TraceEvent { time, port, value })
+        })
+        .collect()
+}
+
+impl Dynamic for TraceDriver {
+    fn new() -> Self {
+        TraceDriver::new()
+    }
+
+    fn dynamic_type(&self) -> String {
+        "trace_driver".to_owned()
+    }
+
+    fn init(
+        &mut self,
+        _model_structure: &mut Structure,
+        init_time: Time,
+        init_value: &Value,
+        _resources: &Resources,
+        _rng: &mut SimRng,
+    ) {
+        if !init_value.is_null() {
+            self.restore_state(init_value)
+                .unwrap_or_else(|err| panic!("Trace driver: {}", err));
+        }
+        self.now = init_time;
+        while self
+            .trace
+            .get(self.next)
+            .map_or(false, |event| event.time < init_time)
+        {
+            self.next += 1;
+        }
+    }
+
+    fn internal_transition(
+        &mut self,
+        _model_structure: &mut Structure,
+        sim_time: Time,
+        _rng: &mut SimRng,
+    ) {
+        self.next = self.next_time_end();
+        self.now = sim_time;
+    }
+
+    fn output(&self, _model_structure: &Structure, _sim_time: Time) -> Bag {
+        self.trace[self.next..self.next_time_end()]
+            .iter()
+            .map(|event| Msg::new(&event.port, event.value.clone()))
+            .collect()
+    }
+
+    fn time_advance(&self, _model_structure: &Structure, _rng: &mut SimRng) -> Time {
+        match self.trace.get(self.next) {
+            Some(event) => event.time - self.now,
+            None => Time::Inf,
+        }
+    }
+
+    fn state(&self) -> Value {
+        serde_json::json!({
+            "trace": self.trace.iter().map(Value::from).collect::<Vec<Value>>(),
+            "next": self.next,
+        })
+    }
+
+    /// Fails on an invalid trace.
+    fn restore_state(&mut self, state: &Value) -> Result<(), ExdsdevsError> {
+        let trace = parse_trace(state.get("trace").unwrap_or(&Value::Null))?;
+        *self = Self::new().with_trace(trace);
+        if let Some(next) = state.get("next").and_then(Value::as_u64) {
+            self.next = next as usize;
+        }
+        Ok(())
+    }
+}
+
+/// The passive coupled model built by `TraceDriver::harness`.
+struct Harness;
+
+impl Dynamic for Harness {
+    fn new() -> Self {
+        Harness
+    }
+
+    fn dynamic_type(&self) -> String {
+        "trace_harness".to_owned()
+    }
+
+    fn time_advance(&self, _model_structure: &Structure, _rng: &mut SimRng) -> Time {
+        Time::Inf
+    }
+
+    fn state(&self) -> Value {
+        Value::Null
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::containers::BagExt;
+    use crate::root_simulator::{RootSimulator, TerminationReason};
+    use crate::test_utils::temp_dir;
+    use crate::utils::{read_json_from_file, write_json_to_file};
+    use rand::SeedableRng;
+
+    /// Records the time and values of the bags it receives on `in`, and
+    /// echoes them on `out`.
+    struct Recorder {
+        received: Vec<Value>,
+    }
+
+    impl Dynamic for Recorder {
+        fn new() -> Self {
+            Recorder {
+                received: Vec::new(),
+            }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "recorder".to_owned()
+        }
+
+        fn external_transition(
+            &mut self,
+            _: &Structure,
+            sim_time: Time,
+            _: Time,
+            x_bag: &Bag,
+            _: &mut SimRng,
+        ) {
+            let values: Vec<Value> = x_bag.values_on("in").cloned().collect();
+            self.received.push(Value::Array(vec![
+                Value::from(&sim_time),
+                Value::from(values),
+            ]));
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            Time::Inf
+        }
+
+        fn state(&self) -> Value {
+            Value::from(self.received.clone())
+        }
+    }
+
+    fn recorder() -> Model {
+        let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+        Model::new(structure, Box::new(Recorder::new()))
+    }
+
+    #[test]
+    fn test_replay() {
+        let driver = TraceDriver::new().with_trace(vec![
+            TraceEvent::new(Time::Value(5), "in", Value::from(1)),
+            TraceEvent::new(Time::Value(12), "in", Value::from(3)),
+            TraceEvent::new(Time::Value(5), "in", Value::from(2)),
+            TraceEvent::new(Time::Value(-1), "in", Value::from(-1)),
+            TraceEvent::new(Time::Value(2), "in", Value::from(0)),
+        ]);
+        let harness = driver.harness("recorder", recorder());
+        assert!(harness.model.check().is_ok());
+        assert_eq!(harness.model.structure.output_ports, vec!["out"]);
+        let mut root = RootSimulator::from_simulator(harness, Time::Value(0), Time::Value(100));
+        root.init();
+        assert_eq!(root.run().unwrap(), TerminationReason::Quiescent);
+
+        let snapshot = root.snapshot();
+        assert_eq!(
+            snapshot.find("root/recorder").unwrap().state,
+            serde_json::json!([[2, [0]], [5, [1, 2]], [12, [3]]])
+        );
+        assert_eq!(
+            snapshot.find("root/trace_driver").unwrap().state["next"],
+            Value::from(5)
+        );
+    }
+
+    #[test]
+    fn test_from_file() {
+        let dir = temp_dir("trace_driver");
+        let path = dir.join("trace.json");
+        write_json_to_file(
+            &path,
+            &serde_json::json!([[1.5, "in", "a"], ["3", "in", null]]),
+        )
+        .unwrap();
+        let driver = TraceDriver::from_file(&path).unwrap();
+        assert_eq!(
+            driver.trace,
+            vec![
+                TraceEvent::new(Time::Real(1.5), "in", Value::from("a")),
+                TraceEvent::new(Time::Value(3), "in", Value::Null),
+            ]
+        );
+
+        write_json_to_file(&path, &serde_json::json!([["soon", "in", 1]])).unwrap();
+        assert!(matches!(
+            TraceDriver::from_file(&path),
+            Err(ExdsdevsError::ErrorSimTime(_))
+        ));
+        write_json_to_file(&path, &serde_json::json!([[1, "in"]])).unwrap();
+        assert!(matches!(
+            TraceDriver::from_file(&path),
+            Err(ExdsdevsError::ErrorParseJson(Some(file), _)) if file == path
+        ));
+        write_json_to_file(&path, &serde_json::json!({"version": 0, "inputs": []})).unwrap();
+        assert_eq!(
+            TraceDriver::from_file(&path).err().unwrap().to_string(),
+            format!(
+                "Trace error: {}: trace version 0 is not supported, expected {}",
+                path.display(),
+                TRACE_VERSION
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_trace_in_init_value() {
+        let mut driver = TraceDriver::new();
+        driver.init(
+            &mut Structure::new(&[], &["in"], BTreeMap::new(), &[], &[], &[]),
+            Time::Value(10),
+            &serde_json::json!({"trace": [[20, "in", 2], [5, "in", 1]]}),
+            &Resources::default(),
+            &mut SimRng::seed_from_u64(0),
+        );
+        assert_eq!(driver.next, 1);
+        assert_eq!(
+            driver.state()["trace"],
+            serde_json::json!([[5, "in", 1], [20, "in", 2]])
+        );
+    }
+
+    #[test]
+    fn test_invalid_checkpoint_trace() {
+        let dir = temp_dir("trace_driver_checkpoint");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("checkpoint.json");
+        let root = || {
+            let driver = TraceDriver::new().with_trace(vec![TraceEvent::new(
+                Time::Value(5),
+                "in",
+                1.into(),
+            )]);
+            let harness = driver.harness("recorder", recorder());
+            RootSimulator::from_simulator(harness, Time::Value(0), Time::Value(100))
+        };
+        let mut original = root();
+        original.init();
+        original.save_checkpoint(&path).unwrap();
+
+        let mut checkpoint: Value = read_json_from_file(&path).unwrap();
+        checkpoint["models"]["root/trace_driver"]["state"]["trace"] =
+            serde_json::json!([["soon", "in", 1]]);
+        write_json_to_file(&path, &checkpoint).unwrap();
+        assert!(matches!(
+            RootSimulator::load_checkpoint(&path, root()),
+            Err(ExdsdevsError::ErrorSimTime(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
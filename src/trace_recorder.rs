@@ -0,0 +1,216 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use crate::{
+    containers::{Bag, Value},
+    model::Model,
+    observer::Observer,
+    time::Time,
+    trace_driver::{TraceEvent, TRACE_VERSION},
+};
+
+/// Records the messages a model receives and sends, for regression tests
+/// against golden traces. The result is
+/// `{"version": TRACE_VERSION, "inputs": [...], "outputs": [...]}` with the
+/// messages as `[time, port, value]` events; a `TraceDriver` replays its
+/// inputs.
+pub struct TraceRecorder {
+    inputs: Vec<TraceEvent>,
+    outputs: Vec<TraceEvent>,
+}
+
+impl TraceRecorder {
+    pub fn new() -> Self {
+        TraceRecorder {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    fn record(events: &mut Vec<TraceEvent>, sim_time: Time, bag: &Bag) {
+        events.extend(
+            bag.iter()
+                .map(|msg| TraceEvent::new(sim_time, msg.port(), msg.value().clone())),
+        );
+    }
+}
+
+impl Default for TraceRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Observer for TraceRecorder {
+    fn new() -> Self {
+        TraceRecorder::new()
+    }
+
+    fn init_observer(&mut self, _init_config: &Value) {
+        self.inputs.clear();
+        self.outputs.clear();
+    }
+
+    fn on_outputs(&mut self, _model: &Model, sim_time: Time, bag: &Bag) {
+        Self::record(&mut self.outputs, sim_time, bag);
+    }
+
+    fn before_external_transition(
+        &mut self,
+        _model: &Model,
+        sim_time: Time,
+        x_bag: &Bag,
+        _elapsed: Time,
+    ) {
+        Self::record(&mut self.inputs, sim_time, x_bag);
+    }
+
+    fn before_confluent_transition(
+        &mut self,
+        _model: &Model,
+        sim_time: Time,
+        x_bag: &Bag,
+        _t_last: Time,
+    ) {
+        Self::record(&mut self.inputs, sim_time, x_bag);
+    }
+
+    fn result(&self) -> Option<Value> {
+        Some(serde_json::json!({
+            "version": TRACE_VERSION,
+            "inputs": self.inputs.iter().map(Value::from).collect::<Vec<Value>>(),
+            "outputs": self.outputs.iter().map(Value::from).collect::<Vec<Value>>(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::containers::{BagExt, Msg, SimRng};
+    use crate::dynamic::Dynamic;
+    use crate::errors::ExdsdevsError;
+    use crate::model::Structure;
+    use crate::root_simulator::RootSimulator;
+    use crate::trace_driver::TraceDriver;
+    use std::collections::BTreeMap;
+
+    /// Sends on `out` the sum of the values received so far, at every input.
+    struct Accumulator {
+        sum: i64,
+        pending: bool,
+    }
+
+    impl Dynamic for Accumulator {
+        fn new() -> Self {
+            Accumulator {
+                sum: 0,
+                pending: false,
+            }
+        }
+
+        fn dynamic_type(&self) -> String {
+            "accumulator".to_owned()
+        }
+
+        fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
+            self.pending = false;
+        }
+
+        fn external_transition(
+            &mut self,
+            _: &Structure,
+            _: Time,
+            _: Time,
+            x_bag: &Bag,
+            _: &mut SimRng,
+        ) {
+            self.sum += x_bag
+                .values_on("in")
+                .map(|value| value.as_i64().unwrap())
+                .sum::<i64>();
+            self.pending = true;
+        }
+
+        fn output(&self, _: &Structure, _: Time) -> Bag {
+            vec![Msg::new("out", Value::from(self.sum))]
+        }
+
+        fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+            if self.pending {
+                Time::Value(1)
+            } else {
+                Time::Inf
+            }
+        }
+
+        fn state(&self) -> Value {
+            Value::from(self.sum)
+        }
+    }
+
+    /// Replays `trace` into an accumulator observed by a `TraceRecorder` and
+    /// returns the recorded trace.
+    fn record(trace: &Value) -> Value {
+        let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+        let model = Model::new(structure, Box::new(Accumulator::new()));
+        let mut harness = TraceDriver::from_value(trace)
+            .unwrap()
+            .harness("accumulator", model);
+        harness
+            .model
+            .structure
+            .sub_simulators
+            .get_mut("accumulator")
+            .unwrap()
+            .add_observer(Box::new(TraceRecorder::new()));
+        let mut root = RootSimulator::from_simulator(harness, Time::Value(0), Time::Value(100));
+        root.init();
+        root.run().unwrap();
+        root.results()["root/accumulator"][0].clone()
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let recorded = record(&serde_json::json!([
+            [2, "in", 1],
+            [2, "in", 2],
+            [10, "in", 4]
+        ]));
+        assert_eq!(
+            recorded,
+            serde_json::json!({
+                "version": TRACE_VERSION,
+                "inputs": [[2, "in", 1], [2, "in", 2], [10, "in", 4]],
+                "outputs": [[3, "out", 3], [11, "out", 7]],
+            })
+        );
+        // Replaying the recorded inputs gives the same trace, the golden one.
+        assert_eq!(record(&recorded), recorded);
+    }
+
+    #[test]
+    fn test_unsupported_version() {
+        let trace = serde_json::json!({"version": TRACE_VERSION + 1, "inputs": []});
+        assert_eq!(
+            TraceDriver::from_value(&trace).err(),
+            Some(ExdsdevsError::ErrorTrace(
+                None,
+                format!(
+                    "trace version {} is not supported, expected {}",
+                    TRACE_VERSION + 1,
+                    TRACE_VERSION
+                )
+            ))
+        );
+        assert!(matches!(
+            TraceDriver::from_value(&serde_json::json!({"inputs": []})),
+            Err(ExdsdevsError::ErrorTrace(None, _))
+        ));
+    }
+}
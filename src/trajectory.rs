@@ -0,0 +1,131 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use crate::{containers::Value, model::Model, observer::Observer, time::Time};
+
+/// Records the trajectory of one field of the model state, the value at a JSON
+/// pointer such as `/position/x`, after the initialization and every
+/// transition. The result is the list of `[time, value]` samples; a state where
+/// the pointer does not resolve gives no sample.
+#[derive(Default)]
+pub struct TrajectoryObserver {
+    pointer: String,
+    samples: Vec<Value>,
+}
+
+impl TrajectoryObserver {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The JSON pointer of the recorded field; the empty pointer records the
+    /// whole state.
+    pub fn with_pointer(mut self, pointer: &str) -> Self {
+        self.pointer = pointer.to_owned();
+        self
+    }
+
+    fn sample(&mut self, model: &Model, sim_time: Time) {
+        if let Some(value) = model.state().pointer(&self.pointer) {
+            self.samples
+                .push(Value::Array(vec![Value::from(&sim_time), value.clone()]));
+        }
+    }
+}
+
+impl Observer for TrajectoryObserver {
+    fn new() -> Self {
+        TrajectoryObserver::new()
+    }
+
+    /// Reads the JSON pointer from the `pointer` option of the model class.
+    fn config(&mut self, observer_config: &Value) {
+        if let Some(pointer) = observer_config.get("pointer") {
+            let pointer = pointer
+                .as_str()
+                .unwrap_or_else(|| panic!("Trajectory pointer must be a string, got {}", pointer));
+            self.pointer = pointer.to_owned();
+        }
+    }
+
+    fn init_observer(&mut self, _init_config: &Value) {
+        self.samples.clear();
+    }
+
+    fn on_init(&mut self, model: &Model, init_time: Time, _init_value: &Value, _t_next: Time) {
+        self.sample(model, init_time);
+    }
+
+    fn after_internal_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.sample(model, sim_time);
+    }
+
+    fn after_external_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.sample(model, sim_time);
+    }
+
+    fn after_external_mail_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.sample(model, sim_time);
+    }
+
+    fn after_confluent_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.sample(model, sim_time);
+    }
+
+    fn after_submodels_transition(&mut self, model: &Model, sim_time: Time, _t_next: Time) {
+        self.sample(model, sim_time);
+    }
+
+    fn result(&self) -> Option<Value> {
+        Some(Value::Array(self.samples.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic::Dynamic;
+    use crate::test_utils::{build_root_observed, child_state, Clock};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_trajectory() {
+        let mut root = build_root_observed(
+            vec![("clock", Box::new(Clock::new()))],
+            Time::Value(30),
+            &PathBuf::new(),
+            &|| {
+                vec![
+                    Box::new(TrajectoryObserver::new().with_pointer("/t_next")),
+                    Box::new(TrajectoryObserver::new().with_pointer("/events/1")),
+                ]
+            },
+        );
+        root.run().unwrap();
+
+        let events = child_state(&root, "clock")["events"].clone();
+        let events = events.as_array().unwrap();
+        let results = &root.results()["root/clock"];
+        let t_next = results[0].as_array().unwrap();
+        assert_eq!(t_next.len(), events.len() + 1);
+        assert_eq!(t_next[0][0], Value::from(&Time::Value(0)));
+        assert_eq!(t_next[1][0], events[0]);
+        assert_eq!(t_next[0][1], events[0]);
+        // The second event is only known from the second transition on.
+        let second_event = results[1].as_array().unwrap();
+        assert_eq!(second_event.len(), events.len() - 1);
+        assert!(second_event.iter().all(|sample| sample[1] == events[1]));
+    }
+
+    #[test]
+    fn test_config() {
+        let mut observer = TrajectoryObserver::new();
+        Observer::config(&mut observer, &serde_json::json!({"pointer": "/count"}));
+        assert_eq!(observer.pointer, "/count");
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use std::collections::BTreeMap;
+use std::fs::{DirBuilder, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Map;
+
+use crate::containers::Value;
+use crate::errors::ExdsdevsError;
+
+/// Reads a JSON file, the errors name the file.
+pub fn read_json_from_file<T: DeserializeOwned, P: AsRef<Path>>(
+    file_path: P,
+) -> Result<T, ExdsdevsError> {
+    let file_path = file_path.as_ref();
+    let file = File::open(file_path)
+        .map_err(|err| ExdsdevsError::ErrorIo(Some(file_path.to_owned()), Arc::new(err)))?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|err| ExdsdevsError::ErrorParseJson(Some(file_path.to_owned()), Arc::new(err)))
+}
+
+/// Writes `value` as pretty-printed JSON, creating the missing parent
+/// directories.
+pub fn write_json_to_file<T: Serialize, P: AsRef<Path>>(
+    file_path: P,
+    value: &T,
+) -> Result<(), ExdsdevsError> {
+    let file_path = file_path.as_ref();
+    if let Some(parent_dir) = file_path.parent() {
+        DirBuilder::new().recursive(true).create(parent_dir)?;
+    }
+    let mut writer = BufWriter::new(File::create(file_path)?);
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// SplitMix64 step: mixes `seed` into an unrelated value, such that close
+/// seeds give independent random streams.
+pub(crate) fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Converts the results returned by `RootSimulator::results` into a JSON object
+/// keyed by model full name, holding the observer results in the order the
+/// observers were added.
+pub fn results_to_json(results: &BTreeMap<String, Vec<Value>>) -> Value {
+    Value::Object(
+        results
+            .iter()
+            .map(|(model_full_name, observer_results)| {
+                (
+                    model_full_name.clone(),
+                    Value::Array(observer_results.clone()),
+                )
+            })
+            .collect::<Map<String, Value>>(),
+    )
+}
+
+/// Default cap of `cartesian_product`.
+pub const MAX_CARTESIAN_PRODUCT: usize = 1_000_000;
+
+/// Enumerates every combination of the parameter values, the first parameter
+/// varies the slowest. Fails when a parameter has no values or there are more
+/// than `MAX_CARTESIAN_PRODUCT` combinations, see
+/// `cartesian_product_capped`.
+pub fn cartesian_product(
+    params: BTreeMap<String, Vec<Value>>,
+) -> Result<Vec<BTreeMap<String, Value>>, ExdsdevsError> {
+    cartesian_product_capped(params, MAX_CARTESIAN_PRODUCT)
+}
+
+/// Same as `cartesian_product` with at most `max_combinations` combinations.
+pub fn cartesian_product_capped(
+    params: BTreeMap<String, Vec<Value>>,
+    max_combinations: usize,
+) -> Result<Vec<BTreeMap<String, Value>>, ExdsdevsError> {
+    let mut count: usize = 1;
+    for (param, values) in &params {
+        if values.is_empty() {
+            return Err(ExdsdevsError::ErrorCartesian(format!(
+                "parameter '{}' has no values",
+                param
+            )));
+        }
+        count = count
+            .checked_mul(values.len())
+            .filter(|count| *count <= max_combinations)
+            .ok_or_else(|| {
+                ExdsdevsError::ErrorCartesian(format!(
+                    "the parameters {:?} have more than {} combinations",
+                    params.keys().collect::<Vec<_>>(),
+                    max_combinations
+                ))
+            })?;
+    }
+
+    let mut combinations = Vec::with_capacity(count);
+    combinations.push(BTreeMap::new());
+    for (param, values) in &params {
+        combinations = combinations
+            .iter()
+            .flat_map(|combination| {
+                values.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.insert(param.clone(), value.clone());
+                    combination
+                })
+            })
+            .collect();
+    }
+    Ok(combinations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+    use crate::test_utils::{build_root_observed, temp_dir, Clock, Passive};
+    use crate::{dynamic::Dynamic, time::Time};
+
+    fn params(params: &[(&str, Value)]) -> BTreeMap<String, Vec<Value>> {
+        params
+            .iter()
+            .map(|(param, values)| ((*param).to_owned(), values.as_array().unwrap().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_cartesian_product() {
+        let combinations = cartesian_product(params(&[
+            ("a", serde_json::json!([1, 2])),
+            ("b", serde_json::json!(["x", "y", "z"])),
+        ]))
+        .unwrap();
+        let combinations: Vec<Value> = combinations
+            .into_iter()
+            .map(|combination| serde_json::to_value(combination).unwrap())
+            .collect();
+        assert_eq!(
+            combinations,
+            vec![
+                serde_json::json!({"a": 1, "b": "x"}),
+                serde_json::json!({"a": 1, "b": "y"}),
+                serde_json::json!({"a": 1, "b": "z"}),
+                serde_json::json!({"a": 2, "b": "x"}),
+                serde_json::json!({"a": 2, "b": "y"}),
+                serde_json::json!({"a": 2, "b": "z"}),
+            ]
+        );
+
+        let three = params(&[
+            ("a", serde_json::json!([1, 2])),
+            ("b", serde_json::json!([true, false])),
+            ("c", serde_json::json!([0.5, 1.5, 2.5])),
+        ]);
+        let combinations = cartesian_product(three.clone()).unwrap();
+        assert_eq!(combinations.len(), 12);
+        assert_eq!(
+            serde_json::to_value(&combinations[7]).unwrap(),
+            serde_json::json!({"a": 2, "b": true, "c": 1.5})
+        );
+        assert_eq!(
+            cartesian_product_capped(three.clone(), 12).unwrap().len(),
+            12
+        );
+        assert_eq!(
+            cartesian_product_capped(three, 11),
+            Err(ExdsdevsError::ErrorCartesian(
+                "the parameters [\"a\", \"b\", \"c\"] have more than 11 combinations".to_owned()
+            ))
+        );
+
+        assert_eq!(
+            cartesian_product(BTreeMap::new()).unwrap(),
+            vec![BTreeMap::new()]
+        );
+        assert_eq!(
+            cartesian_product(params(&[
+                ("a", serde_json::json!([1])),
+                ("b", serde_json::json!([]))
+            ])),
+            Err(ExdsdevsError::ErrorCartesian(
+                "parameter 'b' has no values".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_results_to_json() {
+        let sim_dir = temp_dir("utils_results");
+        let mut root = build_root_observed(
+            vec![
+                ("clock", Box::new(Clock::new())),
+                ("passive", Box::new(Passive::new())),
+            ],
+            Time::Value(10),
+            &sim_dir,
+            &|| vec![Box::new(Metrics::new())],
+        );
+        root.run().unwrap();
+        let results = results_to_json(&root.results());
+        let results = results.as_object().unwrap();
+        assert_eq!(
+            results.keys().collect::<Vec<_>>(),
+            vec!["root/clock", "root/passive"]
+        );
+        assert_eq!(
+            results["root/clock"],
+            Value::Array(root.results()["root/clock"].clone())
+        );
+
+        let file_path = sim_dir.join("nested/results.json");
+        write_json_to_file(&file_path, results).unwrap();
+        let read: Value = read_json_from_file(&file_path).unwrap();
+        assert_eq!(&read, &Value::Object(results.clone()));
+        assert!(matches!(
+            read_json_from_file::<Value, _>(sim_dir.join("missing.json")),
+            Err(ExdsdevsError::ErrorIo(Some(path), err))
+                if path == sim_dir.join("missing.json") && err.kind() == std::io::ErrorKind::NotFound
+        ));
+        std::fs::remove_dir_all(&sim_dir).unwrap();
+    }
+}
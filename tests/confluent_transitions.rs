@@ -0,0 +1,181 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+//! Two pulse generators with the same period are coupled to each other, so at
+//! every period each one is internally imminent and receives the output of
+//! the other at the same time: the simulator must make them take confluent
+//! transitions rather than an internal and an external one.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use exdsdevs::{
+    containers::{Bag, BagExt, Msg, SimRng, Value},
+    dynamic::Dynamic,
+    model::{Model, Resources, Structure},
+    observer::Observer,
+    root_simulator::RootSimulator,
+    simulator::Simulator,
+    time::Time,
+};
+
+const PERIOD: i128 = 5;
+
+/// Emits its pulse count every `PERIOD` and records what it went through.
+struct Pulse {
+    pulses: i64,
+    received: Vec<Value>,
+    transitions: Vec<String>,
+}
+
+impl Dynamic for Pulse {
+    fn new() -> Self {
+        Pulse {
+            pulses: 0,
+            received: Vec::new(),
+            transitions: Vec::new(),
+        }
+    }
+
+    fn dynamic_type(&self) -> String {
+        "pulse".to_owned()
+    }
+
+    fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
+        self.pulses += 1;
+        self.transitions.push("internal".to_owned());
+    }
+
+    fn external_transition(
+        &mut self,
+        _: &Structure,
+        _: Time,
+        _: Time,
+        x_bag: &Bag,
+        _: &mut SimRng,
+    ) {
+        self.received.extend(x_bag.values_on("in").cloned());
+        self.transitions.push("external".to_owned());
+    }
+
+    fn confluent_transition(
+        &mut self,
+        _: &mut Structure,
+        _: Time,
+        _: Time,
+        x_bag: &Bag,
+        _: &mut SimRng,
+    ) {
+        self.pulses += 1;
+        self.received.extend(x_bag.values_on("in").cloned());
+        self.transitions.push("confluent".to_owned());
+    }
+
+    fn output(&self, _: &Structure, _: Time) -> Bag {
+        vec![Msg::new("out", Value::from(self.pulses + 1))]
+    }
+
+    fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+        Time::Value(PERIOD)
+    }
+
+    fn state(&self) -> Value {
+        serde_json::json!({
+            "pulses": self.pulses,
+            "received": self.received,
+            "transitions": self.transitions,
+        })
+    }
+}
+
+/// Counts the confluent transitions reported to observers.
+struct ConfluentCount(Arc<Mutex<Vec<(Time, usize)>>>);
+
+impl Observer for ConfluentCount {
+    fn new() -> Self {
+        ConfluentCount(Arc::default())
+    }
+
+    fn before_confluent_transition(&mut self, _: &Model, sim_time: Time, x_bag: &Bag, _: Time) {
+        self.0.lock().unwrap().push((sim_time, x_bag.len()));
+    }
+}
+
+fn pulse(full_name: &str, confluent: &Arc<Mutex<Vec<(Time, usize)>>>) -> Simulator {
+    let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+    Simulator::new(
+        full_name,
+        Model::new(structure, Box::new(Pulse::new())),
+        Resources::default(),
+    )
+    .with_observer(Box::new(ConfluentCount(confluent.clone())))
+}
+
+struct Coupled;
+
+impl Dynamic for Coupled {
+    fn new() -> Self {
+        Coupled
+    }
+
+    fn dynamic_type(&self) -> String {
+        "coupled".to_owned()
+    }
+
+    fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+        Time::Inf
+    }
+
+    fn state(&self) -> Value {
+        Value::Null
+    }
+}
+
+#[test]
+fn test_simultaneous_input_and_internal_event_is_confluent() {
+    let confluent = Arc::new(Mutex::new(Vec::new()));
+    let mut submodels = BTreeMap::new();
+    submodels.insert("a".to_owned(), pulse("root/a", &confluent));
+    submodels.insert("b".to_owned(), pulse("root/b", &confluent));
+    let structure = Structure::new(
+        &[],
+        &[],
+        submodels,
+        &[],
+        &[("a", "out", "b", "in"), ("b", "out", "a", "in")],
+        &[],
+    );
+    let root = Simulator::new(
+        "root",
+        Model::new(structure, Box::new(Coupled)),
+        Resources::default(),
+    );
+    let mut root = RootSimulator::new_with_seed(root, Time::Value(0), Time::Value(18), 0, 0);
+    root.init();
+    root.run().unwrap();
+
+    let snapshot = root.snapshot();
+    for name in ["root/a", "root/b"] {
+        let state = &snapshot.find(name).unwrap().state;
+        assert_eq!(state["pulses"], Value::from(3), "{}", name);
+        assert_eq!(
+            state["transitions"],
+            serde_json::json!(["confluent", "confluent", "confluent"]),
+            "{}",
+            name
+        );
+        assert_eq!(state["received"], serde_json::json!([1, 2, 3]), "{}", name);
+    }
+    let expected: Vec<(Time, usize)> = [1, 1, 2, 2, 3, 3]
+        .iter()
+        .map(|period| (Time::Value(period * PERIOD), 1))
+        .collect();
+    assert_eq!(*confluent.lock().unwrap(), expected);
+}
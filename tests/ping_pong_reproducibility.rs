@@ -0,0 +1,101 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use exdsdevs::{
+    dynamic::{DynamicFactory, DynamicFactoryStorage},
+    experiment::Experiment,
+    logger::Logger,
+    observer::{ObserverFactory, ObserverFactoryStorage},
+};
+
+#[path = "../examples/ping_pong/ping_pong.rs"]
+mod ping_pong;
+
+use ping_pong::{AgentDynamic, RootDynamic};
+
+/// Writes an experiment on the ping_pong example model into `dir` and returns
+/// its path.
+fn write_experiment(dir: &Path, random_seed: u64) -> PathBuf {
+    fs::create_dir_all(dir).unwrap();
+    let model_directory =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/ping_pong/ping_pong_model");
+    let experiment = serde_json::json!({
+        "name": "Ping-pong reproducibility",
+        "results_directory": "results",
+        "model_directory": model_directory,
+        "root_model_class": "ping-pong",
+        "init_time": "0",
+        "finish_time": "100",
+        "random_seed": random_seed,
+        "iterations": 2,
+        "global_resources": {}
+    });
+    let experiment_path = dir.join("experiment.json");
+    fs::write(&experiment_path, experiment.to_string()).unwrap();
+    experiment_path
+}
+
+fn create_experiment(experiment_path: &Path) -> Experiment {
+    let dynamic_factory = DynamicFactoryStorage::new()
+        .with_dynamic_factory("root", DynamicFactory::<RootDynamic>::new())
+        .with_dynamic_factory("agent", DynamicFactory::<AgentDynamic>::new());
+    let observer_factory = ObserverFactoryStorage::new()
+        .with_observer_factory("std_logger", ObserverFactory::<Logger>::new());
+    Experiment::new(experiment_path, dynamic_factory, observer_factory)
+}
+
+/// Contents of every file under `dir`, keyed by path relative to `dir`.
+fn read_tree(dir: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+    let mut files = BTreeMap::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current_dir) = dirs.pop() {
+        for entry in fs::read_dir(&current_dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else {
+                let relative_path = path.strip_prefix(dir).unwrap().to_path_buf();
+                files.insert(relative_path, fs::read(&path).unwrap());
+            }
+        }
+    }
+    files
+}
+
+#[test]
+fn test_same_seed_gives_identical_logs() {
+    let dir = std::env::temp_dir().join(format!(
+        "exdsdevs_ping_pong_reproducibility_{}",
+        std::process::id()
+    ));
+    let run = |name: &str, random_seed: u64, multi_thread: bool| {
+        let run_dir = dir.join(name);
+        let mut experiment = create_experiment(&write_experiment(&run_dir, random_seed));
+        if multi_thread {
+            experiment.run_multi_thread().unwrap();
+        } else {
+            experiment.run_single_thread().unwrap();
+        }
+        read_tree(&run_dir.join("results"))
+    };
+
+    let single_thread = run("single_thread", 123, false);
+    assert!(single_thread
+        .keys()
+        .any(|path| path.ends_with("var_1/iter_1/root/agent_5.log")));
+    assert!(single_thread.values().all(|log| !log.is_empty()));
+    assert_eq!(single_thread, run("single_thread_again", 123, false));
+    assert_eq!(single_thread, run("multi_thread", 123, true));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,246 @@
+// Copyright 2023 Developers of the exdsdevs project.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// https://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms
+
+//! A pulse generator feeds a doubler whose outputs are computed from its
+//! inputs by `output_with_input`, the doubled values reach a sink at the time
+//! of the pulses. The doubler also has an internal event due with the second
+//! pulse: its `output` comes first, then the confluent transition and the
+//! reaction to the pulse.
+
+use std::collections::BTreeMap;
+
+use exdsdevs::{
+    containers::{Bag, BagExt, Msg, SimRng, Value},
+    dynamic::Dynamic,
+    model::{Model, Resources, Structure},
+    root_simulator::RootSimulator,
+    simulator::Simulator,
+    time::Time,
+};
+
+const PERIOD: i128 = 5;
+
+/// Emits its pulse count every `PERIOD`.
+struct Pulse {
+    pulses: i64,
+}
+
+impl Dynamic for Pulse {
+    fn new() -> Self {
+        Pulse { pulses: 0 }
+    }
+
+    fn dynamic_type(&self) -> String {
+        "pulse".to_owned()
+    }
+
+    fn internal_transition(&mut self, _: &mut Structure, _: Time, _: &mut SimRng) {
+        self.pulses += 1;
+    }
+
+    fn output(&self, _: &Structure, _: Time) -> Bag {
+        vec![Msg::new("out", Value::from(self.pulses + 1))]
+    }
+
+    fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+        Time::Value(PERIOD)
+    }
+
+    fn state(&self) -> Value {
+        Value::from(self.pulses)
+    }
+}
+
+/// Doubles the values it receives and records its transitions; its only
+/// internal event is due at `2 * PERIOD`.
+struct Doubler {
+    now: Time,
+    due: Time,
+    transitions: Vec<String>,
+}
+
+impl Dynamic for Doubler {
+    fn new() -> Self {
+        Doubler {
+            now: Time::Value(0),
+            due: Time::Value(2 * PERIOD),
+            transitions: Vec::new(),
+        }
+    }
+
+    fn dynamic_type(&self) -> String {
+        "doubler".to_owned()
+    }
+
+    fn internal_transition(&mut self, _: &mut Structure, sim_time: Time, _: &mut SimRng) {
+        self.now = sim_time;
+        self.due = Time::Inf;
+        self.transitions.push("internal".to_owned());
+    }
+
+    fn external_transition(
+        &mut self,
+        _: &Structure,
+        sim_time: Time,
+        _: Time,
+        _: &Bag,
+        _: &mut SimRng,
+    ) {
+        self.now = sim_time;
+        self.transitions.push("external".to_owned());
+    }
+
+    fn confluent_transition(
+        &mut self,
+        _: &mut Structure,
+        sim_time: Time,
+        _: Time,
+        _: &Bag,
+        _: &mut SimRng,
+    ) {
+        self.now = sim_time;
+        self.due = Time::Inf;
+        self.transitions.push("confluent".to_owned());
+    }
+
+    fn output(&self, _: &Structure, _: Time) -> Bag {
+        vec![Msg::new("out", Value::from("due"))]
+    }
+
+    fn output_with_input(&self, _: &Structure, _: Time, x_bag: &Bag) -> Bag {
+        x_bag
+            .values_on("in")
+            .map(|value| Msg::new("out", Value::from(value.as_i64().unwrap() * 2)))
+            .collect()
+    }
+
+    fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+        self.due - self.now
+    }
+
+    fn state(&self) -> Value {
+        Value::from(self.transitions.clone())
+    }
+}
+
+/// Records the time and value of every message it receives.
+struct Sink {
+    received: Vec<Value>,
+}
+
+impl Dynamic for Sink {
+    fn new() -> Self {
+        Sink {
+            received: Vec::new(),
+        }
+    }
+
+    fn dynamic_type(&self) -> String {
+        "sink".to_owned()
+    }
+
+    fn external_transition(
+        &mut self,
+        _: &Structure,
+        sim_time: Time,
+        _: Time,
+        x_bag: &Bag,
+        _: &mut SimRng,
+    ) {
+        for value in x_bag.values_on("in") {
+            self.received
+                .push(Value::Array(vec![Value::from(&sim_time), value.clone()]));
+        }
+    }
+
+    fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+        Time::Inf
+    }
+
+    fn state(&self) -> Value {
+        Value::from(self.received.clone())
+    }
+}
+
+struct Coupled;
+
+impl Dynamic for Coupled {
+    fn new() -> Self {
+        Coupled
+    }
+
+    fn dynamic_type(&self) -> String {
+        "coupled".to_owned()
+    }
+
+    fn time_advance(&self, _: &Structure, _: &mut SimRng) -> Time {
+        Time::Inf
+    }
+
+    fn state(&self) -> Value {
+        Value::Null
+    }
+}
+
+fn atomic(full_name: &str, dynamic: Box<dyn Dynamic>) -> Simulator {
+    let structure = Structure::new(&["in"], &["out"], BTreeMap::new(), &[], &[], &[]);
+    Simulator::new(
+        full_name,
+        Model::new(structure, dynamic),
+        Resources::default(),
+    )
+}
+
+#[test]
+fn test_outputs_react_to_inputs_at_the_same_time() {
+    let mut submodels = BTreeMap::new();
+    submodels.insert(
+        "pulse".to_owned(),
+        atomic("root/pulse", Box::new(Pulse::new())),
+    );
+    submodels.insert(
+        "doubler".to_owned(),
+        atomic("root/doubler", Box::new(Doubler::new())),
+    );
+    submodels.insert(
+        "sink".to_owned(),
+        atomic("root/sink", Box::new(Sink::new())),
+    );
+    let structure = Structure::new(
+        &[],
+        &[],
+        submodels,
+        &[],
+        &[
+            ("pulse", "out", "doubler", "in"),
+            ("doubler", "out", "sink", "in"),
+        ],
+        &[],
+    );
+    let root = Simulator::new(
+        "root",
+        Model::new(structure, Box::new(Coupled)),
+        Resources::default(),
+    );
+    let mut root = RootSimulator::new_with_seed(root, Time::Value(0), Time::Value(18), 0, 0);
+    root.init();
+    root.run().unwrap();
+
+    let snapshot = root.snapshot();
+    assert_eq!(
+        snapshot.find("root/sink").unwrap().state,
+        serde_json::json!([[5, 2], [10, "due"], [10, 4], [15, 6]])
+    );
+    // The reactions make no transition of their own.
+    let doubler = snapshot.find("root/doubler").unwrap();
+    assert_eq!(
+        doubler.state,
+        serde_json::json!(["external", "confluent", "external"])
+    );
+    assert_eq!(doubler.t_next, Time::Inf);
+}